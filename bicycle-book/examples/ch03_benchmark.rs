@@ -1,26 +1,137 @@
 use std::env;
+use std::error::Error;
+use std::fmt::Debug;
+use std::fs::File;
+use std::hash::Hash;
+use std::io::{self, BufWriter, Write};
 use std::str::FromStr;
 
-use bicycle_book::ch03::fourth::sort as par_sort;
+use bicycle_book::ch03::fourth::{calibrate, BitonicSort};
+use bicycle_book::ch03::merge::MergeSort;
+use bicycle_book::ch03::quick::QuickSort;
+use bicycle_book::ch03::radix::RadixSort;
+use bicycle_book::ch03::simd::{Backend, SimdBitonicSort};
 use bicycle_book::ch03::third::sort as seq_sort;
-use bicycle_book::ch03::util::{is_sorted, new_u32_vec};
-use bicycle_book::ch03::SortOrder;
+use bicycle_book::ch03::util::{
+    assert_permutation, assert_sorted, fingerprint, is_sorted, new_payload_vec, new_string_vec,
+    new_u32_vec, new_u32_vec_with, Distribution,
+};
+use bicycle_book::ch03::{SortConfig, SortOrder, Sorter};
+use rayon::ThreadPoolBuilder;
 use std::time::Instant;
 
+const SWEEP_USAGE: &str = "Usage: ch03_benchmark --sweep [OPTIONS]
+
+Options:
+  --sizes BITS,...          numbers of elements in bits (default: 12,16,20)
+  --threads N,...           numbers of threads (default: 1 and the number of logical cores)
+  --dists NAME,...          input distributions (default: all)
+  --payloads NAME,...       u32, string or struct (default: u32)
+  --algorithms NAME,...     algorithms to run (default: all available for the payload)
+  --repeat N                runs per measurement; the median is reported (default: 3)
+  --csv FILE                write the results as CSV
+  --json FILE               write the results as JSON";
+
+/// 並列化していないバイトニックソート。速度向上の比較の基準にする
+struct SeqBitonicSort;
+
+impl<T: Ord> Sorter<T> for SeqBitonicSort {
+    fn name(&self) -> &'static str {
+        "seq_bitonic"
+    }
+
+    fn is_stable(&self) -> bool {
+        false
+    }
+
+    fn sort(&self, x: &mut [T], order: &SortOrder) {
+        seq_sort(x, order).expect("Failed to sort: ");
+    }
+}
+
+/// 標準ライブラリの安定ソート
+struct StdSort;
+
+impl<T: Ord> Sorter<T> for StdSort {
+    fn name(&self) -> &'static str {
+        "std"
+    }
+
+    fn is_stable(&self) -> bool {
+        true
+    }
+
+    fn sort(&self, x: &mut [T], order: &SortOrder) {
+        match *order {
+            SortOrder::Ascending => x.sort(),
+            SortOrder::Descending => x.sort_by(|a, b| b.cmp(a)),
+        }
+    }
+}
+
+fn sorters() -> Vec<Box<dyn Sorter<u32> + Sync>> {
+    vec![
+        Box::new(SeqBitonicSort),
+        Box::new(BitonicSort),
+        Box::new(SimdBitonicSort),
+        Box::new(MergeSort),
+        Box::new(QuickSort),
+        Box::new(RadixSort),
+        Box::new(StdSort),
+    ]
+}
+
+/// 比較だけでソートするアルゴリズム。どの要素型にも使える
+fn comparison_sorters<T: Ord + Clone + Send + Sync>() -> Vec<Box<dyn Sorter<T> + Sync>> {
+    vec![
+        Box::new(SeqBitonicSort),
+        Box::new(BitonicSort),
+        Box::new(MergeSort),
+        Box::new(QuickSort),
+        Box::new(StdSort),
+    ]
+}
+
 fn main() {
-    if let Some(n) = env::args().nth(1) {
+    if env::args().nth(1).as_deref() == Some("--calibrate") {
+        run_calibration();
+    } else if env::args().nth(1).as_deref() == Some("--sweep") {
+        let options = parse_sweep_options().unwrap_or_else(|e| {
+            eprintln!("error: {}\n\n{}", e, SWEEP_USAGE);
+            std::process::exit(2);
+        });
+        if let Err(e) = run_sweep(&options) {
+            eprintln!("error: {}", e);
+            std::process::exit(1);
+        }
+    } else if let Some(n) = env::args().nth(1) {
         let bits = u32::from_str(&n).expect("error parsing argument");
-        run_sorts(bits);
+        let names = env::args().skip(2).collect::<Vec<_>>();
+
+        let mut sorters = sorters();
+        if !names.is_empty() {
+            if let Some(name) = names
+                .iter()
+                .find(|name| sorters.iter().all(|s| s.name() != name.as_str()))
+            {
+                eprintln!("unknown algorithm: {}", name);
+                std::process::exit(1);
+            }
+            sorters.retain(|s| names.iter().any(|name| name == s.name()));
+        }
+        run_sorts(bits, &sorters);
     } else {
+        let names = sorters().iter().map(|s| s.name()).collect::<Vec<_>>();
         eprintln!(
-            "Usage {} <number of elements in bits>",
-            env::args().nth(0).unwrap()
+            "Usage {0} <number of elements in bits> [algorithm...]\n      {0} --calibrate\n      {0} --sweep [OPTIONS]\nalgorithms: {1}",
+            env::args().next().unwrap(),
+            names.join(", ")
         );
         std::process::exit(1);
     }
 }
 
-fn run_sorts(bits: u32) {
+fn run_sorts(bits: u32, sorters: &[Box<dyn Sorter<u32> + Sync>]) {
     let len = 2.0_f64.powi(bits as i32) as usize;
     println!(
         "sorting {} integers ({:.1} MB)",
@@ -32,27 +143,73 @@ fn run_sorts(bits: u32) {
         num_cpus::get_physical(),
         num_cpus::get()
     );
+    println!("simd backend: {}", Backend::detect().name());
 
-    let seq_duration = times_sort(&seq_sort, len, "seq_sort");
-    let par_duration = times_sort(&par_sort, len, "par_sort");
+    let durations = sorters
+        .iter()
+        .map(|sorter| times_sort(sorter.as_ref(), len))
+        .collect::<Vec<_>>();
 
-    println!("speed up: {:.2}x", seq_duration / par_duration);
+    // 最初のアルゴリズムを基準にした速度向上を表示する
+    if let Some(base) = durations.first() {
+        for (sorter, duration) in sorters.iter().zip(&durations).skip(1) {
+            println!("{}: speed up {:.2}x", sorter.name(), base / duration);
+        }
+    }
+
+    // SIMD 命令による速度向上を、同じ手順のバイトニックソートと比べて表示する
+    let duration_of = |name: &str| {
+        sorters
+            .iter()
+            .zip(&durations)
+            .find(|(sorter, _)| sorter.name() == name)
+            .map(|(_, duration)| *duration)
+    };
+    if let (Some(scalar), Some(simd)) = (duration_of("bitonic"), duration_of("simd_bitonic")) {
+        println!("simd_bitonic: speed up {:.2}x over bitonic", scalar / simd);
+    }
 }
 
-fn times_sort<F>(sorter: &F, len: usize, name: &str) -> f64
-where
-    F: Fn(&mut [u32], &SortOrder) -> Result<(), String>,
-{
+/// このマシンで計測したしきい値の候補ごとの時間と、推奨する設定を表示する
+fn run_calibration() {
+    println!(
+        "cpu info: {} physical cores, {} logical cores",
+        num_cpus::get_physical(),
+        num_cpus::get()
+    );
+
+    let calibration = calibrate(&SortConfig::default());
+    println!("calibrated with {} threads", calibration.threads);
+    for (name, timings) in &[
+        ("sort_threshold", &calibration.sort_timings),
+        ("merge_threshold", &calibration.merge_timings),
+    ] {
+        for (threshold, time) in timings.iter() {
+            println!(
+                "{} {:>6}: {:.4} seconds",
+                name,
+                threshold,
+                time.as_secs_f64()
+            );
+        }
+    }
+    println!(
+        "suggested: sort_threshold = {}, merge_threshold = {}",
+        calibration.config.sort_threshold, calibration.config.merge_threshold
+    );
+}
+
+fn times_sort(sorter: &dyn Sorter<u32>, len: usize) -> f64 {
     let mut x = new_u32_vec(len);
 
     let start = Instant::now();
-    sorter(&mut x, &SortOrder::Ascending).expect("Failed to sort: ");
+    sorter.sort(&mut x, &SortOrder::Ascending);
     let dur = start.elapsed();
 
     let nano_secs = dur.subsec_nanos() as f64 + dur.as_secs() as f64 * 1e9_f64;
     println!(
         "{}: sorted {} integers in {} seconds",
-        name,
+        sorter.name(),
         len,
         nano_secs / 1e9
     );
@@ -61,3 +218,240 @@ where
 
     nano_secs
 }
+
+/// `--sweep` の設定
+struct SweepOptions {
+    sizes: Vec<u32>,
+    threads: Vec<usize>,
+    dists: Vec<Distribution>,
+    payloads: Vec<String>,
+    algorithms: Vec<String>,
+    repeat: usize,
+    csv: Option<String>,
+    json: Option<String>,
+}
+
+/// 1 つの組み合わせの計測結果
+struct Measurement {
+    payload: &'static str,
+    distribution: &'static str,
+    len: usize,
+    threads: usize,
+    algorithm: &'static str,
+    stable: bool,
+    /// repeat 回の計測の中央値
+    seconds: f64,
+}
+
+const PAYLOADS: [&str; 3] = ["u32", "string", "struct"];
+
+/// `a,b,c` のようなカンマ区切りの値を解釈する
+fn parse_list<T>(s: &str) -> Result<Vec<T>, Box<dyn Error>>
+where
+    T: FromStr,
+    T::Err: Error + 'static,
+{
+    s.split(',')
+        .map(|item| item.trim().parse().map_err(|e: T::Err| e.into()))
+        .collect()
+}
+
+fn parse_sweep_options() -> Result<SweepOptions, Box<dyn Error>> {
+    let mut threads = vec![1, num_cpus::get()];
+    threads.dedup();
+    let mut options = SweepOptions {
+        sizes: vec![12, 16, 20],
+        threads,
+        dists: Distribution::ALL.to_vec(),
+        payloads: vec!["u32".to_string()],
+        algorithms: Vec::new(),
+        repeat: 3,
+        csv: None,
+        json: None,
+    };
+
+    let mut iter = env::args().skip(2);
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| format!("{} requires a value", arg))
+        };
+        match arg.as_str() {
+            "--sizes" => options.sizes = parse_list(&value()?)?,
+            "--threads" => options.threads = parse_list(&value()?)?,
+            "--dists" => options.dists = parse_list(&value()?)?,
+            "--payloads" => options.payloads = parse_list(&value()?)?,
+            "--algorithms" => options.algorithms = parse_list(&value()?)?,
+            "--repeat" => options.repeat = value()?.parse()?,
+            "--csv" => options.csv = Some(value()?),
+            "--json" => options.json = Some(value()?),
+            "-h" | "--help" => {
+                println!("{}", SWEEP_USAGE);
+                std::process::exit(0);
+            }
+            _ => return Err(format!("unknown option: {}", arg).into()),
+        }
+    }
+
+    if let Some(bits) = options.sizes.iter().find(|&&bits| bits > 32) {
+        return Err(format!("size too large: {} bits", bits).into());
+    }
+    if options.threads.contains(&0) {
+        return Err("the number of threads must be positive".into());
+    }
+    if options.repeat == 0 {
+        return Err("--repeat must be positive".into());
+    }
+    if let Some(payload) = options
+        .payloads
+        .iter()
+        .find(|payload| !PAYLOADS.contains(&payload.as_str()))
+    {
+        return Err(format!("unknown payload: {}", payload).into());
+    }
+    // u32 に使えるアルゴリズムが全てのアルゴリズムを含む
+    let names = sorters().iter().map(|s| s.name()).collect::<Vec<_>>();
+    if let Some(name) = options
+        .algorithms
+        .iter()
+        .find(|name| !names.contains(&name.as_str()))
+    {
+        return Err(format!("unknown algorithm: {}", name).into());
+    }
+    Ok(options)
+}
+
+/// 要素数、分布、スレッド数、アルゴリズムの全ての組み合わせを計測して、結果を書き出す
+fn run_sweep(options: &SweepOptions) -> Result<(), Box<dyn Error>> {
+    println!(
+        "cpu info: {} physical cores, {} logical cores",
+        num_cpus::get_physical(),
+        num_cpus::get()
+    );
+    println!("simd backend: {}", Backend::detect().name());
+
+    let mut results = Vec::new();
+    for payload in &options.payloads {
+        match payload.as_str() {
+            "u32" => sweep("u32", sorters(), new_u32_vec_with, options, &mut results)?,
+            "string" => sweep(
+                "string",
+                comparison_sorters(),
+                new_string_vec,
+                options,
+                &mut results,
+            )?,
+            "struct" => sweep(
+                "struct",
+                comparison_sorters(),
+                new_payload_vec,
+                options,
+                &mut results,
+            )?,
+            _ => unreachable!(),
+        }
+    }
+
+    if let Some(path) = &options.csv {
+        write_csv(&mut BufWriter::new(File::create(path)?), &results)?;
+    }
+    if let Some(path) = &options.json {
+        write_json(&mut BufWriter::new(File::create(path)?), &results)?;
+    }
+    Ok(())
+}
+
+fn sweep<T: Clone + Ord + Hash + Debug + Send + Sync>(
+    payload: &'static str,
+    mut sorters: Vec<Box<dyn Sorter<T> + Sync>>,
+    generate: fn(usize, Distribution) -> Vec<T>,
+    options: &SweepOptions,
+    results: &mut Vec<Measurement>,
+) -> Result<(), Box<dyn Error>> {
+    if !options.algorithms.is_empty() {
+        sorters.retain(|s| options.algorithms.iter().any(|name| name == s.name()));
+    }
+
+    for &bits in &options.sizes {
+        let len = 1_usize << bits;
+        for &dist in &options.dists {
+            let input = generate(len, dist);
+            let expected = fingerprint(&input);
+            for &threads in &options.threads {
+                let pool = ThreadPoolBuilder::new().num_threads(threads).build()?;
+                for sorter in &sorters {
+                    let sorter = sorter.as_ref();
+                    let mut times = (0..options.repeat)
+                        .map(|_| {
+                            let mut x = input.clone();
+                            let start = Instant::now();
+                            pool.install(|| sorter.sort(&mut x, &SortOrder::Ascending));
+                            let seconds = start.elapsed().as_secs_f64();
+                            assert_sorted(&x, &SortOrder::Ascending);
+                            assert_permutation(&expected, &x);
+                            seconds
+                        })
+                        .collect::<Vec<_>>();
+                    times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+                    let measurement = Measurement {
+                        payload,
+                        distribution: dist.name(),
+                        len,
+                        threads,
+                        algorithm: sorter.name(),
+                        stable: sorter.is_stable(),
+                        seconds: times[times.len() / 2],
+                    };
+                    println!(
+                        "{} {} len={} threads={} {}: {:.6} seconds",
+                        measurement.payload,
+                        measurement.distribution,
+                        measurement.len,
+                        measurement.threads,
+                        measurement.algorithm,
+                        measurement.seconds
+                    );
+                    results.push(measurement);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_csv(w: &mut impl Write, results: &[Measurement]) -> io::Result<()> {
+    writeln!(
+        w,
+        "payload,distribution,len,threads,algorithm,stable,seconds"
+    )?;
+    for m in results {
+        writeln!(
+            w,
+            "{},{},{},{},{},{},{:.9}",
+            m.payload, m.distribution, m.len, m.threads, m.algorithm, m.stable, m.seconds
+        )?;
+    }
+    w.flush()
+}
+
+fn write_json(w: &mut impl Write, results: &[Measurement]) -> io::Result<()> {
+    // 名前は全て ASCII の識別子なので、Debug 表示がそのまま JSON の文字列になる
+    writeln!(w, "[")?;
+    for (i, m) in results.iter().enumerate() {
+        writeln!(
+            w,
+            "  {{\"payload\": {:?}, \"distribution\": {:?}, \"len\": {}, \"threads\": {}, \"algorithm\": {:?}, \"stable\": {}, \"seconds\": {:.9}}}{}",
+            m.payload,
+            m.distribution,
+            m.len,
+            m.threads,
+            m.algorithm,
+            m.stable,
+            m.seconds,
+            if i + 1 < results.len() { "," } else { "" }
+        )?;
+    }
+    writeln!(w, "]")?;
+    w.flush()
+}
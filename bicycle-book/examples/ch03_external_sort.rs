@@ -0,0 +1,128 @@
+use bicycle_book::ch03::external::{sort, ExternalSortConfig, RecordFormat};
+use bicycle_book::ch03::SortOrder;
+use rayon::ThreadPoolBuilder;
+use std::env;
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::Arc;
+
+const USAGE: &str = "Usage: ch03_external_sort [OPTIONS] [INPUT [OUTPUT]]
+
+Sorts INPUT (or stdin) into OUTPUT (or stdout) using temporary files.
+
+Options:
+  -m, --memory SIZE      memory budget, e.g. 512K, 64M, 2G (default: 256M)
+  -f, --format FORMAT    lines, u32 or u64 (default: lines)
+  -r, --reverse          sort in descending order
+  -T, --temp-dir DIR     directory for temporary files
+  -j, --threads N        number of threads used to sort chunks
+      --fan-in N         maximum number of runs merged at once (default: 64)";
+
+/// 実行時の引数
+struct Args {
+    config: ExternalSortConfig,
+    format: RecordFormat,
+    order: SortOrder,
+    input: Option<String>,
+    output: Option<String>,
+}
+
+/// `64M` のような大きさを解釈する。接尾辞 K、M、G はそれぞれ 1024 倍ずつになる
+fn parse_size(s: &str) -> Result<usize, Box<dyn Error>> {
+    let (digits, unit) = match s.char_indices().last() {
+        Some((i, c)) if c.is_ascii_alphabetic() => (&s[..i], c.to_ascii_uppercase()),
+        _ => (s, 'B'),
+    };
+    let shift = match unit {
+        'B' => 0,
+        'K' => 10,
+        'M' => 20,
+        'G' => 30,
+        _ => return Err(format!("unknown size unit: {}", unit).into()),
+    };
+    let n = digits.parse::<usize>()?;
+    n.checked_mul(1 << shift)
+        .ok_or_else(|| format!("size too large: {}", s).into())
+}
+
+fn parse_args() -> Result<Args, Box<dyn Error>> {
+    let mut args = Args {
+        config: ExternalSortConfig::default(),
+        format: RecordFormat::Lines,
+        order: SortOrder::Ascending,
+        input: None,
+        output: None,
+    };
+    let mut paths = Vec::new();
+
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = || {
+            iter.next()
+                .ok_or_else(|| format!("{} requires a value", arg))
+        };
+        match arg.as_str() {
+            "-m" | "--memory" => args.config.memory_budget = parse_size(&value()?)?,
+            "-f" | "--format" => args.format = value()?.parse()?,
+            "-r" | "--reverse" => args.order = SortOrder::Descending,
+            "-T" | "--temp-dir" => args.config.temp_dir = PathBuf::from(value()?),
+            "-j" | "--threads" => {
+                let pool = ThreadPoolBuilder::new()
+                    .num_threads(value()?.parse()?)
+                    .build()?;
+                args.config.sort.pool = Some(Arc::new(pool));
+            }
+            "--fan-in" => args.config.max_fan_in = value()?.parse()?,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option: {}", arg).into())
+            }
+            _ => paths.push(arg),
+        }
+    }
+    if paths.len() > 2 {
+        return Err("too many arguments".into());
+    }
+    // "-" は標準入出力を表す
+    let mut paths = paths
+        .into_iter()
+        .map(|path| Some(path).filter(|path| path != "-"));
+    args.input = paths.next().flatten();
+    args.output = paths.next().flatten();
+    Ok(args)
+}
+
+fn run(args: Args) -> Result<(), Box<dyn Error>> {
+    let input: Box<dyn Read> = match &args.input {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(io::stdin()),
+    };
+    let output: Box<dyn Write> = match &args.output {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout()),
+    };
+
+    let stats = sort(input, output, args.format, &args.order, &args.config)?;
+    eprintln!(
+        "sorted {} records ({} runs, {} merge passes)",
+        stats.records, stats.runs, stats.merge_passes
+    );
+    Ok(())
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("error: {}\n\n{}", e, USAGE);
+        process::exit(2);
+    });
+    if let Err(e) = run(args) {
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
@@ -0,0 +1,60 @@
+use bicycle_book::ch03::network::{small_sort_module, CodeStyle, Network};
+use std::env;
+use std::process;
+
+const USAGE: &str =
+    "Usage: ch03_network <bitonic|odd-even|optimal> <size> [text|svg|rust|rust-branchless]
+       ch03_network small-sort";
+
+fn main() {
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let args = args.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+
+    // src/ch03/small_sort.rs を生成する
+    if args == ["small-sort"] {
+        print!("{}", small_sort_module());
+        return;
+    }
+
+    let (kind, size, output) = match args.as_slice() {
+        [kind, size] => (*kind, *size, "text"),
+        [kind, size, output] => (*kind, *size, *output),
+        _ => usage(),
+    };
+    let size = size.parse::<usize>().unwrap_or_else(|_| usage());
+    let network = match kind {
+        "bitonic" => Network::bitonic(size),
+        "odd-even" => Network::odd_even_merge(size),
+        "optimal" => Network::optimal(size).unwrap_or_else(|| {
+            eprintln!("no optimal network is known for size {}", size);
+            process::exit(1);
+        }),
+        _ => usage(),
+    };
+
+    let name = format!("sort{}", size);
+    match output {
+        "text" => {
+            print!("{}", network.to_text());
+            println!(
+                "{} wires, {} comparators, depth {}",
+                network.size(),
+                network.comparators().len(),
+                network.depth()
+            );
+            match network.verify() {
+                Ok(()) => println!("verified: sorts all 2^{} 0-1 inputs", size),
+                Err(e) => println!("not verified: {}", e),
+            }
+        }
+        "svg" => print!("{}", network.to_svg()),
+        "rust" => print!("{}", network.to_rust(&name, CodeStyle::Comparator)),
+        "rust-branchless" => print!("{}", network.to_rust(&name, CodeStyle::Branchless)),
+        _ => usage(),
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("{}", USAGE);
+    process::exit(1);
+}
@@ -1,4 +1,8 @@
+use bicycle_book::ch09::complex::ComplexInterpreter;
+use bicycle_book::ch09::unit::QuantityInterpreter;
+use bicycle_book::ch09::visualize::{to_dot, to_tree};
 use bicycle_book::ch09::*;
+use std::env;
 use std::io;
 
 fn prompt(s: &str) -> io::Result<()> {
@@ -10,6 +14,38 @@ fn prompt(s: &str) -> io::Result<()> {
     stdout.flush()
 }
 
+/// 整数の結果を表示するときの基数
+#[derive(Clone, Copy)]
+enum Radix {
+    Dec,
+    Hex,
+    Bin,
+    Oct,
+}
+
+impl Radix {
+    /// `:hex` のような REPL のコマンドを解釈する
+    fn from_command(command: &str) -> Option<Radix> {
+        match command.trim() {
+            ":dec" => Some(Radix::Dec),
+            ":hex" => Some(Radix::Hex),
+            ":bin" => Some(Radix::Bin),
+            ":oct" => Some(Radix::Oct),
+            _ => None,
+        }
+    }
+
+    /// 負の数は 2 の補数で表示する
+    fn format(self, n: i64) -> String {
+        match self {
+            Radix::Dec => n.to_string(),
+            Radix::Hex => format!("{:#x}", n),
+            Radix::Bin => format!("{:#b}", n),
+            Radix::Oct => format!("{:#o}", n),
+        }
+    }
+}
+
 fn show_trace<E: std::error::Error>(e: E) {
     eprintln!("{}", e);
     let mut source = e.source();
@@ -22,7 +58,19 @@ fn show_trace<E: std::error::Error>(e: E) {
 fn main() {
     use std::io::{stdin, BufRead, BufReader};
 
+    let args = env::args().skip(1).collect::<Vec<_>>();
+    let has_flag = |flag: &str| args.iter().any(|arg| arg == flag);
+    // --tree または --dot を指定すると、評価前に構文木を表示する
+    let show_ast = args.iter().find(|arg| *arg == "--tree" || *arg == "--dot");
+    // --complex を指定すると、複素数として評価する
+    let complex = has_flag("--complex");
+    // --units を指定すると、単位付きの数量として評価する
+    let units = has_flag("--units");
     let mut interp = Interpreter::default();
+    let mut complex_interp = ComplexInterpreter;
+    let mut quantity_interp = QuantityInterpreter;
+    // :dec, :hex, :bin, :oct で整数の結果の表示形式を切り替える
+    let mut radix = Radix::Dec;
 
     let stdin = stdin();
     let stdin = stdin.lock();
@@ -32,6 +80,10 @@ fn main() {
     loop {
         prompt("> ").unwrap();
         if let Some(Ok(line)) = lines.next() {
+            if let Some(r) = Radix::from_command(&line) {
+                radix = r;
+                continue;
+            }
             let ast = match line.parse::<Ast>() {
                 Ok(ast) => ast,
                 Err(e) => {
@@ -40,7 +92,19 @@ fn main() {
                     continue;
                 }
             };
-            let n = match interp.eval(&ast) {
+            match show_ast.map(String::as_str) {
+                Some("--tree") => print!("{}", to_tree(&ast)),
+                Some("--dot") => print!("{}", to_dot(&ast)),
+                _ => {}
+            }
+            let n = if complex {
+                complex_interp.eval(&ast).map(|n| n.to_string())
+            } else if units {
+                quantity_interp.eval(&ast).map(|n| n.to_string())
+            } else {
+                interp.eval(&ast).map(|n| radix.format(n))
+            };
+            let n = match n {
                 Ok(n) => n,
                 Err(e) => {
                     e.show_diagnostic(&line);
@@ -1,32 +1,428 @@
-pub fn rpn(exp: &str) -> f64 {
-    let mut stack = Vec::new();
+//! 逆ポーランド記法を拡張した、Forth 風の小さなスタック言語
+//!
+//! 空白で区切られたトークンを先頭から順に実行する。
+//!
+//! * 数値: スタックに積む
+//! * 算術: `+ - * /` (0 による除算はエラー)
+//! * スタック操作: `dup drop swap over rot`
+//! * 比較: `= <> < > <= >=` (真なら 1、偽なら 0 を積む)
+//! * 条件分岐: `cond if ... else ... then` (0 以外を真とみなす。`else` は省略できる)
+//! * 繰り返し: `limit start do ... loop` (範囲は絶対値が 2^53 以下の整数。`i` で現在の、`j` で 1 つ外側のループの添字を積む)
+//!   と `begin ... cond until` (cond が真になるまで繰り返す)
+//! * ワード定義: `: sq dup * ;`
 
-    for token in exp.split_whitespace() {
-        if let Ok(num) = token.parse::<f64>() {
-            stack.push(num);
-        } else {
-            match token {
-                "+" => apply2(&mut stack, |x, y| x + y),
-                "-" => apply2(&mut stack, |x, y| x - y),
-                "*" => apply2(&mut stack, |x, y| x * y),
-                "/" => apply2(&mut stack, |x, y| x / y),
-                _ => panic!("Unknown operator: {}", token),
+use std::collections::HashMap;
+use std::rc::Rc;
+use thiserror::Error;
+
+/// ユーザ定義ワードの呼び出しの深さの上限
+const MAX_DEPTH: usize = 1000;
+
+/// `do` の範囲の絶対値の上限。これを超える f64 は隣り合う整数を区別できない
+const MAX_LOOP_BOUND: f64 = (1u64 << 53) as f64;
+
+/// 式 exp を評価し、スタックの一番上の値を返す
+///
+/// # Examples
+///
+/// ```
+/// use bicycle_book::ch02::rpn::rpn;
+///
+/// assert_eq!(rpn("1 2 + 3 *"), Ok(9.0));
+/// assert_eq!(rpn(": sq dup * ; 3 sq 4 sq +"), Ok(25.0));
+/// assert_eq!(rpn("1 10 < if 100 else 200 then"), Ok(100.0));
+/// ```
+pub fn rpn(exp: &str) -> Result<f64, RpnError> {
+    let mut forth = Forth::new();
+    forth.eval(exp)?;
+    forth.stack.pop().ok_or(RpnError {
+        index: exp.split_whitespace().count(),
+        kind: RpnErrorKind::StackUnderflow,
+    })
+}
+
+/// 評価中に起きたエラー
+#[derive(Error, Debug, Clone, PartialEq)]
+#[error("{kind} (token {index})")]
+pub struct RpnError {
+    /// エラーが起きたトークンの、入力の先頭から数えた位置
+    ///
+    /// ユーザ定義ワードの中で起きたエラーは、そのワードを呼び出した位置を指す。
+    pub index: usize,
+    pub kind: RpnErrorKind,
+}
+
+/// エラーの種類
+#[derive(Error, Debug, Clone, PartialEq)]
+pub enum RpnErrorKind {
+    #[error("stack underflow")]
+    StackUnderflow,
+    #[error("unknown word: {0}")]
+    UnknownWord(String),
+    #[error("unexpected word: {0}")]
+    UnexpectedWord(String),
+    #[error("missing `{0}`")]
+    Unterminated(&'static str),
+    #[error("invalid word name: {0}")]
+    InvalidName(String),
+    #[error("words are nested too deeply")]
+    TooDeep,
+    #[error("invalid loop bound: {0}")]
+    InvalidLoopBound(f64),
+    #[error("division by zero")]
+    DivisionByZero,
+}
+
+/// 構文上の意味を持つため、ワードとして定義できない名前
+const RESERVED: &[&str] = &[
+    ":", ";", "if", "else", "then", "do", "loop", "begin", "until",
+];
+
+/// 実行する命令
+#[derive(Debug, Clone, PartialEq)]
+enum OpKind {
+    Push(f64),
+    Word(String),
+    Define(String, Rc<[Op]>),
+    If(Vec<Op>, Vec<Op>),
+    Do(Vec<Op>),
+    Begin(Vec<Op>),
+}
+
+/// 命令とその元になったトークンの位置の組
+#[derive(Debug, Clone, PartialEq)]
+struct Op {
+    kind: OpKind,
+    index: usize,
+}
+
+/// トークン列を命令列に変換する
+struct Compiler<'a> {
+    tokens: std::iter::Peekable<std::iter::Enumerate<std::str::SplitWhitespace<'a>>>,
+}
+
+impl<'a> Compiler<'a> {
+    fn new(exp: &'a str) -> Self {
+        Compiler {
+            tokens: exp.split_whitespace().enumerate().peekable(),
+        }
+    }
+
+    /// 入力の終わりか、terminators のいずれかのトークンまで読み進める
+    ///
+    /// 終端のトークンは消費せずに残す。
+    fn compile(&mut self, terminators: &[&str], top_level: bool) -> Result<Vec<Op>, RpnError> {
+        let mut ops = Vec::new();
+
+        while let Some(&(index, token)) = self.tokens.peek() {
+            if terminators.contains(&token) {
+                break;
             }
+            self.tokens.next();
+
+            let kind = if let Ok(num) = token.parse::<f64>() {
+                OpKind::Push(num)
+            } else {
+                match token {
+                    ":" if top_level => self.compile_definition(index)?,
+                    "if" => {
+                        let then = self.compile(&["else", "then"], false)?;
+                        let els = if self.expect(index, &["else", "then"])? == "else" {
+                            let els = self.compile(&["then"], false)?;
+                            self.expect(index, &["then"])?;
+                            els
+                        } else {
+                            Vec::new()
+                        };
+                        OpKind::If(then, els)
+                    }
+                    "do" => {
+                        let body = self.compile(&["loop"], false)?;
+                        self.expect(index, &["loop"])?;
+                        OpKind::Do(body)
+                    }
+                    "begin" => {
+                        let body = self.compile(&["until"], false)?;
+                        self.expect(index, &["until"])?;
+                        OpKind::Begin(body)
+                    }
+                    _ if RESERVED.contains(&token) => {
+                        return Err(RpnError {
+                            index,
+                            kind: RpnErrorKind::UnexpectedWord(token.to_string()),
+                        })
+                    }
+                    _ => OpKind::Word(token.to_string()),
+                }
+            };
+            ops.push(Op { kind, index });
         }
+
+        Ok(ops)
     }
 
-    stack.pop().expect("Stack underflow")
+    /// `: name body ;` の `:` より後ろを読む
+    fn compile_definition(&mut self, index: usize) -> Result<OpKind, RpnError> {
+        let name = match self.tokens.next() {
+            Some((_, name)) if RESERVED.contains(&name) || name.parse::<f64>().is_ok() => {
+                return Err(RpnError {
+                    index: index + 1,
+                    kind: RpnErrorKind::InvalidName(name.to_string()),
+                })
+            }
+            Some((_, name)) => name.to_string(),
+            None => {
+                return Err(RpnError {
+                    index,
+                    kind: RpnErrorKind::Unterminated(";"),
+                })
+            }
+        };
+        let body = self.compile(&[";"], false)?;
+        self.expect(index, &[";"])?;
+        Ok(OpKind::Define(name, body.into()))
+    }
+
+    /// 次のトークンが expected のいずれかであれば消費して返す
+    ///
+    /// 入力が尽きていれば、開始位置 start を指すエラーを返す。
+    fn expect(&mut self, start: usize, expected: &[&'static str]) -> Result<&'a str, RpnError> {
+        match self.tokens.next() {
+            Some((_, token)) if expected.contains(&token) => Ok(token),
+            Some((index, token)) => Err(RpnError {
+                index,
+                kind: RpnErrorKind::UnexpectedWord(token.to_string()),
+            }),
+            None => Err(RpnError {
+                index: start,
+                kind: RpnErrorKind::Unterminated(expected[expected.len() - 1]),
+            }),
+        }
+    }
+}
+
+/// スタックとユーザ定義ワードを保持する評価器
+///
+/// 複数回 [`Forth::eval`] を呼び出すと、スタックと定義したワードは引き継がれる。
+///
+/// ```
+/// use bicycle_book::ch02::rpn::Forth;
+///
+/// let mut forth = Forth::new();
+/// forth.eval(": sq dup * ;").unwrap();
+/// forth.eval("0 5 0 do i sq + loop").unwrap();
+/// assert_eq!(forth.stack(), &[30.0]);
+/// ```
+#[derive(Debug, Default)]
+pub struct Forth {
+    stack: Vec<f64>,
+    words: HashMap<String, Rc<[Op]>>,
+    loops: Vec<f64>,
+    depth: usize,
 }
 
-fn apply2<F>(stack: &mut Vec<f64>, fun: F)
-where
-    F: Fn(f64, f64) -> f64,
-{
-    if let (Some(y), Some(x)) = (stack.pop(), stack.pop()) {
-        let z = fun(x, y);
-        stack.push(z);
+impl Forth {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 現在のスタックを底から順に返す
+    pub fn stack(&self) -> &[f64] {
+        &self.stack
+    }
+
+    /// 式 exp を評価する
+    ///
+    /// エラーが起きた場合、それまでの実行によるスタックの変化は残る。
+    pub fn eval(&mut self, exp: &str) -> Result<(), RpnError> {
+        // トップレベルでは終端を指定しないので、全てのトークンを読み切る
+        let ops = Compiler::new(exp).compile(&[], true)?;
+        self.loops.clear();
+        self.depth = 0;
+        self.exec(&ops, None)
+    }
+
+    /// 命令列 ops を実行する
+    ///
+    /// site はユーザ定義ワードの中を実行しているとき、その呼び出し位置を表す。
+    fn exec(&mut self, ops: &[Op], site: Option<usize>) -> Result<(), RpnError> {
+        for op in ops {
+            let index = site.unwrap_or(op.index);
+            let err = |kind| RpnError { index, kind };
+
+            match &op.kind {
+                OpKind::Push(num) => self.stack.push(*num),
+                OpKind::Define(name, body) => {
+                    self.words.insert(name.clone(), body.clone());
+                }
+                OpKind::If(then, els) => {
+                    let cond = self.pop().map_err(err)?;
+                    self.exec(if cond != 0.0 { then } else { els }, site)?;
+                }
+                OpKind::Do(body) => {
+                    let (limit, start) = self.pop2().map_err(err)?;
+                    self.exec_loop(body, site, index, start, limit)?;
+                }
+                OpKind::Begin(body) => loop {
+                    self.exec(body, site)?;
+                    if self.pop().map_err(err)? != 0.0 {
+                        break;
+                    }
+                },
+                OpKind::Word(word) => self.call(word, index)?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 位置 index の `do ... loop` の本体 body を、添字を start から limit の手前まで整数で数えながら実行する
+    ///
+    /// f64 で数えると、2^53 を超えたところで添字が進まなくなる。
+    /// 再帰する [`Forth::exec`] のフレームを小さく保つため、別の関数にしている。
+    fn exec_loop(
+        &mut self,
+        body: &[Op],
+        site: Option<usize>,
+        index: usize,
+        start: f64,
+        limit: f64,
+    ) -> Result<(), RpnError> {
+        let err = |kind| RpnError { index, kind };
+        let start = loop_bound(start).map_err(err)?;
+        let limit = loop_bound(limit).map_err(err)?;
+        for i in start..limit {
+            self.loops.push(i as f64);
+            let result = self.exec(body, site);
+            self.loops.pop();
+            result?;
+        }
+        Ok(())
+    }
+
+    /// 位置 index に書かれたワード word を実行する
+    fn call(&mut self, word: &str, index: usize) -> Result<(), RpnError> {
+        if let Some(body) = self.words.get(word).cloned() {
+            if self.depth >= MAX_DEPTH {
+                return Err(RpnError {
+                    index,
+                    kind: RpnErrorKind::TooDeep,
+                });
+            }
+            self.depth += 1;
+            let result = self.exec(&body, Some(index));
+            self.depth -= 1;
+            return result;
+        }
+
+        self.call_builtin(word)
+            .map_err(|kind| RpnError { index, kind })
+    }
+
+    /// 組み込みのワード word を実行する
+    fn call_builtin(&mut self, word: &str) -> Result<(), RpnErrorKind> {
+        match word {
+            "+" => self.apply2(|x, y| x + y),
+            "-" => self.apply2(|x, y| x - y),
+            "*" => self.apply2(|x, y| x * y),
+            "/" => {
+                // 他の電卓に合わせ、inf や NaN を積まずにエラーとする
+                self.peek(1)?;
+                if self.peek(0)? == 0.0 {
+                    return Err(RpnErrorKind::DivisionByZero);
+                }
+                self.apply2(|x, y| x / y)
+            }
+            "=" => self.apply2(|x, y| bool_to_f64(x == y)),
+            "<>" => self.apply2(|x, y| bool_to_f64(x != y)),
+            "<" => self.apply2(|x, y| bool_to_f64(x < y)),
+            ">" => self.apply2(|x, y| bool_to_f64(x > y)),
+            "<=" => self.apply2(|x, y| bool_to_f64(x <= y)),
+            ">=" => self.apply2(|x, y| bool_to_f64(x >= y)),
+            "dup" => {
+                let x = self.peek(0)?;
+                self.stack.push(x);
+                Ok(())
+            }
+            "drop" => self.pop().map(|_| ()),
+            "swap" => {
+                let (x, y) = self.pop2()?;
+                self.stack.extend(&[y, x]);
+                Ok(())
+            }
+            "over" => {
+                let x = self.peek(1)?;
+                self.stack.push(x);
+                Ok(())
+            }
+            "rot" => {
+                let len = self.stack.len();
+                if len < 3 {
+                    return Err(RpnErrorKind::StackUnderflow);
+                }
+                self.stack[len - 3..].rotate_left(1);
+                Ok(())
+            }
+            "i" | "j" => {
+                let depth = if word == "i" { 0 } else { 1 };
+                let len = self.loops.len();
+                if depth >= len {
+                    return Err(RpnErrorKind::UnexpectedWord(word.to_string()));
+                }
+                self.stack.push(self.loops[len - 1 - depth]);
+                Ok(())
+            }
+            _ => Err(RpnErrorKind::UnknownWord(word.to_string())),
+        }
+    }
+
+    fn pop(&mut self) -> Result<f64, RpnErrorKind> {
+        self.stack.pop().ok_or(RpnErrorKind::StackUnderflow)
+    }
+
+    /// 2 つ値を取り出し、積んだ順に返す
+    fn pop2(&mut self) -> Result<(f64, f64), RpnErrorKind> {
+        if self.stack.len() < 2 {
+            return Err(RpnErrorKind::StackUnderflow);
+        }
+        let y = self.stack.pop().unwrap();
+        let x = self.stack.pop().unwrap();
+        Ok((x, y))
+    }
+
+    /// 上から n 番目 (0 始まり) の値を返す
+    fn peek(&self, n: usize) -> Result<f64, RpnErrorKind> {
+        self.stack
+            .len()
+            .checked_sub(n + 1)
+            .map(|i| self.stack[i])
+            .ok_or(RpnErrorKind::StackUnderflow)
+    }
+
+    fn apply2<F>(&mut self, fun: F) -> Result<(), RpnErrorKind>
+    where
+        F: Fn(f64, f64) -> f64,
+    {
+        let (x, y) = self.pop2()?;
+        self.stack.push(fun(x, y));
+        Ok(())
+    }
+}
+
+/// `do` の範囲として x を整数に変換する。整数でない値や大きすぎる値はエラーとする
+fn loop_bound(x: f64) -> Result<i64, RpnErrorKind> {
+    if x.fract() == 0.0 && x.abs() <= MAX_LOOP_BOUND {
+        Ok(x as i64)
+    } else {
+        Err(RpnErrorKind::InvalidLoopBound(x))
+    }
+}
+
+fn bool_to_f64(b: bool) -> f64 {
+    if b {
+        1.0
     } else {
-        panic!("Stack underflow");
+        0.0
     }
 }
 
@@ -34,10 +430,95 @@ where
 mod tests {
     use super::*;
 
+    fn error(index: usize, kind: RpnErrorKind) -> Result<f64, RpnError> {
+        Err(RpnError { index, kind })
+    }
+
     #[test]
     fn calc_rpn() {
         let exp = "6.1 5.2 4.3 * + 3.4 2.5 / 1.6 * -";
-        let ans = rpn(exp);
+        let ans = rpn(exp).unwrap();
         assert_eq!("26.2840", format!("{:.4}", ans));
     }
+
+    #[test]
+    fn stack_words() {
+        assert_eq!(rpn("1 2 swap -"), Ok(1.0));
+        assert_eq!(rpn("3 dup *"), Ok(9.0));
+        assert_eq!(rpn("1 2 drop"), Ok(1.0));
+        assert_eq!(rpn("1 2 over - +"), Ok(2.0));
+        assert_eq!(rpn("1 2 3 rot - -"), Ok(0.0));
+    }
+
+    #[test]
+    fn conditionals() {
+        assert_eq!(rpn("2 3 < if 10 else 20 then"), Ok(10.0));
+        assert_eq!(rpn("2 3 >= if 10 else 20 then"), Ok(20.0));
+        assert_eq!(rpn("5 0 if 1 + then"), Ok(5.0));
+        assert_eq!(rpn("1 1 if 0 if 1 else 2 then then"), Ok(2.0));
+    }
+
+    #[test]
+    fn loops() {
+        assert_eq!(rpn("0 11 1 do i + loop"), Ok(55.0));
+        assert_eq!(rpn("0 3 0 do 3 0 do i j * + loop loop"), Ok(9.0));
+        assert_eq!(rpn("1 begin 2 * dup 100 > until"), Ok(128.0));
+        // 2^53 付近でも添字は 1 ずつ進む
+        assert_eq!(
+            rpn("0 9007199254740992 9007199254740990 do 1 + loop"),
+            Ok(2.0)
+        );
+    }
+
+    #[test]
+    fn user_words() {
+        assert_eq!(rpn(": sq dup * ; 3 sq 4 sq +"), Ok(25.0));
+        assert_eq!(
+            rpn(": fact dup 1 > if dup 1 - fact * then ; 5 fact"),
+            Ok(120.0)
+        );
+        assert_eq!(rpn(": + * ; 3 4 +"), Ok(12.0));
+    }
+
+    #[test]
+    fn errors() {
+        use RpnErrorKind::*;
+        assert_eq!(rpn("1 +"), error(1, StackUnderflow));
+        assert_eq!(rpn(""), error(0, StackUnderflow));
+        assert_eq!(rpn("1 2 foo"), error(2, UnknownWord("foo".to_string())));
+        assert_eq!(rpn("1 if 2"), error(1, Unterminated("then")));
+        assert_eq!(
+            rpn("1 2 then"),
+            error(2, UnexpectedWord("then".to_string()))
+        );
+        assert_eq!(rpn(": sq dup *"), error(0, Unterminated(";")));
+        assert_eq!(rpn(": 1 2 ;"), error(1, InvalidName("1".to_string())));
+        assert_eq!(
+            rpn("1 if : a ; then"),
+            error(2, UnexpectedWord(":".to_string()))
+        );
+        assert_eq!(rpn("i"), error(0, UnexpectedWord("i".to_string())));
+        // ワードの中で起きたエラーは呼び出し位置を指す
+        assert_eq!(rpn(": f + ; 1 f"), error(5, StackUnderflow));
+        assert_eq!(rpn(": f f ; f"), error(4, TooDeep));
+        assert_eq!(rpn("1.5 0 do loop"), error(2, InvalidLoopBound(1.5)));
+        assert_eq!(rpn("1 0 /"), error(2, DivisionByZero));
+        assert_eq!(rpn("0 /"), error(1, StackUnderflow));
+        assert_eq!(rpn("1e300 0 do loop"), error(2, InvalidLoopBound(1e300)));
+        assert_eq!(
+            rpn("18014398509481984 0 do loop"),
+            error(2, InvalidLoopBound(18014398509481984.0))
+        );
+    }
+
+    #[test]
+    fn forth_keeps_state() {
+        let mut forth = Forth::new();
+        forth.eval(": inc 1 + ;").unwrap();
+        forth.eval("1 inc").unwrap();
+        forth.eval("inc inc").unwrap();
+        assert_eq!(forth.stack(), &[4.0]);
+        assert!(forth.eval("+").is_err());
+        assert_eq!(forth.stack(), &[4.0]);
+    }
 }
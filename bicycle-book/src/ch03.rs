@@ -1,10 +1,96 @@
+pub mod collate;
+mod common;
+pub mod external;
 pub mod first;
 pub mod fourth;
+pub mod merge;
+pub mod network;
+pub mod quick;
+pub mod radix;
 pub mod second;
+pub mod select;
+pub mod simd;
+pub mod small_sort;
 pub mod third;
 pub mod util;
 
+use rayon::ThreadPool;
+use std::cmp::Ordering;
+use std::sync::Arc;
+use thiserror::Error;
+
 pub enum SortOrder {
     Ascending,
     Descending,
 }
+
+/// ソートアルゴリズム
+///
+/// アルゴリズムごとの型 ([`fourth::BitonicSort`]、[`merge::MergeSort`]、
+/// [`radix::RadixSort`]、[`quick::QuickSort`]、[`simd::SimdBitonicSort`]) に実装されている。
+pub trait Sorter<T> {
+    /// アルゴリズムの名前
+    fn name(&self) -> &'static str;
+
+    /// 等しい要素の元の並びを保つかどうか
+    fn is_stable(&self) -> bool;
+
+    /// x を order の順にソートする
+    fn sort(&self, x: &mut [T], order: &SortOrder);
+}
+
+/// 比較関数を与えてソートできるアルゴリズム
+pub trait ComparisonSorter<T>: Sorter<T> {
+    /// comparator が返す順序に従って x をソートする
+    fn sort_by(&self, x: &mut [T], comparator: &(dyn Fn(&T, &T) -> Ordering + Sync));
+}
+
+/// 並列ソートの設定
+///
+/// [`fourth`]、[`merge`]、[`quick`]、[`radix`]、[`select`]、[`simd`] の
+/// `*_with_config` 関数に渡す。
+/// しきい値は [`fourth::calibrate`] で、実行するマシンに合わせた値を求められる。
+#[derive(Debug, Clone)]
+pub struct SortConfig {
+    /// 要素数がこれ以上の区間は、前半と後半を並列にソートする
+    pub sort_threshold: usize,
+    /// 要素数がこれ以上の区間は、マージの前半と後半を並列に処理する
+    pub merge_threshold: usize,
+    /// 並列に分割する再帰の深さの上限。0 なら並列化しない
+    pub max_parallel_depth: usize,
+    /// ソートを実行するスレッドプール。`None` なら rayon のグローバルプールを使う
+    pub pool: Option<Arc<ThreadPool>>,
+}
+
+impl Default for SortConfig {
+    fn default() -> Self {
+        Self {
+            sort_threshold: common::PARALLEL_THRESHOLD,
+            merge_threshold: common::PARALLEL_THRESHOLD,
+            max_parallel_depth: usize::MAX,
+            pool: None,
+        }
+    }
+}
+
+impl SortConfig {
+    /// 設定されたスレッドプールで f を実行する
+    pub fn install<R: Send>(&self, f: impl FnOnce() -> R + Send) -> R {
+        match &self.pool {
+            Some(pool) => pool.install(f),
+            None => f(),
+        }
+    }
+
+    /// 深さ depth で要素数 len の区間を並列に処理するかどうか
+    fn forks(&self, len: usize, threshold: usize, depth: usize) -> bool {
+        depth < self.max_parallel_depth && len >= threshold
+    }
+}
+
+/// 要素数が 2 のべき乗の場合にしかソートできない関数が返すエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum SortError {
+    #[error("the length of x is not a power of two (x.len(): {0})")]
+    NotPowerOfTwo(usize),
+}
@@ -0,0 +1,299 @@
+//! 文字列を人が期待する順に並べる比較関数
+//!
+//! `str` の [`Ord`] はコードポイントの順に比べるため、`"GC"` が `"and"` より、
+//! `"file10"` が `"file2"` より前に来る。ここの関数は [`fourth::sort_by`](crate::ch03::fourth::sort_by)
+//! などに比較関数としてそのまま渡せる。どの関数も、他の点で等しい場合は元の文字列の順に並べるので、
+//! 異なる文字列が等しいと判定されることはない。
+
+use std::cmp::Ordering;
+
+/// 数字の並びを数値として比べる (`"file2"` < `"file10"`)
+///
+/// 数値が等しい場合は、先頭の 0 が少ない方を前にする (`"file1"` < `"file01"`)。
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut xs, mut ys) = (segments(a), segments(b));
+    // 先頭の 0 の数の違い。他の部分が全て等しい場合にだけ使う
+    let mut tie = Ordering::Equal;
+    loop {
+        let (s, t) = match (xs.next(), ys.next()) {
+            (None, None) => return tie.then_with(|| a.cmp(b)),
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(s), Some(t)) => (s, t),
+        };
+        let ord = if is_digits(s) && is_digits(t) {
+            let (u, v) = (s.trim_start_matches('0'), t.trim_start_matches('0'));
+            tie = tie.then(s.len().cmp(&t.len()));
+            u.len().cmp(&v.len()).then_with(|| u.cmp(v))
+        } else {
+            s.cmp(t)
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+}
+
+/// s を数字の並びとそれ以外の並びに分ける
+fn segments(s: &str) -> impl Iterator<Item = &str> {
+    let mut rest = s;
+    std::iter::from_fn(move || {
+        let is_digit = rest.chars().next()?.is_ascii_digit();
+        let end = rest
+            .find(|c: char| c.is_ascii_digit() != is_digit)
+            .unwrap_or(rest.len());
+        let (segment, tail) = rest.split_at(end);
+        rest = tail;
+        Some(segment)
+    })
+}
+
+fn is_digits(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_digit())
+}
+
+/// 大文字と小文字を区別せずに比べる (`"and"` < `"GC"`)
+pub fn case_insensitive_cmp(a: &str, b: &str) -> Ordering {
+    a.chars()
+        .flat_map(char::to_lowercase)
+        .cmp(b.chars().flat_map(char::to_lowercase))
+        .then_with(|| a.cmp(b))
+}
+
+/// 日本語の文字列を辞書に近い順に比べる
+///
+/// 比較の前に、全角の英数字と記号を半角に、半角カタカナを全角に、カタカナをひらがなに、
+/// 英字を小文字にそろえる。まず濁点、半濁点、小書きの違いも無視して比べ、
+/// 等しければそれらを区別して比べる (`"かま"` < `"がみ"` < `"かむ"`)。
+/// 小書きの文字は、同じ大きさの文字より前に並ぶ。
+/// 漢字は読みが分からないため、コードポイントの順に並ぶ。
+///
+/// 比較のたびにキーを作るので、多くの要素をソートする時は [`japanese_key`] と
+/// [`fourth::sort_by_cached_key`](crate::ch03::fourth::sort_by_cached_key) を使う方が速い。
+pub fn japanese_cmp(a: &str, b: &str) -> Ordering {
+    japanese_key(a).cmp(&japanese_key(b))
+}
+
+/// [`japanese_cmp`] と同じ順に並ぶソートキー
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct JapaneseKey {
+    /// 濁点、半濁点、小書きを取り除いた文字
+    primary: Vec<char>,
+    /// 字形の違いだけをそろえた文字
+    secondary: Vec<char>,
+    original: String,
+}
+
+/// s の [`JapaneseKey`] を作る
+pub fn japanese_key(s: &str) -> JapaneseKey {
+    let secondary = normalize(s);
+    JapaneseKey {
+        primary: secondary.iter().map(|&c| base_kana(c)).collect(),
+        secondary,
+        original: s.to_string(),
+    }
+}
+
+/// U+FF61 から U+FF9F までの半角カタカナに対応する全角の文字
+const HALF_WIDTH_KATAKANA: &str = "。「」、・ヲァィゥェォャュョッーアイウエオカキクケコサシスセソタチツテトナニヌネノハヒフヘホマミムメモヤユヨラリルレロワン゛゜";
+
+/// 濁点を付けるとコードポイントが 1 増える (半濁点なら 2 増える) ひらがな
+const VOICEABLE: &str = "かきくけこさしすせそたちつてとはひふへほ";
+const SEMI_VOICEABLE: &str = "はひふへほ";
+
+/// 字形の違いをそろえる
+///
+/// 全角英数字と記号を半角に、半角カタカナを全角に、カタカナをひらがなに、英字を小文字にしてから、
+/// 後に続く濁点と半濁点を前の文字と合成する。
+fn normalize(s: &str) -> Vec<char> {
+    let mut chars: Vec<char> = Vec::with_capacity(s.len());
+    for c in s.chars() {
+        let c = match c {
+            '\u{3000}' => ' ',
+            '\u{ff01}'..='\u{ff5e}' => char_from(c as u32 - 0xfee0),
+            '\u{ff61}'..='\u{ff9f}' => HALF_WIDTH_KATAKANA
+                .chars()
+                .nth((c as u32 - 0xff61) as usize)
+                .unwrap(),
+            _ => c,
+        };
+        let c = match c {
+            'ァ'..='ヶ' => char_from(c as u32 - 0x60),
+            _ => c.to_ascii_lowercase(),
+        };
+        // 濁点と半濁点 (結合文字を含む) を前のかなと合成する
+        let voiced = match (c, chars.last()) {
+            ('゛' | '\u{3099}', Some(&'う')) => Some('ゔ'),
+            ('゛' | '\u{3099}', Some(&prev)) if VOICEABLE.contains(prev) => {
+                Some(char_from(prev as u32 + 1))
+            }
+            ('゜' | '\u{309a}', Some(&prev)) if SEMI_VOICEABLE.contains(prev) => {
+                Some(char_from(prev as u32 + 2))
+            }
+            _ => None,
+        };
+        match voiced {
+            Some(v) => *chars.last_mut().unwrap() = v,
+            None => chars.push(c),
+        }
+    }
+    chars
+}
+
+/// ひらがなの濁点、半濁点、小書きを取り除く
+fn base_kana(c: char) -> char {
+    match c {
+        'ぁ' | 'ぃ' | 'ぅ' | 'ぇ' | 'ぉ' | 'っ' | 'ゃ' | 'ゅ' | 'ょ' | 'ゎ' => {
+            char_from(c as u32 + 1)
+        }
+        'ゔ' => 'う',
+        'ゕ' => 'か',
+        'ゖ' => 'け',
+        'が'..='ぢ' if (c as u32 - 'か' as u32) % 2 == 1 => char_from(c as u32 - 1),
+        'づ' | 'で' | 'ど' => char_from(c as u32 - 1),
+        'ば'..='ぽ' => match (c as u32 - 'は' as u32) % 3 {
+            0 => c,
+            n => char_from(c as u32 - n),
+        },
+        _ => c,
+    }
+}
+
+fn char_from(code: u32) -> char {
+    std::char::from_u32(code).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch03::fourth::{sort_by, sort_by_cached_key};
+    use crate::ch03::SortOrder::Ascending;
+
+    #[test]
+    fn natural_order() {
+        let mut x = vec![
+            "file10.txt",
+            "file2.txt",
+            "file01.txt",
+            "file1.txt",
+            "file",
+            "file1a.txt",
+            "img12",
+            "file100.txt",
+        ];
+        sort_by(&mut x, &|a, b| natural_cmp(a, b));
+        assert_eq!(
+            x,
+            vec![
+                "file",
+                "file1.txt",
+                "file01.txt",
+                "file1a.txt",
+                "file2.txt",
+                "file10.txt",
+                "file100.txt",
+                "img12",
+            ]
+        );
+        assert_eq!(
+            natural_cmp("a99999999999999999999", "a100000000000000000000"),
+            Ordering::Less
+        );
+        assert_eq!(natural_cmp("x", "x"), Ordering::Equal);
+    }
+
+    #[test]
+    fn case_insensitive_order() {
+        let mut x = vec![
+            "Rust",
+            "is",
+            "fast",
+            "and",
+            "memory-efficient",
+            "with",
+            "no",
+            "GC",
+        ];
+        sort_by(&mut x, &|a, b| case_insensitive_cmp(a, b));
+        assert_eq!(
+            x,
+            vec![
+                "and",
+                "fast",
+                "GC",
+                "is",
+                "memory-efficient",
+                "no",
+                "Rust",
+                "with",
+            ]
+        );
+        assert_eq!(case_insensitive_cmp("Ab", "ab"), Ordering::Less);
+    }
+
+    #[test]
+    fn normalize_width_and_kana() {
+        let normalized = |s: &str| normalize(s).into_iter().collect::<String>();
+        assert_eq!(normalized("ＡＢＣ１２３！　ｘ"), "abc123! x");
+        assert_eq!(normalized("ｶﾞｷﾞﾊﾟﾝｳﾞｧｰ"), "がぎぱんゔぁー");
+        assert_eq!(normalized("ハ\u{309a}ン"), "ぱん");
+        assert_eq!(normalized("か\u{3099}む"), "がむ");
+        assert_eq!(normalized("は\u{309a}ん"), "ぱん");
+        assert_eq!(normalized("う゛ぁ"), "ゔぁ");
+        assert_eq!(normalized("と゛カ\u{3099}"), "どが");
+        assert_eq!(normalized("あ\u{3099}"), "あ\u{3099}");
+        assert_eq!(normalized("ｶﾒﾗ"), normalized("カメラ"));
+        assert_eq!(normalized("ﾞa"), "゛a");
+    }
+
+    #[test]
+    fn japanese_order() {
+        let mut x = vec![
+            "カメラ",
+            "きのこ",
+            "がむ",
+            "ｶﾒﾗ",
+            "かめら",
+            "かも",
+            "ｶﾞﾑ",
+            "ＡＢＣ",
+            "abc",
+            "しゃしん",
+            "シヤシン",
+        ];
+        sort_by(&mut x, &|a, b| japanese_cmp(a, b));
+        assert_eq!(
+            x,
+            vec![
+                "abc",
+                "ＡＢＣ",
+                "がむ",
+                "ｶﾞﾑ",
+                "かめら",
+                "カメラ",
+                "ｶﾒﾗ",
+                "かも",
+                "きのこ",
+                "しゃしん",
+                "シヤシン",
+            ]
+        );
+        assert_eq!(japanese_cmp("かま", "がみ"), Ordering::Less);
+        assert_eq!(japanese_cmp("がみ", "かむ"), Ordering::Less);
+        assert_eq!(japanese_cmp("つか", "づか"), Ordering::Less);
+        assert_eq!(japanese_cmp("づか", "つき"), Ordering::Less);
+        assert_eq!(japanese_cmp("ほん", "ぼん"), Ordering::Less);
+        assert_eq!(japanese_cmp("ぼん", "ぽん"), Ordering::Less);
+        assert_eq!(japanese_cmp("ぽん", "ほんや"), Ordering::Less);
+        assert_eq!(
+            japanese_key("か\u{3099}む").secondary,
+            japanese_key("がむ").secondary
+        );
+        assert_eq!(japanese_cmp("か\u{3099}む", "かめら"), Ordering::Less);
+
+        let mut y = x.clone();
+        y.reverse();
+        sort_by_cached_key(&mut y, &|s| japanese_key(s), &Ascending);
+        assert_eq!(y, x);
+    }
+}
@@ -0,0 +1,90 @@
+//! ch03 のソートで共有する定数と関数
+
+use std::cmp::Ordering;
+
+/// 要素数がこれ以上の入力を並列に処理する
+pub(crate) const PARALLEL_THRESHOLD: usize = 4096;
+
+/// これより短い区間は挿入ソートで並べる
+pub(crate) const INSERTION_THRESHOLD: usize = 16;
+
+pub(crate) fn insertion_sort<T, F>(x: &mut [T], comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    for i in 1..x.len() {
+        let mut j = i;
+        while j > 0 && comparator(&x[j - 1], &x[j]) == Ordering::Greater {
+            x.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+/// 要素数 len の入力を並列に処理する時のチャンクの長さ
+///
+/// threshold より短い入力は分割しない。チャンクは threshold 以上の長さにする。
+pub(crate) fn chunk_len(len: usize, threshold: usize) -> usize {
+    if len < threshold {
+        len
+    } else {
+        let chunks = rayon::current_num_threads() * 4;
+        (len / chunks + 1).max(threshold)
+    }
+}
+
+/// 並列ソートのテストで共有する関数
+#[cfg(test)]
+pub(crate) mod test_util {
+    use rayon::{ThreadPool, ThreadPoolBuilder};
+    use std::cmp::Ordering;
+    use std::sync::atomic::{AtomicBool, Ordering as AtomicOrdering};
+    use std::sync::Arc;
+    use std::thread;
+
+    /// f に渡す比較関数
+    pub(crate) type Comparator<'a, T> = &'a (dyn Fn(&T, &T) -> Ordering + Sync);
+
+    /// スレッド数 threads のプール
+    pub(crate) fn test_pool(threads: usize) -> Arc<ThreadPool> {
+        Arc::new(
+            ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap(),
+        )
+    }
+
+    /// f に渡した比較関数が、全て呼び出したスレッドで呼ばれることを確かめる
+    #[track_caller]
+    pub(crate) fn assert_runs_on_caller<T: Ord>(f: impl FnOnce(Comparator<T>)) {
+        let caller = thread::current().id();
+        assert_all_calls(f, &|| thread::current().id() == caller);
+    }
+
+    /// f に渡した比較関数が、全てスレッド数 threads のプールのスレッドで呼ばれることを確かめる
+    #[track_caller]
+    pub(crate) fn assert_runs_in_pool<T: Ord>(threads: usize, f: impl FnOnce(Comparator<T>)) {
+        assert_all_calls(f, &|| {
+            rayon::current_num_threads() == threads && rayon::current_thread_index().is_some()
+        });
+    }
+
+    #[track_caller]
+    fn assert_all_calls<T: Ord>(
+        f: impl FnOnce(Comparator<T>),
+        expected: &(dyn Fn() -> bool + Sync),
+    ) {
+        let unexpected = AtomicBool::new(false);
+        f(&|a, b| {
+            if !expected() {
+                unexpected.store(true, AtomicOrdering::Relaxed);
+            }
+            a.cmp(b)
+        });
+        assert!(
+            !unexpected.load(AtomicOrdering::Relaxed),
+            "the comparator was called on an unexpected thread"
+        );
+    }
+}
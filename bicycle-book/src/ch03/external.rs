@@ -0,0 +1,573 @@
+//! 外部マージソート
+//!
+//! メモリに収まらない入力を、メモリの予算に収まるチャンクに分けて並列にソートし、
+//! ソート済みの列 (ラン) を一時ファイルに書き出してから、ヒープを使って k-way マージする。
+
+use crate::ch03::{merge, radix, SortConfig, SortOrder};
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::env;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+use thiserror::Error;
+
+/// メモリの予算の下限 (バイト)
+pub const MIN_MEMORY_BUDGET: usize = 1024;
+
+/// マージでランを読む時のバッファの大きさの下限 (バイト)
+const MIN_READ_BUFFER: usize = 4096;
+
+#[derive(Debug, Error)]
+pub enum ExternalSortError {
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("the input ends in the middle of a {0}-byte record")]
+    TruncatedRecord(usize),
+    #[error(
+        "the memory budget must be at least {} bytes (got {0})",
+        MIN_MEMORY_BUDGET
+    )]
+    BudgetTooSmall(usize),
+    #[error("the maximum fan-in must be at least 2 (got {0})")]
+    FanInTooSmall(usize),
+    #[error("unknown record format: {0}")]
+    UnknownFormat(String),
+}
+
+/// 入力のレコードの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RecordFormat {
+    /// 改行区切りのテキスト。各行をバイト列として比較する
+    Lines,
+    /// リトルエンディアンの 4 バイトの符号なし整数
+    U32,
+    /// リトルエンディアンの 8 バイトの符号なし整数
+    U64,
+}
+
+impl FromStr for RecordFormat {
+    type Err = ExternalSortError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "lines" => Ok(RecordFormat::Lines),
+            "u32" => Ok(RecordFormat::U32),
+            "u64" => Ok(RecordFormat::U64),
+            _ => Err(ExternalSortError::UnknownFormat(s.to_string())),
+        }
+    }
+}
+
+/// [`sort`] の設定
+#[derive(Debug, Clone)]
+pub struct ExternalSortConfig {
+    /// ソート中に使うメモリの目安 (バイト)
+    pub memory_budget: usize,
+    /// 1 回のマージで同時に開くランの数の上限
+    pub max_fan_in: usize,
+    /// ランを書き出すディレクトリ
+    pub temp_dir: PathBuf,
+    /// チャンクをソートする時の並列化の設定
+    pub sort: SortConfig,
+}
+
+impl Default for ExternalSortConfig {
+    fn default() -> Self {
+        Self {
+            memory_budget: 256 * 1024 * 1024,
+            max_fan_in: 64,
+            temp_dir: env::temp_dir(),
+            sort: SortConfig::default(),
+        }
+    }
+}
+
+/// [`sort`] が処理した内容
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SortStats {
+    /// レコードの数
+    pub records: u64,
+    /// 最初に作ったランの数。入力がメモリに収まった場合は 1
+    pub runs: usize,
+    /// マージを行った回数 (パス数)
+    pub merge_passes: usize,
+}
+
+/// 外部ソートできるレコード
+pub trait Record: Ord + Clone + Send + Sync + Sized {
+    /// レコードを 1 つ読む。入力の終わりに達していたら None を返す
+    fn read_from<R: BufRead>(reader: &mut R) -> Result<Option<Self>, ExternalSortError>;
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()>;
+
+    /// メモリ上で占める大きさの目安 (バイト)
+    fn memory_size(&self) -> usize;
+
+    /// メモリ上のチャンクを config に従って order の順にソートする。安定でなければならない
+    fn sort_chunk(chunk: &mut [Self], order: &SortOrder, config: &SortConfig) {
+        merge::sort_with_config(chunk, order, config);
+    }
+}
+
+/// 改行区切りのテキストの 1 行。改行文字は含まない
+///
+/// 最後の行に改行がなくても 1 行として読み、書き出す時は全ての行の後に改行を付ける。
+impl Record for Vec<u8> {
+    fn read_from<R: BufRead>(reader: &mut R) -> Result<Option<Self>, ExternalSortError> {
+        let mut line = Vec::new();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            return Ok(None);
+        }
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        Ok(Some(line))
+    }
+
+    fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+        writer.write_all(self)?;
+        writer.write_all(b"\n")
+    }
+
+    fn memory_size(&self) -> usize {
+        std::mem::size_of::<Self>() + self.len()
+    }
+}
+
+macro_rules! impl_record_for_binary {
+    ($($t:ty),*) => {$(
+        /// リトルエンディアンの固定長のバイナリ。チャンクは基数ソートで並べる
+        impl Record for $t {
+            fn read_from<R: BufRead>(reader: &mut R) -> Result<Option<Self>, ExternalSortError> {
+                let mut buf = [0; std::mem::size_of::<$t>()];
+                Ok(read_exact_or_eof(reader, &mut buf)?.then(|| <$t>::from_le_bytes(buf)))
+            }
+
+            fn write_to<W: Write>(&self, writer: &mut W) -> io::Result<()> {
+                writer.write_all(&self.to_le_bytes())
+            }
+
+            fn memory_size(&self) -> usize {
+                std::mem::size_of::<$t>()
+            }
+
+            fn sort_chunk(chunk: &mut [Self], order: &SortOrder, config: &SortConfig) {
+                radix::sort_with_config(chunk, order, config);
+            }
+        }
+    )*};
+}
+
+impl_record_for_binary!(u32, u64);
+
+/// buf を埋めるまで読む。入力の終わりで 1 バイトも読めなければ false を返す
+fn read_exact_or_eof<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<bool, ExternalSortError> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e.into()),
+        }
+    }
+    match filled {
+        0 => Ok(false),
+        n if n == buf.len() => Ok(true),
+        _ => Err(ExternalSortError::TruncatedRecord(buf.len())),
+    }
+}
+
+/// input のレコードを order の順にソートして output に書き出す
+pub fn sort<R: Read, W: Write>(
+    input: R,
+    output: W,
+    format: RecordFormat,
+    order: &SortOrder,
+    config: &ExternalSortConfig,
+) -> Result<SortStats, ExternalSortError> {
+    match format {
+        RecordFormat::Lines => sort_records::<Vec<u8>, _, _>(input, output, order, config),
+        RecordFormat::U32 => sort_records::<u32, _, _>(input, output, order, config),
+        RecordFormat::U64 => sort_records::<u64, _, _>(input, output, order, config),
+    }
+}
+
+/// input の T のレコードを order の順に安定ソートして output に書き出す
+///
+/// チャンクのソートでは作業領域として同じ大きさのメモリを使うので、
+/// 予算の半分ずつ読み込んでソートする。
+pub fn sort_records<T: Record, R: Read, W: Write>(
+    input: R,
+    output: W,
+    order: &SortOrder,
+    config: &ExternalSortConfig,
+) -> Result<SortStats, ExternalSortError> {
+    if config.memory_budget < MIN_MEMORY_BUDGET {
+        return Err(ExternalSortError::BudgetTooSmall(config.memory_budget));
+    }
+    if config.max_fan_in < 2 {
+        return Err(ExternalSortError::FanInTooSmall(config.max_fan_in));
+    }
+
+    let mut reader = BufReader::new(input);
+    let mut output = BufWriter::new(output);
+    let mut stats = SortStats::default();
+    let mut runs = Vec::new();
+
+    loop {
+        let mut chunk = read_chunk::<T, _>(&mut reader, config.memory_budget / 2)?;
+        if chunk.is_empty() {
+            break;
+        }
+        stats.records += chunk.len() as u64;
+        T::sort_chunk(&mut chunk, order, &config.sort);
+
+        // 入力が 1 つのチャンクに収まったら、一時ファイルを使わずに書き出す
+        if runs.is_empty() && reader.fill_buf()?.is_empty() {
+            for record in &chunk {
+                record.write_to(&mut output)?;
+            }
+            stats.runs = 1;
+            output.flush()?;
+            return Ok(stats);
+        }
+
+        let (run, mut writer) = Run::create(&config.temp_dir)?;
+        for record in &chunk {
+            record.write_to(&mut writer)?;
+        }
+        writer.flush()?;
+        runs.push(run);
+    }
+    stats.runs = runs.len();
+
+    let descending = matches!(order, SortOrder::Descending);
+    let buffer_len = (config.memory_budget / (config.max_fan_in + 1)).max(MIN_READ_BUFFER);
+
+    // 一度に開けるランの数に収まるまで、ランをまとめてマージする
+    while runs.len() > config.max_fan_in {
+        runs = runs
+            .chunks(config.max_fan_in)
+            .map(|group| {
+                let (run, mut writer) = Run::create(&config.temp_dir)?;
+                merge_runs::<T, _>(group, &mut writer, descending, buffer_len)?;
+                writer.flush()?;
+                Ok(run)
+            })
+            .collect::<Result<Vec<_>, ExternalSortError>>()?;
+        stats.merge_passes += 1;
+    }
+    if !runs.is_empty() {
+        merge_runs::<T, _>(&runs, &mut output, descending, buffer_len)?;
+        stats.merge_passes += 1;
+    }
+    output.flush()?;
+    Ok(stats)
+}
+
+/// メモリ上の大きさの合計が budget に達するまでレコードを読む
+fn read_chunk<T: Record, R: BufRead>(
+    reader: &mut R,
+    budget: usize,
+) -> Result<Vec<T>, ExternalSortError> {
+    let mut chunk = Vec::new();
+    let mut size = 0;
+    while size < budget {
+        match T::read_from(reader)? {
+            Some(record) => {
+                size += record.memory_size();
+                chunk.push(record);
+            }
+            None => break,
+        }
+    }
+    Ok(chunk)
+}
+
+/// 一時ファイル名が重ならないようにするための通し番号
+static NEXT_RUN_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// 一時ファイルに書き出したラン。drop するとファイルを削除する
+struct Run {
+    path: PathBuf,
+}
+
+impl Run {
+    fn create(dir: &Path) -> io::Result<(Self, BufWriter<File>)> {
+        let id = NEXT_RUN_ID.fetch_add(1, AtomicOrdering::Relaxed);
+        let path = dir.join(format!("bicycle-book-sort-{}-{}.run", process::id(), id));
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)?;
+        Ok((Self { path }, BufWriter::new(file)))
+    }
+}
+
+impl Drop for Run {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// ヒープに入れる、各ランの先頭のレコード
+struct Head<T> {
+    record: T,
+    run: usize,
+    descending: bool,
+}
+
+/// BinaryHeap は最大のものから取り出すので、次に書き出すものが最大になるよう比較する
+impl<T: Ord> Ord for Head<T> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let by_record = if self.descending {
+            self.record.cmp(&other.record)
+        } else {
+            other.record.cmp(&self.record)
+        };
+        // 等しいレコードは前のランのものを先に書き出し、安定性を保つ
+        by_record.then_with(|| other.run.cmp(&self.run))
+    }
+}
+
+impl<T: Ord> PartialOrd for Head<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T: Ord> PartialEq for Head<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<T: Ord> Eq for Head<T> {}
+
+/// ソート済みの runs を 1 つにマージして output に書き出す
+fn merge_runs<T: Record, W: Write>(
+    runs: &[Run],
+    output: &mut W,
+    descending: bool,
+    buffer_len: usize,
+) -> Result<(), ExternalSortError> {
+    let mut readers = runs
+        .iter()
+        .map(|run| Ok(BufReader::with_capacity(buffer_len, File::open(&run.path)?)))
+        .collect::<io::Result<Vec<_>>>()?;
+
+    let mut heap = BinaryHeap::with_capacity(readers.len());
+    for (run, reader) in readers.iter_mut().enumerate() {
+        if let Some(record) = T::read_from(reader)? {
+            heap.push(Head {
+                record,
+                run,
+                descending,
+            });
+        }
+    }
+
+    while let Some(Head { record, run, .. }) = heap.pop() {
+        record.write_to(output)?;
+        if let Some(record) = T::read_from(&mut readers[run])? {
+            heap.push(Head {
+                record,
+                run,
+                descending,
+            });
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch03::util::new_u32_vec;
+    use crate::ch03::SortOrder::*;
+
+    /// テストごとの一時ディレクトリを作る
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            env::temp_dir().join(format!("bicycle-book-sort-test-{}-{}", name, process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn small_config(dir: &Path) -> ExternalSortConfig {
+        ExternalSortConfig {
+            memory_budget: MIN_MEMORY_BUDGET,
+            max_fan_in: 3,
+            temp_dir: dir.to_path_buf(),
+            ..ExternalSortConfig::default()
+        }
+    }
+
+    #[test]
+    fn sort_lines_through_temporary_files() {
+        let dir = temp_dir("lines");
+        let lines = new_u32_vec(2000)
+            .iter()
+            .map(|v| format!("{}", v % 500))
+            .collect::<Vec<_>>();
+        let input = lines.join("\n");
+
+        let mut output = Vec::new();
+        let stats = sort(
+            input.as_bytes(),
+            &mut output,
+            RecordFormat::Lines,
+            &Ascending,
+            &small_config(&dir),
+        )
+        .unwrap();
+
+        let mut expected = lines.clone();
+        expected.sort();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            expected.join("\n") + "\n"
+        );
+        assert_eq!(stats.records, 2000);
+        assert!(stats.runs > 3, "runs: {}", stats.runs);
+        assert!(stats.merge_passes > 1, "passes: {}", stats.merge_passes);
+
+        // 一時ファイルは全て削除されている
+        assert_eq!(fs::read_dir(&dir).unwrap().count(), 0);
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn sort_binary_records() {
+        let dir = temp_dir("binary");
+        let config = small_config(&dir);
+
+        let values = new_u32_vec(3000);
+        let input = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+        let mut output = Vec::new();
+        sort(
+            &input[..],
+            &mut output,
+            RecordFormat::U32,
+            &Descending,
+            &config,
+        )
+        .unwrap();
+        let sorted = output
+            .chunks(4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .collect::<Vec<_>>();
+        let mut expected = values.clone();
+        expected.sort_by(|a, b| b.cmp(a));
+        assert_eq!(sorted, expected);
+
+        let values = values
+            .iter()
+            .map(|&v| (v as u64) << 32 | (v as u64 % 7))
+            .collect::<Vec<_>>();
+        let input = values
+            .iter()
+            .flat_map(|v| v.to_le_bytes().to_vec())
+            .collect::<Vec<_>>();
+        let mut output = Vec::new();
+        let stats = sort(
+            &input[..],
+            &mut output,
+            RecordFormat::U64,
+            &Ascending,
+            &config,
+        )
+        .unwrap();
+        assert_eq!(stats.records, 3000);
+        let sorted = output
+            .chunks(8)
+            .map(|b| {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(b);
+                u64::from_le_bytes(bytes)
+            })
+            .collect::<Vec<_>>();
+        let mut expected = values;
+        expected.sort_unstable();
+        assert_eq!(sorted, expected);
+
+        fs::remove_dir(&dir).unwrap();
+    }
+
+    #[test]
+    fn sort_in_memory() {
+        let mut output = Vec::new();
+        let stats = sort(
+            &b"pear\napple\n\nfig"[..],
+            &mut output,
+            RecordFormat::Lines,
+            &Ascending,
+            &ExternalSortConfig::default(),
+        )
+        .unwrap();
+        assert_eq!(output, b"\napple\nfig\npear\n");
+        assert_eq!(
+            stats,
+            SortStats {
+                records: 4,
+                runs: 1,
+                merge_passes: 0
+            }
+        );
+
+        let mut output = Vec::new();
+        let stats = sort(
+            &b""[..],
+            &mut output,
+            RecordFormat::U32,
+            &Ascending,
+            &ExternalSortConfig::default(),
+        )
+        .unwrap();
+        assert!(output.is_empty());
+        assert_eq!(stats.records, 0);
+    }
+
+    #[test]
+    fn reject_invalid_input_and_config() {
+        let config = ExternalSortConfig::default();
+        assert!(matches!(
+            sort(
+                &[1, 2, 3, 4, 5][..],
+                io::sink(),
+                RecordFormat::U32,
+                &Ascending,
+                &config
+            ),
+            Err(ExternalSortError::TruncatedRecord(4))
+        ));
+
+        let config = ExternalSortConfig {
+            memory_budget: 100,
+            ..ExternalSortConfig::default()
+        };
+        assert!(matches!(
+            sort(
+                &b"a"[..],
+                io::sink(),
+                RecordFormat::Lines,
+                &Ascending,
+                &config
+            ),
+            Err(ExternalSortError::BudgetTooSmall(100))
+        ));
+
+        assert!(matches!(
+            "u16".parse::<RecordFormat>(),
+            Err(ExternalSortError::UnknownFormat(_))
+        ));
+    }
+}
@@ -1,108 +1,365 @@
-use crate::ch03::SortOrder;
+use crate::ch03::small_sort;
+use crate::ch03::util::new_u32_vec;
+use crate::ch03::{ComparisonSorter, SortConfig, SortOrder, Sorter};
+use rayon::prelude::*;
 use std::cmp::Ordering;
+use std::time::{Duration, Instant};
 
-const PARALLEL_THRESHOLD: usize = 4096;
+/// 並列化したバイトニックソート。安定ではない
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BitonicSort;
 
-pub fn sort<T: Ord + Send>(x: &mut [T], order: &SortOrder) -> Result<(), String> {
+impl<T: Ord + Send> Sorter<T> for BitonicSort {
+    fn name(&self) -> &'static str {
+        "bitonic"
+    }
+
+    fn is_stable(&self) -> bool {
+        false
+    }
+
+    fn sort(&self, x: &mut [T], order: &SortOrder) {
+        sort(x, order);
+    }
+}
+
+impl<T: Ord + Send> ComparisonSorter<T> for BitonicSort {
+    fn sort_by(&self, x: &mut [T], comparator: &(dyn Fn(&T, &T) -> Ordering + Sync)) {
+        sort_by(x, &|a, b| comparator(a, b));
+    }
+}
+
+/// x をソートする
+///
+/// 要素数が 2 のべき乗でなくてもソートできる。
+pub fn sort<T: Ord + Send>(x: &mut [T], order: &SortOrder) {
+    sort_with_config(x, order, &SortConfig::default());
+}
+
+/// config に従って x をソートする
+pub fn sort_with_config<T: Ord + Send>(x: &mut [T], order: &SortOrder, config: &SortConfig) {
     match *order {
-        SortOrder::Ascending => sort_by(x, &|a, b| a.cmp(b)),
-        SortOrder::Descending => sort_by(x, &|a, b| b.cmp(a)),
+        SortOrder::Ascending => sort_by_with_config(x, &|a, b| a.cmp(b), config),
+        SortOrder::Descending => sort_by_with_config(x, &|a, b| b.cmp(a), config),
     }
 }
 
-pub fn sort_by<T, F>(x: &mut [T], comparator: &F) -> Result<(), String>
+/// comparator が返す順序に従って x をソートする
+///
+/// 要素数が 2 のべき乗でなくてもソートできる。
+pub fn sort_by<T, F>(x: &mut [T], comparator: &F)
 where
     T: Send,
     F: Fn(&T, &T) -> Ordering + Sync,
 {
-    if x.len().is_power_of_two() {
-        do_sort(x, true, comparator);
-        Ok(())
-    } else {
-        Err(format!(
-            "The length of x is not a power of two. (x.len(): {})",
-            x.len()
-        ))
+    sort_by_with_config(x, comparator, &SortConfig::default());
+}
+
+/// config に従って、comparator が返す順序で x をソートする
+pub fn sort_by_with_config<T, F>(x: &mut [T], comparator: &F, config: &SortConfig)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    sort_with_kernel(x, true, &ComparatorKernel(comparator), config);
+}
+
+/// key が返すキーの order の順に x をソートする
+///
+/// キーは比較のたびに計算する。計算に時間がかかるキーには [`sort_by_cached_key`] を使う。
+pub fn sort_by_key<T, K, F>(x: &mut [T], key: &F, order: &SortOrder)
+where
+    T: Send,
+    K: Ord,
+    F: Fn(&T) -> K + Sync,
+{
+    match *order {
+        SortOrder::Ascending => sort_by(x, &|a, b| key(a).cmp(&key(b))),
+        SortOrder::Descending => sort_by(x, &|a, b| key(b).cmp(&key(a))),
+    }
+}
+
+/// key が返すキーの order の順に x を安定ソートする
+///
+/// 各要素のキーを並列に 1 回ずつ計算し、キーで添字をソートしてから、
+/// その順列に従って x の要素をその場で並べ替える。
+pub fn sort_by_cached_key<T, K, F>(x: &mut [T], key: &F, order: &SortOrder)
+where
+    T: Send + Sync,
+    K: Ord + Send + Sync,
+    F: Fn(&T) -> K + Sync,
+{
+    let keys = x.par_iter().map(key).collect::<Vec<_>>();
+    let indices = argsort(&keys, order);
+    apply_permutation(x, indices);
+}
+
+/// x を order の順に安定ソートした時に、各位置に来る要素の元の添字を返す
+///
+/// x 自体は並べ替えない。
+pub fn argsort<T: Ord + Sync>(x: &[T], order: &SortOrder) -> Vec<usize> {
+    match *order {
+        SortOrder::Ascending => argsort_by(x, &|a, b| a.cmp(b)),
+        SortOrder::Descending => argsort_by(x, &|a, b| b.cmp(a)),
+    }
+}
+
+/// x を comparator が返す順序で安定ソートした時に、各位置に来る要素の元の添字を返す
+pub fn argsort_by<T, F>(x: &[T], comparator: &F) -> Vec<usize>
+where
+    T: Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    let mut indices = (0..x.len()).collect::<Vec<_>>();
+    // 等しい要素は元の添字の順に並べ、安定にする
+    sort_by(&mut indices, &|&i, &j| {
+        comparator(&x[i], &x[j]).then(i.cmp(&j))
+    });
+    indices
+}
+
+/// x[i] に元の x[indices[i]] が来るように、x をその場で並べ替える
+///
+/// 順列を巡回ごとにたどり、たどった位置は indices[i] = i として印を付ける。
+fn apply_permutation<T>(x: &mut [T], mut indices: Vec<usize>) {
+    for start in 0..x.len() {
+        let mut i = start;
+        loop {
+            let j = indices[i];
+            indices[i] = i;
+            if j == start {
+                break;
+            }
+            x.swap(i, j);
+            i = j;
+        }
     }
 }
 
-fn do_sort<T, F>(x: &mut [T], forward: bool, comparator: &F)
+/// config に従って、kernel で x を forward の向きにソートする
+pub(crate) fn sort_with_kernel<T, K>(x: &mut [T], forward: bool, kernel: &K, config: &SortConfig)
 where
     T: Send,
+    K: Kernel<T>,
+{
+    config.install(|| do_sort(x, forward, kernel, config, 0));
+}
+
+/// バイトニック列 x を、並列化せずに kernel で forward の向きにマージする
+pub(crate) fn merge_seq<T: Send, K: Kernel<T>>(x: &mut [T], forward: bool, kernel: &K) {
+    let config = SortConfig {
+        max_parallel_depth: 0,
+        ..SortConfig::default()
+    };
+    sub_sort(x, forward, kernel, &config, 0);
+}
+
+/// [`do_sort`] と [`sub_sort`] が要素を比較して並べ替える処理
+///
+/// 比較関数を使う [`ComparatorKernel`] のほかに、
+/// [`simd::Backend`](crate::ch03::simd::Backend) が SIMD 命令を使う実装を持つ。
+pub(crate) trait Kernel<T>: Sync {
+    /// 要素数がこれ以下の区間は [`Kernel::sort_leaf`] でソートする
+    fn leaf_len(&self) -> usize;
+
+    /// 要素数 leaf_len() 以下の x を forward の向きにソートする
+    fn sort_leaf(&self, x: &mut [T], forward: bool);
+
+    /// x[i] と x[mid + i] を比較し、順序が forward の向きと逆なら交換する
+    fn compare_and_swap(&self, x: &mut [T], mid: usize, forward: bool);
+
+    /// バイトニック列 x を分割せずにマージできる場合はマージして true を返す
+    fn merge_leaf(&self, _x: &mut [T], _forward: bool) -> bool {
+        false
+    }
+}
+
+/// 比較関数で要素を比べる [`Kernel`]。短い区間は [`small_sort`] でソートする
+struct ComparatorKernel<'a, F>(&'a F);
+
+impl<T, F> Kernel<T> for ComparatorKernel<'_, F>
+where
     F: Fn(&T, &T) -> Ordering + Sync,
 {
-    if x.len() > 1 {
+    fn leaf_len(&self) -> usize {
+        small_sort::MAX_LEN
+    }
+
+    fn sort_leaf(&self, x: &mut [T], forward: bool) {
+        small_sort::sort_by(x, forward, self.0);
+    }
+
+    fn compare_and_swap(&self, x: &mut [T], mid: usize, forward: bool) {
+        let swap_condition = if forward {
+            Ordering::Greater
+        } else {
+            Ordering::Less
+        };
+
+        for i in 0..x.len() - mid {
+            if (self.0)(&x[i], &x[mid + i]) == swap_condition {
+                x.swap(i, mid + i);
+            }
+        }
+    }
+}
+
+/// 任意の要素数に対応したバイトニックソート
+///
+/// 前半を逆順に、後半を forward の向きにソートすると全体がバイトニック列になるので、
+/// それを [`sub_sort`] でマージする。depth はここまでに並列に分割した回数。
+///
+/// 短い区間は kernel の葉の処理でソートする。
+fn do_sort<T, K>(x: &mut [T], forward: bool, kernel: &K, config: &SortConfig, depth: usize)
+where
+    T: Send,
+    K: Kernel<T>,
+{
+    if x.len() <= kernel.leaf_len() {
+        kernel.sort_leaf(x, forward);
+    } else {
         let mid = x.len() / 2;
         let (first, second) = x.split_at_mut(mid);
-        if mid >= PARALLEL_THRESHOLD {
+        if config.forks(mid, config.sort_threshold, depth) {
             rayon::join(
-                || do_sort(first, true, comparator),
-                || do_sort(second, false, comparator),
+                || do_sort(first, !forward, kernel, config, depth + 1),
+                || do_sort(second, forward, kernel, config, depth + 1),
             );
         } else {
-            do_sort(first, true, comparator);
-            do_sort(second, false, comparator);
+            do_sort(first, !forward, kernel, config, depth);
+            do_sort(second, forward, kernel, config, depth);
         }
-        sub_sort(x, forward, comparator);
+        sub_sort(x, forward, kernel, config, depth);
     }
 }
 
-fn sub_sort<T, F>(x: &mut [T], forward: bool, comparator: &F)
+/// バイトニック列 x を forward の向きにマージする
+///
+/// x.len() 未満で最大の 2 のべき乗 mid を境に比較と交換を行うと、
+/// 前半の要素は全て後半の要素以下 (forward が false なら以上) のバイトニック列になる。
+fn sub_sort<T, K>(x: &mut [T], forward: bool, kernel: &K, config: &SortConfig, depth: usize)
 where
     T: Send,
-    F: Fn(&T, &T) -> Ordering + Sync,
+    K: Kernel<T>,
 {
-    if x.len() > 1 {
-        compare_and_swap(x, forward, comparator);
-        let mid = x.len() / 2;
+    if x.len() > 1 && !kernel.merge_leaf(x, forward) {
+        let mid = greatest_power_of_two_less_than(x.len());
+        kernel.compare_and_swap(x, mid, forward);
         let (first, second) = x.split_at_mut(mid);
-        if mid > PARALLEL_THRESHOLD {
+        if config.forks(mid, config.merge_threshold, depth) {
             rayon::join(
-                || sub_sort(first, forward, comparator),
-                || sub_sort(second, forward, comparator),
+                || sub_sort(first, forward, kernel, config, depth + 1),
+                || sub_sort(second, forward, kernel, config, depth + 1),
             );
         } else {
-            sub_sort(first, forward, comparator);
-            sub_sort(second, forward, comparator);
+            sub_sort(first, forward, kernel, config, depth);
+            sub_sort(second, forward, kernel, config, depth);
         }
     }
 }
 
-fn compare_and_swap<T, F>(x: &mut [T], forward: bool, comparator: &F)
-where
-    F: Fn(&T, &T) -> Ordering,
-{
-    let swap_condition = if forward {
-        Ordering::Greater
-    } else {
-        Ordering::Less
+/// n (> 1) 未満で最大の 2 のべき乗を返す
+pub(crate) fn greatest_power_of_two_less_than(n: usize) -> usize {
+    1 << (usize::BITS - 1 - (n - 1).leading_zeros())
+}
+
+/// calibrate で 1 回にソートする要素数
+const CALIBRATION_LEN: usize = 1 << 18;
+
+/// calibrate で試すしきい値
+const CALIBRATION_THRESHOLDS: [usize; 8] = [256, 512, 1024, 2048, 4096, 8192, 16384, 32768];
+
+/// [`calibrate`] の結果
+#[derive(Debug, Clone)]
+pub struct Calibration {
+    /// 計測に使ったスレッド数
+    pub threads: usize,
+    /// sort_threshold の候補と、それぞれのソートにかかった時間
+    pub sort_timings: Vec<(usize, Duration)>,
+    /// merge_threshold の候補と、それぞれのソートにかかった時間
+    pub merge_timings: Vec<(usize, Duration)>,
+    /// 最も速かったしきい値を設定した config
+    pub config: SortConfig,
+}
+
+/// config のスレッドプールでソートの時間を計測し、このマシンに合ったしきい値を求める
+///
+/// まず sort_threshold を、次に求めた sort_threshold のもとで merge_threshold を、
+/// 候補ごとに 3 回ずつ計測して中央値が最も小さいものを選ぶ。
+/// 数秒かかるので、プログラムの起動時などに 1 回だけ実行して結果を使い回すとよい。
+pub fn calibrate(config: &SortConfig) -> Calibration {
+    calibrate_with(config, CALIBRATION_LEN)
+}
+
+/// 要素数 len の入力で計測する [`calibrate`]
+fn calibrate_with(config: &SortConfig, len: usize) -> Calibration {
+    let data = new_u32_vec(len);
+    let mut config = config.clone();
+
+    let measure = |config: &SortConfig| {
+        let mut times = (0..3)
+            .map(|_| {
+                let mut x = data.clone();
+                let start = Instant::now();
+                sort_with_config(&mut x, &SortOrder::Ascending, config);
+                start.elapsed()
+            })
+            .collect::<Vec<_>>();
+        times.sort();
+        times[1]
+    };
+    let sweep = |config: &SortConfig, set: &dyn Fn(&mut SortConfig, usize)| {
+        CALIBRATION_THRESHOLDS
+            .iter()
+            .map(|&threshold| {
+                let mut candidate = config.clone();
+                set(&mut candidate, threshold);
+                (threshold, measure(&candidate))
+            })
+            .collect::<Vec<_>>()
+    };
+    let fastest = |timings: &[(usize, Duration)]| {
+        timings
+            .iter()
+            .min_by_key(|(_, time)| *time)
+            .map(|(threshold, _)| *threshold)
+            .unwrap()
     };
 
-    let mid = x.len() / 2;
-    for i in 0..mid {
-        if comparator(&x[i], &x[mid + i]) == swap_condition {
-            x.swap(i, mid + i);
-        }
+    let sort_timings = sweep(&config, &|c, t| {
+        c.sort_threshold = t;
+        c.merge_threshold = t;
+    });
+    config.sort_threshold = fastest(&sort_timings);
+    let merge_timings = sweep(&config, &|c, t| c.merge_threshold = t);
+    config.merge_threshold = fastest(&merge_timings);
+
+    Calibration {
+        threads: config.install(rayon::current_num_threads),
+        sort_timings,
+        merge_timings,
+        config,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::ch03::util::{is_sorted, new_u32_vec};
+    use crate::ch03::common::test_util::{assert_runs_in_pool, assert_runs_on_caller, test_pool};
+    use crate::ch03::util::{assert_permutation, fingerprint, is_sorted, new_u32_vec};
     use crate::ch03::SortOrder::*;
 
     #[test]
     fn sort_u32_ascending() {
         let mut x: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
-        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        sort(&mut x, &Ascending);
         assert_eq!(x, vec![4, 10, 11, 20, 21, 30, 110, 330]);
     }
 
     #[test]
     fn sort_u32_descending() {
         let mut x: Vec<u32> = vec![10, 30, 11, 20, 4, 330, 21, 110];
-        assert_eq!(sort(&mut x, &Descending), Ok(()));
+        sort(&mut x, &Descending);
         assert_eq!(x, vec![330, 110, 30, 21, 20, 11, 10, 4]);
     }
 
@@ -118,7 +375,7 @@ mod tests {
             "no",
             "GC",
         ];
-        assert_eq!(sort(&mut x, &Ascending), Ok(()));
+        sort(&mut x, &Ascending);
         assert_eq!(
             x,
             vec![
@@ -146,7 +403,7 @@ mod tests {
             "no",
             "GC",
         ];
-        assert_eq!(sort(&mut x, &Descending), Ok(()));
+        sort(&mut x, &Descending);
         assert_eq!(
             x,
             vec![
@@ -163,9 +420,57 @@ mod tests {
     }
 
     #[test]
-    fn sort_to_fail() {
+    fn sort_not_power_of_two() {
         let mut x = vec![10, 30, 11];
-        assert!(sort(&mut x, &Ascending).is_err());
+        sort(&mut x, &Ascending);
+        assert_eq!(x, vec![10, 11, 30]);
+    }
+
+    /// 長さ 0 から 10000 までをソートできることを確かめる
+    ///
+    /// デバッグビルドで全ての長さを試すと時間がかかるので、1024 までは全て、
+    /// それより長いものは 2 のべき乗の前後と一定の間隔の長さを試す。
+    /// 10000 までの全ての長さは [`sort_all_lengths`] で試す。
+    #[test]
+    fn sort_every_length() {
+        let lengths = (0..=1024)
+            .chain((1025..=10000).step_by(97))
+            .chain((11..=13).flat_map(|bits| {
+                let n = 1 << bits;
+                vec![n - 1, n, n + 1]
+            }))
+            .chain(std::iter::once(10000));
+
+        for len in lengths {
+            check_length(len);
+        }
+    }
+
+    /// 0 から 10000 までの全ての長さを試す。リリースビルド (`cargo test --release`) でだけ実行する
+    #[test]
+    #[cfg_attr(debug_assertions, ignore)]
+    fn sort_all_lengths() {
+        for len in 0..=10000 {
+            check_length(len);
+        }
+    }
+
+    fn check_length(len: usize) {
+        let mut x = new_u32_vec(len);
+        let mut expected = x.clone();
+        expected.sort_unstable();
+        sort(&mut x, &Ascending);
+        assert_eq!(x, expected, "len: {}", len);
+
+        sort(&mut x, &Descending);
+        assert!(is_sorted(&x, &Descending), "len: {}", len);
+    }
+
+    #[test]
+    fn sort_with_duplicates() {
+        let mut x = (0..1000).map(|i| i % 7).collect::<Vec<u32>>();
+        sort(&mut x, &Ascending);
+        assert!(is_sorted(&x, &Ascending));
     }
 
     #[derive(Debug, PartialEq)]
@@ -195,7 +500,7 @@ mod tests {
         let mut x = vec![&taro, &hanako, &kyoko, &ryosuke];
         let expected = vec![&hanako, &kyoko, &taro, &ryosuke];
 
-        assert_eq!(sort_by(&mut x, &|a, b| a.age.cmp(&b.age)), Ok(()));
+        sort_by(&mut x, &|a, b| a.age.cmp(&b.age));
         assert_eq!(x, expected);
     }
 
@@ -209,27 +514,153 @@ mod tests {
         let mut x = vec![&taro, &hanako, &kyoko, &ryosuke];
         let expected = vec![&ryosuke, &kyoko, &hanako, &taro];
 
-        assert_eq!(
-            sort_by(&mut x, &|a, b| a
-                .last_name
+        sort_by(&mut x, &|a, b| {
+            a.last_name
                 .cmp(&b.last_name)
-                .then_with(|| a.first_name.cmp(&b.first_name))),
-            Ok(())
+                .then_with(|| a.first_name.cmp(&b.first_name))
+        });
+        assert_eq!(x, expected);
+    }
+
+    #[test]
+    fn calibrate_in_custom_pool() {
+        let config = SortConfig {
+            pool: Some(test_pool(2)),
+            ..SortConfig::default()
+        };
+        let calibration = calibrate_with(&config, 4096);
+
+        assert_eq!(calibration.threads, 2);
+        for timings in &[&calibration.sort_timings, &calibration.merge_timings] {
+            let thresholds = timings.iter().map(|&(t, _)| t).collect::<Vec<_>>();
+            assert_eq!(thresholds, CALIBRATION_THRESHOLDS);
+        }
+        assert!(CALIBRATION_THRESHOLDS.contains(&calibration.config.sort_threshold));
+        assert!(CALIBRATION_THRESHOLDS.contains(&calibration.config.merge_threshold));
+        // 求めた config も同じプールを使う
+        let pool = calibration.config.pool.as_ref().unwrap();
+        assert!(std::sync::Arc::ptr_eq(pool, config.pool.as_ref().unwrap()));
+    }
+
+    #[test]
+    fn sort_in_custom_pool() {
+        let config = SortConfig {
+            sort_threshold: 64,
+            merge_threshold: 64,
+            pool: Some(test_pool(3)),
+            ..SortConfig::default()
+        };
+        let mut x = new_u32_vec(10000);
+        assert_runs_in_pool(3, |cmp| {
+            sort_by_with_config(&mut x, &|a, b| cmp(a, b), &config)
+        });
+        assert!(is_sorted(&x, &Ascending));
+    }
+
+    #[test]
+    fn sort_without_parallelism() {
+        let config = SortConfig {
+            sort_threshold: 1,
+            merge_threshold: 1,
+            max_parallel_depth: 0,
+            ..SortConfig::default()
+        };
+        let mut x = new_u32_vec(5000);
+        assert_runs_on_caller(|cmp| sort_by_with_config(&mut x, &|a, b| cmp(a, b), &config));
+        assert!(is_sorted(&x, &Ascending));
+    }
+
+    #[test]
+    fn sort_students_by_key() {
+        let taro = Student::new("Taro", "Yamada", 16);
+        let hanako = Student::new("Hanako", "Yamada", 14);
+        let kyoko = Student::new("Kyoko", "Ito", 15);
+        let ryosuke = Student::new("Ryosuke", "Hayashi", 17);
+
+        let mut x = vec![&taro, &hanako, &kyoko, &ryosuke];
+        sort_by_key(&mut x, &|s| s.age, &Ascending);
+        assert_eq!(x, vec![&hanako, &kyoko, &taro, &ryosuke]);
+
+        let mut x = vec![&taro, &hanako, &kyoko, &ryosuke];
+        sort_by_cached_key(
+            &mut x,
+            &|s| (s.last_name.clone(), s.first_name.clone()),
+            &Descending,
+        );
+        assert_eq!(x, vec![&taro, &hanako, &kyoko, &ryosuke]);
+    }
+
+    #[test]
+    fn cached_key_is_computed_once() {
+        use std::sync::atomic::{AtomicUsize, Ordering as AtomicOrdering};
+
+        let calls = AtomicUsize::new(0);
+        let mut x = new_u32_vec(10000);
+        let mut expected = x.clone();
+        expected.sort_by_key(|v| v.to_string());
+
+        sort_by_cached_key(
+            &mut x,
+            &|v| {
+                calls.fetch_add(1, AtomicOrdering::Relaxed);
+                v.to_string()
+            },
+            &Ascending,
         );
         assert_eq!(x, expected);
+        assert_eq!(calls.load(AtomicOrdering::Relaxed), 10000);
+    }
+
+    #[test]
+    fn sort_by_cached_key_is_stable() {
+        let mut x = new_u32_vec(5000)
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| (v % 10, i))
+            .collect::<Vec<_>>();
+        sort_by_cached_key(&mut x, &|&(k, _)| k, &Descending);
+        assert!(x.windows(2).all(|w| w[0].0 > w[1].0 || w[0].1 < w[1].1));
+    }
+
+    #[test]
+    fn argsort_returns_permutation() {
+        let x = vec!["pear", "apple", "fig", "apple"];
+        assert_eq!(argsort(&x, &Ascending), vec![1, 3, 2, 0]);
+        assert_eq!(argsort(&x, &Descending), vec![0, 2, 1, 3]);
+        assert_eq!(
+            argsort_by(&x, &|a, b| a.len().cmp(&b.len())),
+            vec![2, 0, 1, 3]
+        );
+        assert!(argsort::<u32>(&[], &Ascending).is_empty());
+
+        let x = new_u32_vec(20000);
+        let indices = argsort(&x, &Ascending);
+        let sorted = indices.iter().map(|&i| x[i]).collect::<Vec<_>>();
+        assert!(is_sorted(&sorted, &Ascending));
+
+        let mut y = x.clone();
+        apply_permutation(&mut y, indices);
+        assert_eq!(y, sorted);
     }
 
     #[test]
     fn sort_u32_large() {
         {
             let mut x = new_u32_vec(65536);
-            assert_eq!(sort(&mut x, &Ascending), Ok(()));
+            sort(&mut x, &Ascending);
             assert!(is_sorted(&x, &Ascending));
         }
         {
             let mut x = new_u32_vec(65536);
-            assert_eq!(sort(&mut x, &Descending), Ok(()));
+            sort(&mut x, &Descending);
             assert!(is_sorted(&x, &Descending));
         }
+        {
+            let mut x = new_u32_vec(100000);
+            let expected = fingerprint(&x);
+            sort(&mut x, &Ascending);
+            assert!(is_sorted(&x, &Ascending));
+            assert_permutation(&expected, &x);
+        }
     }
 }
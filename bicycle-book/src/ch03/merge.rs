@@ -0,0 +1,231 @@
+use crate::ch03::common::{insertion_sort, INSERTION_THRESHOLD};
+use crate::ch03::{ComparisonSorter, SortConfig, SortOrder, Sorter};
+use std::cmp::Ordering;
+
+/// 並列化したマージソート。安定である
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MergeSort;
+
+impl<T: Ord + Clone + Send + Sync> Sorter<T> for MergeSort {
+    fn name(&self) -> &'static str {
+        "merge"
+    }
+
+    fn is_stable(&self) -> bool {
+        true
+    }
+
+    fn sort(&self, x: &mut [T], order: &SortOrder) {
+        sort(x, order);
+    }
+}
+
+impl<T: Ord + Clone + Send + Sync> ComparisonSorter<T> for MergeSort {
+    fn sort_by(&self, x: &mut [T], comparator: &(dyn Fn(&T, &T) -> Ordering + Sync)) {
+        sort_by(x, &|a, b| comparator(a, b));
+    }
+}
+
+/// x を安定ソートする
+pub fn sort<T: Ord + Clone + Send + Sync>(x: &mut [T], order: &SortOrder) {
+    sort_with_config(x, order, &SortConfig::default());
+}
+
+/// config に従って x を安定ソートする
+pub fn sort_with_config<T: Ord + Clone + Send + Sync>(
+    x: &mut [T],
+    order: &SortOrder,
+    config: &SortConfig,
+) {
+    match *order {
+        SortOrder::Ascending => sort_by_with_config(x, &|a, b| a.cmp(b), config),
+        SortOrder::Descending => sort_by_with_config(x, &|a, b| b.cmp(a), config),
+    }
+}
+
+/// comparator が返す順序に従って x を安定ソートする
+///
+/// x と同じ長さの作業領域を確保する。
+pub fn sort_by<T, F>(x: &mut [T], comparator: &F)
+where
+    T: Clone + Send + Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    sort_by_with_config(x, comparator, &SortConfig::default());
+}
+
+/// config に従って、comparator が返す順序で x を安定ソートする
+pub fn sort_by_with_config<T, F>(x: &mut [T], comparator: &F, config: &SortConfig)
+where
+    T: Clone + Send + Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    if x.len() > 1 {
+        let mut buf = x.to_vec();
+        config.install(|| do_sort(x, &mut buf, comparator, config, 0));
+    }
+}
+
+/// buf を作業領域にして x をソートする
+fn do_sort<T, F>(x: &mut [T], buf: &mut [T], comparator: &F, config: &SortConfig, depth: usize)
+where
+    T: Clone + Send + Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    if x.len() <= INSERTION_THRESHOLD {
+        insertion_sort(x, comparator);
+        return;
+    }
+
+    let mid = x.len() / 2;
+    {
+        let (first, second) = x.split_at_mut(mid);
+        let (first_buf, second_buf) = buf.split_at_mut(mid);
+        if config.forks(mid, config.sort_threshold, depth) {
+            rayon::join(
+                || do_sort(first, first_buf, comparator, config, depth + 1),
+                || do_sort(second, second_buf, comparator, config, depth + 1),
+            );
+        } else {
+            do_sort(first, first_buf, comparator, config, depth);
+            do_sort(second, second_buf, comparator, config, depth);
+        }
+    }
+
+    // 前半の最後が後半の先頭以下なら、すでに全体が並んでいる
+    if comparator(&x[mid - 1], &x[mid]) != Ordering::Greater {
+        return;
+    }
+    let (first, second) = x.split_at(mid);
+    merge(first, second, buf, comparator, config, depth);
+    x.clone_from_slice(buf);
+}
+
+/// ソート済みの a と b を out にマージする
+///
+/// 等しい要素は a のものを先に置く。長い区間は、長い方の中央の要素で両方を分割して並列にマージする。
+fn merge<T, F>(a: &[T], b: &[T], out: &mut [T], comparator: &F, config: &SortConfig, depth: usize)
+where
+    T: Clone + Send + Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    if !config.forks(a.len() + b.len(), config.merge_threshold, depth) {
+        merge_seq(a, b, out, comparator);
+        return;
+    }
+
+    // (a を分割する位置, b を分割する位置) を決める。
+    // 等しい要素について a が先になるよう、a の要素より小さい b の要素だけを前に、
+    // b の要素以下の a の要素を前に置く。
+    let (i, j) = if a.len() >= b.len() {
+        let i = a.len() / 2;
+        let j = b.partition_point(|y| comparator(y, &a[i]) == Ordering::Less);
+        (i, j)
+    } else {
+        let j = b.len() / 2;
+        let i = a.partition_point(|x| comparator(x, &b[j]) != Ordering::Greater);
+        (i, j)
+    };
+
+    let (a1, a2) = a.split_at(i);
+    let (b1, b2) = b.split_at(j);
+    let (out1, out2) = out.split_at_mut(i + j);
+    rayon::join(
+        || merge(a1, b1, out1, comparator, config, depth + 1),
+        || merge(a2, b2, out2, comparator, config, depth + 1),
+    );
+}
+
+fn merge_seq<T, F>(a: &[T], b: &[T], out: &mut [T], comparator: &F)
+where
+    T: Clone,
+    F: Fn(&T, &T) -> Ordering,
+{
+    let (mut i, mut j) = (0, 0);
+    for slot in out.iter_mut() {
+        let take_a = j == b.len() || (i < a.len() && comparator(&a[i], &b[j]) != Ordering::Greater);
+        if take_a {
+            slot.clone_from(&a[i]);
+            i += 1;
+        } else {
+            slot.clone_from(&b[j]);
+            j += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch03::common::test_util::assert_runs_on_caller;
+    use crate::ch03::util::{
+        assert_permutation, assert_stably_sorted_by_key, fingerprint, is_sorted, new_u32_vec,
+    };
+    use crate::ch03::SortOrder::*;
+
+    #[test]
+    fn sort_u32() {
+        for &len in &[0, 1, 2, 15, 16, 17, 1000, 4095, 4096, 10000, 65536] {
+            let mut x = new_u32_vec(len);
+            let mut expected = x.clone();
+            expected.sort();
+            sort(&mut x, &Ascending);
+            assert_eq!(x, expected, "len: {}", len);
+
+            sort(&mut x, &Descending);
+            assert!(is_sorted(&x, &Descending), "len: {}", len);
+        }
+    }
+
+    #[test]
+    fn sort_is_stable() {
+        // 値の種類が少ないキーでソートし、同じキーの中で元の順番が保たれているか確かめる
+        let mut x = new_u32_vec(50000)
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| (v % 10, i))
+            .collect::<Vec<_>>();
+        sort_by(&mut x, &|a, b| a.0.cmp(&b.0));
+        assert!(x.windows(2).all(|w| w[0].0 < w[1].0 || w[0].1 < w[1].1));
+    }
+
+    #[test]
+    fn sort_by_keeps_elements_in_stable_order() {
+        let input = new_u32_vec(30000)
+            .into_iter()
+            .map(|v| (v % 100, v))
+            .collect::<Vec<_>>();
+        let expected = fingerprint(&input);
+        for order in &[Ascending, Descending] {
+            let mut x = input.clone();
+            match *order {
+                Ascending => sort_by(&mut x, &|a, b| a.0.cmp(&b.0)),
+                Descending => sort_by(&mut x, &|a, b| b.0.cmp(&a.0)),
+            }
+            assert_permutation(&expected, &x);
+            assert_stably_sorted_by_key(&input, &x, &|&(k, _)| k, order);
+        }
+    }
+
+    #[test]
+    fn sort_by_trait_object() {
+        let sorter: &dyn ComparisonSorter<&str> = &MergeSort;
+        let mut x = vec!["bb", "a", "ccc", "dd", "e"];
+        sorter.sort_by(&mut x, &|a, b| a.len().cmp(&b.len()));
+        assert_eq!(x, vec!["a", "e", "bb", "dd", "ccc"]);
+        assert!(Sorter::<&str>::is_stable(&MergeSort));
+    }
+
+    #[test]
+    fn sort_without_parallelism() {
+        let config = SortConfig {
+            sort_threshold: 1,
+            merge_threshold: 1,
+            max_parallel_depth: 0,
+            ..SortConfig::default()
+        };
+        let mut x = new_u32_vec(5000);
+        assert_runs_on_caller(|cmp| sort_by_with_config(&mut x, &|a, b| cmp(a, b), &config));
+        assert!(is_sorted(&x, &Ascending));
+    }
+}
@@ -0,0 +1,803 @@
+//! 比較器ネットワーク (ソーティングネットワーク)
+//!
+//! [`first`](crate::ch03::first) や [`fourth`](crate::ch03::fourth) のバイトニックソートは、
+//! 比較する要素の組が入力の値によらず決まっている。その組の列をデータとして作り、
+//! 図にしたり、0-1 原理で正しさを確かめたり、Rust のコードに変換したりする。
+
+use crate::ch03::fourth::greatest_power_of_two_less_than;
+use std::fmt::Write;
+use thiserror::Error;
+
+/// [`Network::verify`] で調べられる線の数の上限 (2^24 通りの入力を試す)
+pub const MAX_VERIFY_SIZE: usize = 24;
+
+/// SVG の図で、線と比較器の間隔 (ピクセル)
+const SVG_SPACING: usize = 20;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum NetworkError {
+    #[error("comparator ({0}, {1}) is out of range for a network of size {2}")]
+    OutOfRange(usize, usize, usize),
+    #[error("comparator ({0}, {0}) compares a wire with itself")]
+    SameWire(usize),
+    #[error(
+        "cannot verify a network of size {0} (at most {} wires)",
+        MAX_VERIFY_SIZE
+    )]
+    TooLargeToVerify(usize),
+    /// ソートできなかった 0-1 の入力。ビット i が線 i の値を表す
+    #[error("the network does not sort the 0-1 input {0:#b} (bit i is wire i)")]
+    Unsorted(u64),
+}
+
+/// 生成する Rust のコードの形
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CodeStyle {
+    /// 比較関数と向きを受け取り、順序が逆なら交換する。任意の型をソートできる
+    Comparator,
+    /// `T: Ord + Copy` の値を min と max で並べる。分岐を含まない
+    Branchless,
+}
+
+/// 比較器ネットワーク
+///
+/// 比較器 `(a, b)` は線 a と線 b の値を比べ、小さい方を a に、大きい方を b に置く。
+/// a が b より大きくてもよい。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Network {
+    size: usize,
+    comparators: Vec<(usize, usize)>,
+}
+
+impl Network {
+    pub fn new(size: usize, comparators: Vec<(usize, usize)>) -> Result<Self, NetworkError> {
+        for &(a, b) in &comparators {
+            if a >= size || b >= size {
+                return Err(NetworkError::OutOfRange(a, b, size));
+            }
+            if a == b {
+                return Err(NetworkError::SameWire(a));
+            }
+        }
+        Ok(Self { size, comparators })
+    }
+
+    /// 任意の要素数に対応したバイトニックソートのネットワーク
+    ///
+    /// [`fourth`](crate::ch03::fourth) と同じ順に比較する。
+    pub fn bitonic(size: usize) -> Self {
+        let mut comparators = Vec::new();
+        bitonic_sort(&mut comparators, 0, size, true);
+        Self { size, comparators }
+    }
+
+    /// Batcher の奇偶マージソートのネットワーク
+    ///
+    /// 2 のべき乗の大きさのネットワークから、範囲外の線に触れる比較器を除いたもの。
+    /// 範囲外の線には常に最大の値があると考えれば、除いた比較器は何もしない。
+    pub fn odd_even_merge(size: usize) -> Self {
+        let mut comparators = Vec::new();
+        let mut p = 1;
+        while p < size {
+            let mut k = p;
+            while k >= 1 {
+                let mut j = k % p;
+                while j + k < size {
+                    for i in 0..k.min(size - j - k) {
+                        if (i + j) / (p * 2) == (i + j + k) / (p * 2) {
+                            comparators.push((i + j, i + j + k));
+                        }
+                    }
+                    j += k * 2;
+                }
+                k /= 2;
+            }
+            p *= 2;
+        }
+        Self { size, comparators }
+    }
+
+    /// 16 要素以下の、比較器の数が最小 (または最小に近い) のネットワーク
+    ///
+    /// 12 から 15 要素は 16 要素のネットワークから上の線を取り除いたもので、
+    /// 12 と 13 要素の比較器の数は既知の最小より 1 つ多い。
+    pub fn optimal(size: usize) -> Option<Self> {
+        let comparators = match size {
+            0..=11 => OPTIMAL[size].to_vec(),
+            12..=16 => OPTIMAL_16
+                .iter()
+                .copied()
+                .filter(|&(_, b)| b < size)
+                .collect(),
+            _ => return None,
+        };
+        Some(Self { size, comparators })
+    }
+
+    /// 線の数
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// 比較器を適用する順に返す
+    pub fn comparators(&self) -> &[(usize, usize)] {
+        &self.comparators
+    }
+
+    /// 比較器を、同時に適用できるものごとの層に分ける
+    ///
+    /// 各比較器は、その 2 本の線に触れる比較器を含む最後の層の次の層に入れる。
+    pub fn layers(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut level = vec![0; self.size];
+        let mut layers: Vec<Vec<(usize, usize)>> = Vec::new();
+        for &(a, b) in &self.comparators {
+            let l = level[a].max(level[b]);
+            if l == layers.len() {
+                layers.push(Vec::new());
+            }
+            layers[l].push((a, b));
+            level[a] = l + 1;
+            level[b] = l + 1;
+        }
+        layers
+    }
+
+    /// 層の数
+    pub fn depth(&self) -> usize {
+        self.layers().len()
+    }
+
+    /// x にネットワークを適用する
+    pub fn apply<T: Ord>(&self, x: &mut [T]) {
+        assert_eq!(
+            x.len(),
+            self.size,
+            "the length of x must be the network size"
+        );
+        for &(a, b) in &self.comparators {
+            if x[a] > x[b] {
+                x.swap(a, b);
+            }
+        }
+    }
+
+    /// 0 と 1 からなる 2^n 通りの入力を全てソートできるか調べる
+    ///
+    /// 0-1 原理により、これらを全てソートできるネットワークは任意の入力をソートできる。
+    pub fn verify(&self) -> Result<(), NetworkError> {
+        if self.size > MAX_VERIFY_SIZE {
+            return Err(NetworkError::TooLargeToVerify(self.size));
+        }
+        let all = (1u64 << self.size) - 1;
+        for input in 0..=all {
+            let mut bits = input;
+            for &(a, b) in &self.comparators {
+                // 線 a が 1 で線 b が 0 なら入れ替える
+                if (bits >> a) & 1 == 1 && (bits >> b) & 1 == 0 {
+                    bits ^= (1 << a) | (1 << b);
+                }
+            }
+            // ソートされていれば、1 は添字の大きい線に集まっている
+            let zeros = self.size - bits.count_ones() as usize;
+            if bits != all & !((1 << zeros) - 1) {
+                return Err(NetworkError::Unsorted(input));
+            }
+        }
+        Ok(())
+    }
+
+    /// 線を横に、比較器を縦に並べた図をテキストで描く
+    ///
+    /// 比較器の両端は `o` で表す。小さい方が添字の大きい (下の) 線に行く比較器は、
+    /// 上端を `^` で表す。
+    pub fn to_text(&self) -> String {
+        if self.size == 0 {
+            return String::new();
+        }
+        let columns = self.columns();
+        let mut grid = (0..self.size * 2 - 1)
+            .map(|row| {
+                let c = if row % 2 == 0 { '-' } else { ' ' };
+                vec![c; columns.len() * 3 + 1]
+            })
+            .collect::<Vec<_>>();
+        for (i, column) in columns.iter().enumerate() {
+            let x = i * 3 + 1;
+            for &(a, b) in column {
+                let (top, bottom) = (a.min(b), a.max(b));
+                for row in grid.iter_mut().take(bottom * 2 + 1).skip(top * 2) {
+                    row[x] = '|';
+                }
+                grid[top * 2][x] = if a < b { 'o' } else { '^' };
+                grid[bottom * 2][x] = 'o';
+            }
+        }
+
+        let width = (self.size - 1).to_string().len();
+        let mut text = String::new();
+        for (row, line) in grid.iter().enumerate() {
+            let label = if row % 2 == 0 {
+                (row / 2).to_string()
+            } else {
+                String::new()
+            };
+            let line = line.iter().collect::<String>();
+            let line = format!("{:>width$} {}", label, line, width = width);
+            writeln!(text, "{}", line.trim_end()).unwrap();
+        }
+        text
+    }
+
+    /// 線を横に、比較器を縦に並べた図を SVG で描く
+    ///
+    /// 小さい方が添字の大きい (下の) 線に行く比較器は赤で描く。
+    pub fn to_svg(&self) -> String {
+        let columns = self.columns();
+        let width = (columns.len() + 1) * SVG_SPACING;
+        let height = self.size.max(1) * SVG_SPACING;
+        let y = |wire: usize| wire * SVG_SPACING + SVG_SPACING / 2;
+
+        let mut svg = format!(
+            "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{0}\" height=\"{1}\" viewBox=\"0 0 {0} {1}\">\n",
+            width, height
+        );
+        for wire in 0..self.size {
+            writeln!(
+                svg,
+                "  <line x1=\"0\" y1=\"{1}\" x2=\"{0}\" y2=\"{1}\" stroke=\"#888\"/>",
+                width,
+                y(wire)
+            )
+            .unwrap();
+        }
+        for (i, column) in columns.iter().enumerate() {
+            let x = (i + 1) * SVG_SPACING;
+            for &(a, b) in column {
+                let color = if a < b { "#000" } else { "#c33" };
+                writeln!(
+                    svg,
+                    "  <line x1=\"{0}\" y1=\"{1}\" x2=\"{0}\" y2=\"{2}\" stroke=\"{3}\" stroke-width=\"2\"/>",
+                    x,
+                    y(a),
+                    y(b),
+                    color
+                )
+                .unwrap();
+                for &wire in &[a, b] {
+                    writeln!(
+                        svg,
+                        "  <circle cx=\"{}\" cy=\"{}\" r=\"4\" fill=\"{}\"/>",
+                        x,
+                        y(wire),
+                        color
+                    )
+                    .unwrap();
+                }
+            }
+        }
+        svg.push_str("</svg>\n");
+        svg
+    }
+
+    /// ネットワークの比較器を順に並べた Rust の関数を返す
+    ///
+    /// [`CodeStyle::Comparator`] の関数は、同じモジュールの `compare_and_swap`
+    /// ([`small_sort_module`] が生成するもの) を呼び出す。
+    pub fn to_rust(&self, name: &str, style: CodeStyle) -> String {
+        let mut code = format!(
+            "/// {} 要素をソートする (比較器 {} 個、深さ {})\n",
+            self.size,
+            self.comparators.len(),
+            self.depth()
+        );
+        match style {
+            CodeStyle::Comparator => {
+                writeln!(
+                    code,
+                    "pub fn {}<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)",
+                    name
+                )
+                .unwrap();
+                code.push_str("where\n    F: Fn(&T, &T) -> Ordering,\n{\n");
+                writeln!(code, "    let x = &mut x[..{}];", self.size).unwrap();
+                for &(a, b) in &self.comparators {
+                    writeln!(
+                        code,
+                        "    compare_and_swap(x, {}, {}, swap_condition, comparator);",
+                        a, b
+                    )
+                    .unwrap();
+                }
+            }
+            CodeStyle::Branchless => {
+                writeln!(
+                    code,
+                    "pub fn {}<T: Ord + Copy>(x: &mut [T; {}]) {{",
+                    name, self.size
+                )
+                .unwrap();
+                for &(a, b) in &self.comparators {
+                    writeln!(code, "    let (a, b) = (x[{}], x[{}]);", a, b).unwrap();
+                    writeln!(code, "    x[{}] = a.min(b);", a).unwrap();
+                    writeln!(code, "    x[{}] = a.max(b);", b).unwrap();
+                }
+            }
+        }
+        code.push_str("}\n");
+        code
+    }
+
+    /// 図を描くために、比較器を縦の範囲が重ならない列に分ける
+    fn columns(&self) -> Vec<Vec<(usize, usize)>> {
+        let mut columns = Vec::new();
+        for layer in self.layers() {
+            let mut layer_columns: Vec<Vec<(usize, usize)>> = Vec::new();
+            for (a, b) in layer {
+                let (top, bottom) = (a.min(b), a.max(b));
+                let overlaps = |&(c, d): &(usize, usize)| top <= c.max(d) && c.min(d) <= bottom;
+                match layer_columns
+                    .iter_mut()
+                    .find(|column| !column.iter().any(overlaps))
+                {
+                    Some(column) => column.push((a, b)),
+                    None => layer_columns.push(vec![(a, b)]),
+                }
+            }
+            columns.extend(layer_columns);
+        }
+        columns
+    }
+}
+
+fn bitonic_sort(comparators: &mut Vec<(usize, usize)>, lo: usize, n: usize, forward: bool) {
+    if n > 1 {
+        let mid = n / 2;
+        bitonic_sort(comparators, lo, mid, !forward);
+        bitonic_sort(comparators, lo + mid, n - mid, forward);
+        bitonic_merge(comparators, lo, n, forward);
+    }
+}
+
+fn bitonic_merge(comparators: &mut Vec<(usize, usize)>, lo: usize, n: usize, forward: bool) {
+    if n > 1 {
+        let mid = greatest_power_of_two_less_than(n);
+        for i in lo..lo + n - mid {
+            comparators.push(if forward { (i, i + mid) } else { (i + mid, i) });
+        }
+        bitonic_merge(comparators, lo, mid, forward);
+        bitonic_merge(comparators, lo + mid, n - mid, forward);
+    }
+}
+
+/// [`small_sort`](crate::ch03::small_sort) で扱う最大の要素数
+pub const SMALL_SORT_MAX_LEN: usize = 16;
+
+/// 2 から [`SMALL_SORT_MAX_LEN`] 要素の [`Network::optimal`] を関数にしたモジュールのソースコードを返す
+///
+/// `src/ch03/small_sort.rs` はこの関数の出力である。
+pub fn small_sort_module() -> String {
+    let mut code = String::from(
+        "//! 16 要素以下の固定長のソート
+//!
+//! このファイルは `cargo run --example ch03_network -- small-sort` で生成した。
+//! 直接編集しないこと。
+
+use std::cmp::Ordering;
+
+/// ネットワークでソートできる最大の要素数
+pub const MAX_LEN: usize = 16;
+
+/// 要素数 [`MAX_LEN`] 以下の x を、比較器ネットワークで forward の向きにソートする
+pub fn sort_by<T, F>(x: &mut [T], forward: bool, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let swap_condition = if forward {
+        Ordering::Greater
+    } else {
+        Ordering::Less
+    };
+    match x.len() {
+        0 | 1 => {}
+",
+    );
+    for n in 2..=SMALL_SORT_MAX_LEN {
+        writeln!(
+            code,
+            "        {} => sort{}(x, swap_condition, comparator),",
+            n, n
+        )
+        .unwrap();
+    }
+    code.push_str(
+        "        n => panic!(\"too many elements for a sorting network: {}\", n),
+    }
+}
+
+/// x[i] と x[j] を比較し、順序が swap_condition なら交換する
+#[inline(always)]
+fn compare_and_swap<T, F>(x: &mut [T], i: usize, j: usize, swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    if comparator(&x[i], &x[j]) == swap_condition {
+        x.swap(i, j);
+    }
+}
+",
+    );
+    for n in 2..=SMALL_SORT_MAX_LEN {
+        let network = Network::optimal(n).unwrap();
+        code.push('\n');
+        code.push_str(&network.to_rust(&format!("sort{}", n), CodeStyle::Comparator));
+    }
+    code
+}
+
+/// 11 要素以下の最適なネットワーク
+const OPTIMAL: [&[(usize, usize)]; 12] = [
+    &[],
+    &[],
+    &[(0, 1)],
+    &[(0, 2), (0, 1), (1, 2)],
+    &[(0, 2), (1, 3), (0, 1), (2, 3), (1, 2)],
+    &[
+        (0, 3),
+        (1, 4),
+        (0, 2),
+        (1, 3),
+        (0, 1),
+        (2, 4),
+        (1, 2),
+        (3, 4),
+        (2, 3),
+    ],
+    &[
+        (0, 5),
+        (1, 3),
+        (2, 4),
+        (1, 2),
+        (3, 4),
+        (0, 3),
+        (2, 5),
+        (0, 1),
+        (2, 3),
+        (4, 5),
+        (1, 2),
+        (3, 4),
+    ],
+    &[
+        (0, 6),
+        (2, 3),
+        (4, 5),
+        (0, 2),
+        (1, 4),
+        (3, 6),
+        (0, 1),
+        (2, 5),
+        (3, 4),
+        (1, 2),
+        (4, 6),
+        (2, 3),
+        (4, 5),
+        (1, 2),
+        (3, 4),
+        (5, 6),
+    ],
+    &[
+        (0, 2),
+        (1, 3),
+        (4, 6),
+        (5, 7),
+        (0, 4),
+        (1, 5),
+        (2, 6),
+        (3, 7),
+        (0, 1),
+        (2, 3),
+        (4, 5),
+        (6, 7),
+        (2, 4),
+        (3, 5),
+        (1, 4),
+        (3, 6),
+        (1, 2),
+        (3, 4),
+        (5, 6),
+    ],
+    &[
+        (0, 3),
+        (1, 7),
+        (2, 5),
+        (4, 8),
+        (0, 7),
+        (2, 4),
+        (3, 8),
+        (5, 6),
+        (0, 2),
+        (1, 3),
+        (4, 5),
+        (7, 8),
+        (1, 4),
+        (3, 6),
+        (5, 7),
+        (0, 1),
+        (2, 4),
+        (3, 5),
+        (6, 8),
+        (2, 3),
+        (4, 5),
+        (6, 7),
+        (1, 2),
+        (3, 4),
+        (5, 6),
+    ],
+    &[
+        (0, 8),
+        (1, 9),
+        (2, 7),
+        (3, 5),
+        (4, 6),
+        (0, 2),
+        (1, 4),
+        (5, 8),
+        (7, 9),
+        (0, 3),
+        (2, 4),
+        (5, 7),
+        (6, 9),
+        (0, 1),
+        (3, 6),
+        (8, 9),
+        (1, 5),
+        (2, 3),
+        (4, 8),
+        (6, 7),
+        (1, 2),
+        (3, 5),
+        (4, 6),
+        (7, 8),
+        (2, 3),
+        (4, 5),
+        (6, 7),
+        (3, 4),
+        (5, 6),
+    ],
+    &[
+        (0, 9),
+        (1, 6),
+        (2, 4),
+        (3, 7),
+        (5, 8),
+        (0, 1),
+        (3, 5),
+        (4, 10),
+        (6, 9),
+        (7, 8),
+        (1, 3),
+        (2, 5),
+        (4, 7),
+        (8, 10),
+        (0, 4),
+        (1, 2),
+        (3, 7),
+        (5, 9),
+        (6, 8),
+        (0, 1),
+        (2, 6),
+        (4, 5),
+        (7, 8),
+        (9, 10),
+        (2, 4),
+        (3, 6),
+        (5, 7),
+        (8, 9),
+        (1, 2),
+        (3, 4),
+        (5, 6),
+        (7, 8),
+        (2, 3),
+        (4, 5),
+        (6, 7),
+    ],
+];
+
+/// 16 要素の Green のネットワーク (比較器 60 個、深さ 10)
+const OPTIMAL_16: [(usize, usize); 60] = [
+    (0, 13),
+    (1, 12),
+    (2, 15),
+    (3, 14),
+    (4, 8),
+    (5, 6),
+    (7, 11),
+    (9, 10),
+    (0, 5),
+    (1, 7),
+    (2, 9),
+    (3, 4),
+    (6, 13),
+    (8, 14),
+    (10, 15),
+    (11, 12),
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 8),
+    (7, 9),
+    (10, 11),
+    (12, 13),
+    (14, 15),
+    (0, 2),
+    (1, 3),
+    (4, 10),
+    (5, 11),
+    (6, 7),
+    (8, 9),
+    (12, 14),
+    (13, 15),
+    (1, 2),
+    (3, 12),
+    (4, 6),
+    (5, 7),
+    (8, 10),
+    (9, 11),
+    (13, 14),
+    (1, 4),
+    (2, 6),
+    (5, 8),
+    (7, 10),
+    (9, 13),
+    (11, 14),
+    (2, 4),
+    (3, 6),
+    (9, 12),
+    (11, 13),
+    (3, 5),
+    (6, 8),
+    (7, 9),
+    (10, 12),
+    (3, 4),
+    (5, 6),
+    (7, 8),
+    (9, 10),
+    (11, 12),
+    (6, 7),
+    (8, 9),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch03::util::new_u32_vec;
+
+    #[test]
+    fn generated_networks_sort_every_01_input() {
+        for size in 0..=16 {
+            Network::bitonic(size).verify().unwrap();
+            Network::odd_even_merge(size).verify().unwrap();
+            Network::optimal(size).unwrap().verify().unwrap();
+        }
+        assert!(Network::optimal(17).is_none());
+    }
+
+    #[test]
+    fn optimal_network_sizes() {
+        let sizes = (0..=16)
+            .map(|n| {
+                let network = Network::optimal(n).unwrap();
+                (network.comparators().len(), network.depth())
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            sizes,
+            vec![
+                (0, 0),
+                (0, 0),
+                (1, 1),
+                (3, 3),
+                (5, 3),
+                (9, 5),
+                (12, 5),
+                (16, 6),
+                (19, 6),
+                (25, 7),
+                (29, 8),
+                (35, 8),
+                (40, 10),
+                (46, 10),
+                (51, 10),
+                (56, 10),
+                (60, 10),
+            ]
+        );
+    }
+
+    #[test]
+    fn large_networks_sort_random_inputs() {
+        for size in (17..=64).step_by(7) {
+            for network in &[Network::bitonic(size), Network::odd_even_merge(size)] {
+                let mut x = new_u32_vec(size);
+                let mut expected = x.clone();
+                expected.sort_unstable();
+                network.apply(&mut x);
+                assert_eq!(x, expected, "size: {}", size);
+            }
+        }
+    }
+
+    #[test]
+    fn verify_finds_counterexample() {
+        let network = Network::new(3, vec![(0, 1), (1, 2)]).unwrap();
+        // 線 0 と 1 が 1 の入力は、線 0 の 1 が動かず [1, 0, 1] になる
+        assert_eq!(network.verify(), Err(NetworkError::Unsorted(0b011)));
+        assert_eq!(
+            Network::bitonic(MAX_VERIFY_SIZE + 1).verify(),
+            Err(NetworkError::TooLargeToVerify(MAX_VERIFY_SIZE + 1))
+        );
+    }
+
+    #[test]
+    fn reject_invalid_comparators() {
+        assert_eq!(
+            Network::new(3, vec![(0, 3)]),
+            Err(NetworkError::OutOfRange(0, 3, 3))
+        );
+        assert_eq!(
+            Network::new(3, vec![(1, 1)]),
+            Err(NetworkError::SameWire(1))
+        );
+    }
+
+    #[test]
+    fn render_text() {
+        let network = Network::new(4, vec![(0, 1), (3, 2), (0, 3), (1, 2)]).unwrap();
+        let expected = "\
+0 -o--o-----
+   |  |
+1 -o--|--o--
+      |  |
+2 -^--|--o--
+   |  |
+3 -o--o-----
+";
+        assert_eq!(network.to_text(), expected);
+    }
+
+    #[test]
+    fn render_svg() {
+        let svg = Network::bitonic(4).to_svg();
+        assert!(svg.starts_with("<svg "));
+        assert!(svg.ends_with("</svg>\n"));
+        // 線 4 本と比較器 6 個
+        assert_eq!(svg.matches("<line").count(), 4 + 6);
+        assert_eq!(svg.matches("<circle").count(), 6 * 2);
+        // バイトニックソートには逆向きの比較器がある
+        assert!(svg.contains("#c33"));
+    }
+
+    #[test]
+    fn generate_rust() {
+        let network = Network::optimal(2).unwrap();
+        assert_eq!(
+            network.to_rust("sort2", CodeStyle::Branchless),
+            "\
+/// 2 要素をソートする (比較器 1 個、深さ 1)
+pub fn sort2<T: Ord + Copy>(x: &mut [T; 2]) {
+    let (a, b) = (x[0], x[1]);
+    x[0] = a.min(b);
+    x[1] = a.max(b);
+}
+"
+        );
+    }
+
+    /// 生成したコードと src/ch03/small_sort.rs が一致しているか確かめる
+    #[test]
+    fn small_sort_module_is_up_to_date() {
+        assert!(
+            small_sort_module() == include_str!("small_sort.rs"),
+            "run `cargo run --example ch03_network -- small-sort > src/ch03/small_sort.rs`"
+        );
+    }
+}
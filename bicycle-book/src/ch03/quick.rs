@@ -0,0 +1,190 @@
+use crate::ch03::common::{insertion_sort, INSERTION_THRESHOLD};
+use crate::ch03::{ComparisonSorter, SortConfig, SortOrder, Sorter};
+use std::cmp::Ordering;
+
+/// 並列化したクイックソート。ピボットは 3 つの要素の中央値で選ぶ。安定ではない
+#[derive(Debug, Default, Clone, Copy)]
+pub struct QuickSort;
+
+impl<T: Ord + Send> Sorter<T> for QuickSort {
+    fn name(&self) -> &'static str {
+        "quick"
+    }
+
+    fn is_stable(&self) -> bool {
+        false
+    }
+
+    fn sort(&self, x: &mut [T], order: &SortOrder) {
+        sort(x, order);
+    }
+}
+
+impl<T: Ord + Send> ComparisonSorter<T> for QuickSort {
+    fn sort_by(&self, x: &mut [T], comparator: &(dyn Fn(&T, &T) -> Ordering + Sync)) {
+        sort_by(x, &|a, b| comparator(a, b));
+    }
+}
+
+pub fn sort<T: Ord + Send>(x: &mut [T], order: &SortOrder) {
+    sort_with_config(x, order, &SortConfig::default());
+}
+
+/// config に従って x をソートする
+pub fn sort_with_config<T: Ord + Send>(x: &mut [T], order: &SortOrder, config: &SortConfig) {
+    match *order {
+        SortOrder::Ascending => sort_by_with_config(x, &|a, b| a.cmp(b), config),
+        SortOrder::Descending => sort_by_with_config(x, &|a, b| b.cmp(a), config),
+    }
+}
+
+/// comparator が返す順序に従って x をソートする
+///
+/// ピボットの選び方が悪く再帰が深くなりすぎた区間は、標準ライブラリのソートに任せる。
+pub fn sort_by<T, F>(x: &mut [T], comparator: &F)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    sort_by_with_config(x, comparator, &SortConfig::default());
+}
+
+/// config に従って、comparator が返す順序で x をソートする
+pub fn sort_by_with_config<T, F>(x: &mut [T], comparator: &F, config: &SortConfig)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    let limit = 2 * (usize::BITS - x.len().leading_zeros());
+    config.install(|| do_sort(x, comparator, limit, config, 0));
+}
+
+fn do_sort<T, F>(x: &mut [T], comparator: &F, limit: u32, config: &SortConfig, depth: usize)
+where
+    T: Send,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    if x.len() <= INSERTION_THRESHOLD {
+        insertion_sort(x, comparator);
+        return;
+    }
+    if limit == 0 {
+        x.sort_unstable_by(comparator);
+        return;
+    }
+
+    let pivot = partition(x, comparator);
+    let (first, second) = x.split_at_mut(pivot);
+    let second = &mut second[1..];
+    let len = first.len().min(second.len());
+    if config.forks(len, config.sort_threshold, depth) {
+        rayon::join(
+            || do_sort(first, comparator, limit - 1, config, depth + 1),
+            || do_sort(second, comparator, limit - 1, config, depth + 1),
+        );
+    } else {
+        do_sort(first, comparator, limit - 1, config, depth);
+        do_sort(second, comparator, limit - 1, config, depth);
+    }
+}
+
+/// x[0]、x[mid]、x[len - 1] の中央値をピボットとして x を分割し、ピボットの位置を返す
+///
+/// ピボットより前の要素はピボット以下、後ろの要素はピボット以上になる。
+/// ピボットと等しい要素は両側に振り分けるので、同じ値が多くても分割が偏らない。
+fn partition<T, F>(x: &mut [T], comparator: &F) -> usize
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    median_of_three_to_front(x, comparator);
+
+    let (mut i, mut j) = (1, x.len() - 1);
+    loop {
+        while i <= j && comparator(&x[i], &x[0]) == Ordering::Less {
+            i += 1;
+        }
+        while i <= j && comparator(&x[j], &x[0]) == Ordering::Greater {
+            j -= 1;
+        }
+        if i >= j {
+            break;
+        }
+        x.swap(i, j);
+        i += 1;
+        j -= 1;
+    }
+    x.swap(0, j);
+    j
+}
+
+/// x[0]、x[mid]、x[len - 1] の中央値を x の先頭に置く。x は空であってはならない
+pub(crate) fn median_of_three_to_front<T, F>(x: &mut [T], comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let len = x.len();
+    let mid = len / 2;
+    if comparator(&x[mid], &x[0]) == Ordering::Less {
+        x.swap(mid, 0);
+    }
+    if comparator(&x[len - 1], &x[mid]) == Ordering::Less {
+        x.swap(len - 1, mid);
+        if comparator(&x[mid], &x[0]) == Ordering::Less {
+            x.swap(mid, 0);
+        }
+    }
+    x.swap(0, mid);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch03::common::test_util::{assert_runs_in_pool, test_pool};
+    use crate::ch03::util::{is_sorted, new_u32_vec};
+    use crate::ch03::SortOrder::*;
+
+    #[test]
+    fn sort_u32() {
+        for &len in &[0, 1, 2, 3, 16, 17, 100, 4097, 10000, 65536] {
+            let mut x = new_u32_vec(len);
+            let mut expected = x.clone();
+            expected.sort();
+            sort(&mut x, &Ascending);
+            assert_eq!(x, expected, "len: {}", len);
+
+            sort(&mut x, &Descending);
+            assert!(is_sorted(&x, &Descending), "len: {}", len);
+        }
+    }
+
+    #[test]
+    fn sort_degenerate_inputs() {
+        let inputs = vec![
+            vec![7u32; 10000],
+            (0..10000).collect::<Vec<u32>>(),
+            (0..10000).rev().collect(),
+            (0..10000).map(|i| i % 3).collect(),
+            (0..10000)
+                .map(|i| if i % 2 == 0 { i } else { 10000 - i })
+                .collect(),
+        ];
+        for mut x in inputs {
+            sort(&mut x, &Ascending);
+            assert!(is_sorted(&x, &Ascending));
+        }
+    }
+
+    #[test]
+    fn sort_in_custom_pool() {
+        let config = SortConfig {
+            sort_threshold: 64,
+            pool: Some(test_pool(3)),
+            ..SortConfig::default()
+        };
+        let mut x = new_u32_vec(10000);
+        assert_runs_in_pool(3, |cmp| {
+            sort_by_with_config(&mut x, &|a, b| cmp(a, b), &config)
+        });
+        assert!(is_sorted(&x, &Ascending));
+    }
+}
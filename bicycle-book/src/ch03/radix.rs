@@ -0,0 +1,268 @@
+use crate::ch03::common::chunk_len;
+use crate::ch03::{SortConfig, SortOrder, Sorter};
+use rayon::prelude::*;
+
+/// 1 回の振り分けに使う桁のビット数
+const RADIX_BITS: usize = 8;
+const BUCKETS: usize = 1 << RADIX_BITS;
+
+/// 基数ソートのキーにできる型
+///
+/// [`RadixKey::key`] は、元の値の順序と符号なし整数としての順序が一致する値を返す。
+pub trait RadixKey: Copy + Send + Sync {
+    /// キーのバイト数
+    const BYTES: usize;
+
+    fn key(self) -> u64;
+}
+
+macro_rules! impl_radix_key_for_unsigned {
+    ($($t:ty),*) => {$(
+        impl RadixKey for $t {
+            const BYTES: usize = std::mem::size_of::<$t>();
+
+            fn key(self) -> u64 {
+                self as u64
+            }
+        }
+    )*};
+}
+
+macro_rules! impl_radix_key_for_signed {
+    ($($t:ty => $u:ty),*) => {$(
+        impl RadixKey for $t {
+            const BYTES: usize = std::mem::size_of::<$t>();
+
+            /// 符号ビットを反転すると、負の数が正の数より小さくなる
+            fn key(self) -> u64 {
+                ((self as $u) ^ (1 << (<$u>::BITS - 1))) as u64
+            }
+        }
+    )*};
+}
+
+impl_radix_key_for_unsigned!(u8, u16, u32, u64, usize);
+impl_radix_key_for_signed!(i8 => u8, i16 => u16, i32 => u32, i64 => u64, isize => usize);
+
+/// 浮動小数点数は [`f32::total_cmp`] と同じ全順序で並べる
+///
+/// 負の数は全てのビットを、正の数は符号ビットだけを反転すると、ビット列の大小が値の大小と一致する。
+/// -0.0 は 0.0 より、負の NaN は全ての数より前に、正の NaN は全ての数より後に並ぶ。
+impl RadixKey for f32 {
+    const BYTES: usize = 4;
+
+    fn key(self) -> u64 {
+        let bits = self.to_bits();
+        let mask = if bits >> 31 == 1 { u32::MAX } else { 1 << 31 };
+        (bits ^ mask) as u64
+    }
+}
+
+impl RadixKey for f64 {
+    const BYTES: usize = 8;
+
+    fn key(self) -> u64 {
+        let bits = self.to_bits();
+        let mask = if bits >> 63 == 1 { u64::MAX } else { 1 << 63 };
+        bits ^ mask
+    }
+}
+
+/// 並列化した LSD 基数ソート。安定である
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RadixSort;
+
+impl<T: RadixKey> Sorter<T> for RadixSort {
+    fn name(&self) -> &'static str {
+        "radix"
+    }
+
+    fn is_stable(&self) -> bool {
+        true
+    }
+
+    fn sort(&self, x: &mut [T], order: &SortOrder) {
+        sort(x, order);
+    }
+}
+
+/// x を安定ソートする
+pub fn sort<T: RadixKey>(x: &mut [T], order: &SortOrder) {
+    sort_with_config(x, order, &SortConfig::default());
+}
+
+/// config に従って x を安定ソートする
+///
+/// 要素数が `config.sort_threshold` 以上なら、入力をチャンクに分けて並列に振り分ける。
+/// `config.max_parallel_depth` が 0 なら分割しない。
+pub fn sort_with_config<T: RadixKey>(x: &mut [T], order: &SortOrder, config: &SortConfig) {
+    config.install(|| match *order {
+        SortOrder::Ascending => sort_by_key(x, &|v: &T| v.key(), config),
+        // キーを反転すれば、安定性を保ったまま降順になる
+        SortOrder::Descending => sort_by_key(x, &|v: &T| !v.key(), config),
+    });
+}
+
+/// key が返す値の昇順に x を安定ソートする
+///
+/// 下位の桁から 8 ビットずつ振り分ける。入力をチャンクに分け、
+/// チャンクごとの桁の数え上げと振り分けを並列に行う。
+fn sort_by_key<T, K>(x: &mut [T], key: &K, config: &SortConfig)
+where
+    T: RadixKey,
+    K: Fn(&T) -> u64 + Sync,
+{
+    if x.len() <= 1 {
+        return;
+    }
+    let chunk_len = if config.forks(x.len(), config.sort_threshold, 0) {
+        chunk_len(x.len(), config.sort_threshold)
+    } else {
+        x.len()
+    };
+
+    for pass in 0..T::BYTES {
+        let shift = pass * RADIX_BITS;
+        let digit = |v: &T| ((key(v) >> shift) as usize) & (BUCKETS - 1);
+
+        // チャンクごとに、桁で安定に並べ替えた列とその桁の個数を求める
+        let chunks = x
+            .par_chunks(chunk_len)
+            .map(|chunk| {
+                let mut counts = [0; BUCKETS];
+                for v in chunk {
+                    counts[digit(v)] += 1;
+                }
+                let mut offsets = [0; BUCKETS];
+                for d in 1..BUCKETS {
+                    offsets[d] = offsets[d - 1] + counts[d - 1];
+                }
+                let mut sorted = chunk.to_vec();
+                for v in chunk {
+                    let d = digit(v);
+                    sorted[offsets[d]] = *v;
+                    offsets[d] += 1;
+                }
+                (sorted, counts)
+            })
+            .collect::<Vec<_>>();
+
+        // 全ての要素がこの桁で同じ値なら並びは変わらない
+        if (0..BUCKETS).any(|d| chunks.iter().map(|(_, c)| c[d]).sum::<usize>() == x.len()) {
+            continue;
+        }
+
+        // 桁の小さい順に、各チャンクの同じ桁の要素をチャンクの順につなげる
+        let mut starts = vec![0; chunks.len()];
+        let mut pos = 0;
+        for d in 0..BUCKETS {
+            for (i, (sorted, counts)) in chunks.iter().enumerate() {
+                let n = counts[d];
+                x[pos..pos + n].copy_from_slice(&sorted[starts[i]..starts[i] + n]);
+                starts[i] += n;
+                pos += n;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch03::common::test_util::test_pool;
+    use crate::ch03::util::new_u32_vec;
+    use crate::ch03::SortOrder::*;
+
+    #[test]
+    fn sort_unsigned() {
+        for &len in &[0, 1, 2, 100, 4096, 65536] {
+            let mut x = new_u32_vec(len);
+            let mut expected = x.clone();
+            expected.sort_unstable();
+            sort(&mut x, &Ascending);
+            assert_eq!(x, expected, "len: {}", len);
+
+            expected.reverse();
+            sort(&mut x, &Descending);
+            assert_eq!(x, expected, "len: {}", len);
+        }
+
+        let mut x = vec![300u16, 2, 65535, 256, 0];
+        sort(&mut x, &Ascending);
+        assert_eq!(x, vec![0, 2, 256, 300, 65535]);
+    }
+
+    #[test]
+    fn sort_signed() {
+        let mut x = new_u32_vec(10000)
+            .into_iter()
+            .map(|v| v as i32)
+            .collect::<Vec<_>>();
+        x.extend(&[i32::MIN, i32::MAX, 0, -1, 1]);
+        let mut expected = x.clone();
+        expected.sort_unstable();
+        sort(&mut x, &Ascending);
+        assert_eq!(x, expected);
+
+        let mut x = vec![3i8, -128, 127, -1, 0];
+        sort(&mut x, &Descending);
+        assert_eq!(x, vec![127, 3, 0, -1, -128]);
+    }
+
+    #[test]
+    fn sort_floats_in_total_order() {
+        let mut x = vec![
+            2.5f64,
+            -0.0,
+            f64::INFINITY,
+            0.0,
+            -1.5,
+            f64::NEG_INFINITY,
+            f64::MIN_POSITIVE,
+            -f64::MAX,
+        ];
+        sort(&mut x, &Ascending);
+        let bits = x.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        let expected = [
+            f64::NEG_INFINITY,
+            -f64::MAX,
+            -1.5,
+            -0.0,
+            0.0,
+            f64::MIN_POSITIVE,
+            2.5,
+            f64::INFINITY,
+        ];
+        let expected = expected.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(bits, expected);
+
+        let mut x = vec![1.0f32, f32::NAN, -3.0, 2.0];
+        sort(&mut x, &Ascending);
+        assert_eq!(&x[..3], &[-3.0, 1.0, 2.0]);
+        assert!(x[3].is_nan());
+    }
+
+    #[test]
+    fn sort_with_configs() {
+        let configs = [
+            SortConfig {
+                sort_threshold: 64,
+                pool: Some(test_pool(3)),
+                ..SortConfig::default()
+            },
+            SortConfig {
+                sort_threshold: 1,
+                max_parallel_depth: 0,
+                ..SortConfig::default()
+            },
+        ];
+        let input = new_u32_vec(10000);
+        let mut expected = input.clone();
+        expected.sort_unstable();
+        for config in &configs {
+            let mut x = input.clone();
+            sort_with_config(&mut x, &Ascending, config);
+            assert_eq!(x, expected);
+        }
+    }
+}
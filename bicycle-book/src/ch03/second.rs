@@ -1,6 +1,6 @@
-use crate::ch03::SortOrder;
+use crate::ch03::{SortError, SortOrder};
 
-pub fn sort<T: Ord>(x: &mut [T], order: &SortOrder) -> Result<(), String> {
+pub fn sort<T: Ord>(x: &mut [T], order: &SortOrder) -> Result<(), SortError> {
     if x.len().is_power_of_two() {
         match *order {
             SortOrder::Ascending => do_sort(x, true),
@@ -8,10 +8,7 @@ pub fn sort<T: Ord>(x: &mut [T], order: &SortOrder) -> Result<(), String> {
         }
         Ok(())
     } else {
-        Err(format!(
-            "The length of x is not a power of two. (x.len(): {})",
-            x.len()
-        ))
+        Err(SortError::NotPowerOfTwo(x.len()))
     }
 }
 
@@ -120,6 +117,6 @@ mod tests {
     #[test]
     fn sort_to_fail() {
         let mut x = vec![10, 30, 11];
-        assert!(sort(&mut x, &Ascending).is_err());
+        assert_eq!(sort(&mut x, &Ascending), Err(SortError::NotPowerOfTwo(3)));
     }
 }
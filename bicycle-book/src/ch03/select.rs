@@ -0,0 +1,449 @@
+//! 全体をソートせずに、小さい方から k 個の要素や n 番目の要素を求める
+
+use crate::ch03::common::chunk_len;
+use crate::ch03::quick::{self, median_of_three_to_front};
+use crate::ch03::{SortConfig, SortOrder};
+use rayon::prelude::*;
+use std::cmp::Ordering;
+
+/// これより短い区間は標準ライブラリの select_nth_unstable_by に任せる
+const SMALL_SELECT_THRESHOLD: usize = 16;
+
+fn comparator<T: Ord>(order: &SortOrder) -> fn(&T, &T) -> Ordering {
+    match *order {
+        SortOrder::Ascending => |a, b| a.cmp(b),
+        SortOrder::Descending => |a, b| b.cmp(a),
+    }
+}
+
+/// order の順で先頭から k 個の要素を x[..k] にソートして並べる
+///
+/// x[k..] の並びは定まらない。k が x.len() 以上なら x 全体をソートする。
+pub fn partial_sort<T: Ord + Send + Sync>(x: &mut [T], k: usize, order: &SortOrder) {
+    partial_sort_by(x, k, &comparator(order));
+}
+
+/// config に従って、order の順で先頭から k 個の要素を x[..k] にソートして並べる
+pub fn partial_sort_with_config<T: Ord + Send + Sync>(
+    x: &mut [T],
+    k: usize,
+    order: &SortOrder,
+    config: &SortConfig,
+) {
+    partial_sort_by_with_config(x, k, &comparator(order), config);
+}
+
+/// comparator が返す順序で先頭から k 個の要素を x[..k] にソートして並べる
+///
+/// [`select_nth_by`] で k 番目の要素を境に分けてから、x[..k] だけをソートする。
+pub fn partial_sort_by<T, F>(x: &mut [T], k: usize, comparator: &F)
+where
+    T: Send + Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    partial_sort_by_with_config(x, k, comparator, &SortConfig::default());
+}
+
+/// config に従って、comparator が返す順序で先頭から k 個の要素を x[..k] にソートして並べる
+pub fn partial_sort_by_with_config<T, F>(x: &mut [T], k: usize, comparator: &F, config: &SortConfig)
+where
+    T: Send + Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    if k == 0 {
+        return;
+    }
+    if k < x.len() {
+        select_nth_by_with_config(x, k, comparator, config);
+    }
+    let k = k.min(x.len());
+    quick::sort_by_with_config(&mut x[..k], comparator, config);
+}
+
+/// iter の要素のうち order の順で先頭から k 個を、その順に並べて返す
+pub fn top_k<T, I>(iter: I, k: usize, order: &SortOrder) -> Vec<T>
+where
+    T: Ord,
+    I: IntoIterator<Item = T>,
+{
+    top_k_by(iter, k, &comparator(order))
+}
+
+/// iter の要素のうち comparator が返す順序で先頭から k 個を、その順に並べて返す
+///
+/// 要素数 k のヒープだけを保持するので、メモリに収まらない長さのイテレータも扱える。
+/// 等しい要素は現れた順に並べ、境目をまたぐ場合は先に現れたものを残す。
+pub fn top_k_by<T, I, F>(iter: I, k: usize, comparator: &F) -> Vec<T>
+where
+    I: IntoIterator<Item = T>,
+    F: Fn(&T, &T) -> Ordering,
+{
+    if k == 0 {
+        return Vec::new();
+    }
+
+    // 現れた順番を添えて、等しい要素の間にも順序を付ける
+    let comparator = |a: &(usize, T), b: &(usize, T)| comparator(&a.1, &b.1).then(a.0.cmp(&b.0));
+
+    // heap[0] が、これまでに残した k 個のうち順序が最も後ろの要素になる
+    // k が入力より長い場合もあるので、確保する領域は入力の長さの下限までにする
+    let iter = iter.into_iter();
+    let mut heap = Vec::with_capacity(k.min(iter.size_hint().0));
+    for v in iter.enumerate() {
+        if heap.len() < k {
+            heap.push(v);
+            sift_up(&mut heap, &comparator);
+        } else if comparator(&v, &heap[0]) == Ordering::Less {
+            heap[0] = v;
+            sift_down(&mut heap, &comparator);
+        }
+    }
+    heap.sort_unstable_by(comparator);
+    heap.into_iter().map(|(_, v)| v).collect()
+}
+
+/// 末尾に追加した要素を、comparator の順で大きい方が根に来るように上へ移す
+fn sift_up<T, F>(heap: &mut [T], comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let mut i = heap.len() - 1;
+    while i > 0 {
+        let parent = (i - 1) / 2;
+        if comparator(&heap[i], &heap[parent]) != Ordering::Greater {
+            break;
+        }
+        heap.swap(i, parent);
+        i = parent;
+    }
+}
+
+/// 根に置いた要素を、子の方が大きい間は下へ移す
+fn sift_down<T, F>(heap: &mut [T], comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let mut i = 0;
+    loop {
+        let mut largest = i;
+        for child in [i * 2 + 1, i * 2 + 2].iter().copied() {
+            if child < heap.len() && comparator(&heap[child], &heap[largest]) == Ordering::Greater {
+                largest = child;
+            }
+        }
+        if largest == i {
+            break;
+        }
+        heap.swap(i, largest);
+        i = largest;
+    }
+}
+
+/// x を並べ替え、order の順でソートした時に n 番目になる要素を x[n] に置いて返す
+///
+/// x[..n] は x[n] 以前、x[n + 1..] は x[n] 以後の要素になる。n が x.len() 以上なら panic する。
+pub fn select_nth<'a, T>(x: &'a mut [T], n: usize, order: &SortOrder) -> &'a mut T
+where
+    T: Ord + Send + Sync,
+{
+    select_nth_by(x, n, &comparator(order))
+}
+
+/// config に従って、order の順でソートした時に n 番目になる要素を x[n] に置いて返す
+pub fn select_nth_with_config<'a, T>(
+    x: &'a mut [T],
+    n: usize,
+    order: &SortOrder,
+    config: &SortConfig,
+) -> &'a mut T
+where
+    T: Ord + Send + Sync,
+{
+    select_nth_by_with_config(x, n, &comparator(order), config)
+}
+
+/// x を並べ替え、comparator が返す順序でソートした時に n 番目になる要素を x[n] に置いて返す
+///
+/// 3 つの要素の中央値をピボットとするクイックセレクトで、長い区間の分割は並列に行う。
+/// 再帰が深くなりすぎた場合は標準ライブラリの select_nth_unstable_by に任せる (イントロセレクト)。
+pub fn select_nth_by<'a, T, F>(x: &'a mut [T], n: usize, comparator: &F) -> &'a mut T
+where
+    T: Send + Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    select_nth_by_with_config(x, n, comparator, &SortConfig::default())
+}
+
+/// config に従って、comparator が返す順序でソートした時に n 番目になる要素を x[n] に置いて返す
+///
+/// 要素数が `config.sort_threshold` 以上の区間を並列に分割する。
+/// 区間を絞り込んだ回数を再帰の深さとして `config.max_parallel_depth` と比べる。
+pub fn select_nth_by_with_config<'a, T, F>(
+    x: &'a mut [T],
+    n: usize,
+    comparator: &F,
+    config: &SortConfig,
+) -> &'a mut T
+where
+    T: Send + Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    assert!(
+        n < x.len(),
+        "n ({}) must be less than the length of x ({})",
+        n,
+        x.len()
+    );
+
+    config.install(|| do_select(x, n, comparator, config));
+    &mut x[n]
+}
+
+fn do_select<T, F>(x: &mut [T], n: usize, comparator: &F, config: &SortConfig)
+where
+    T: Send + Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    let mut limit = 2 * (usize::BITS - x.len().leading_zeros());
+    let (mut lo, mut hi) = (0, x.len());
+    for depth in 0.. {
+        let part = &mut x[lo..hi];
+        let k = n - lo;
+        if part.len() <= SMALL_SELECT_THRESHOLD || limit == 0 {
+            part.select_nth_unstable_by(k, comparator);
+            break;
+        }
+        limit -= 1;
+
+        // part を [ピボット][小さい要素][等しい要素][大きい要素] に分け、
+        // ピボットを小さい要素の直後に移す
+        median_of_three_to_front(part, comparator);
+        let (pivot, rest) = part.split_at_mut(1);
+        let pivot = &pivot[0];
+        let less = partition(
+            rest,
+            &|v| comparator(v, pivot) == Ordering::Less,
+            config,
+            depth,
+        );
+        let equal = partition(
+            &mut rest[less..],
+            &|v| comparator(v, pivot) != Ordering::Greater,
+            config,
+            depth,
+        );
+        part.swap(0, less);
+
+        if k < less {
+            hi = lo + less;
+        } else if k <= less + equal {
+            break;
+        } else {
+            lo += less + equal + 1;
+        }
+    }
+}
+
+/// pred を満たす要素を x の前に集め、その個数を返す
+///
+/// 長い x はチャンクごとに並列に分割してから、境界をまたいで置かれた要素を入れ替える。
+fn partition<T, P>(x: &mut [T], pred: &P, config: &SortConfig, depth: usize) -> usize
+where
+    T: Send,
+    P: Fn(&T) -> bool + Sync,
+{
+    if !config.forks(x.len(), config.sort_threshold, depth) {
+        return partition_seq(x, pred);
+    }
+
+    let chunk_len = chunk_len(x.len(), config.sort_threshold);
+    let counts = x
+        .par_chunks_mut(chunk_len)
+        .map(|chunk| partition_seq(chunk, pred))
+        .collect::<Vec<_>>();
+    let total = counts.iter().sum::<usize>();
+
+    // 各チャンクは [満たす要素][満たさない要素] に分かれている。
+    // total より前にある満たさない要素と、total 以降にある満たす要素を入れ替える
+    let mut front = Vec::new();
+    let mut back = Vec::new();
+    for (i, &count) in counts.iter().enumerate() {
+        let start = i * chunk_len;
+        let end = (start + chunk_len).min(x.len());
+        let mid = start + count;
+        if mid < total {
+            front.push(mid..end.min(total));
+        }
+        if mid > total {
+            back.push(start.max(total)..mid);
+        }
+    }
+    let front = front.into_iter().flatten();
+    let back = back.into_iter().flatten();
+    for (i, j) in front.zip(back) {
+        x.swap(i, j);
+    }
+    total
+}
+
+fn partition_seq<T, P>(x: &mut [T], pred: &P) -> usize
+where
+    P: Fn(&T) -> bool,
+{
+    let mut count = 0;
+    for i in 0..x.len() {
+        if pred(&x[i]) {
+            x.swap(count, i);
+            count += 1;
+        }
+    }
+    count
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch03::common::test_util::{assert_runs_in_pool, assert_runs_on_caller, test_pool};
+    use crate::ch03::util::new_u32_vec;
+    use crate::ch03::SortOrder::*;
+
+    #[test]
+    fn partial_sort_smallest() {
+        for &len in &[0, 1, 10, 100, 5000, 100_000] {
+            for &k in &[0, 1, 7, len / 2, len, len + 3] {
+                let mut x = new_u32_vec(len);
+                let mut expected = x.clone();
+                expected.sort_unstable();
+                let k_ = k.min(len);
+
+                partial_sort(&mut x, k, &Ascending);
+                assert_eq!(&x[..k_], &expected[..k_], "len: {}, k: {}", len, k);
+                // 残りの要素も失われていない
+                x.sort_unstable();
+                assert_eq!(x, expected);
+            }
+        }
+    }
+
+    #[test]
+    fn partial_sort_largest_with_duplicates() {
+        let mut x = new_u32_vec(20000)
+            .into_iter()
+            .map(|v| v % 10)
+            .collect::<Vec<_>>();
+        let mut expected = x.clone();
+        expected.sort_unstable_by(|a, b| b.cmp(a));
+        partial_sort(&mut x, 3000, &Descending);
+        assert_eq!(&x[..3000], &expected[..3000]);
+    }
+
+    #[test]
+    fn select_every_position() {
+        let x = new_u32_vec(300)
+            .into_iter()
+            .map(|v| v % 50)
+            .collect::<Vec<_>>();
+        let mut sorted = x.clone();
+        sorted.sort_unstable();
+        for n in 0..x.len() {
+            let mut y = x.clone();
+            let nth = *select_nth(&mut y, n, &Ascending);
+            assert_eq!(nth, sorted[n], "n: {}", n);
+            assert!(y[..n].iter().all(|&v| v <= nth));
+            assert!(y[n + 1..].iter().all(|&v| v >= nth));
+        }
+    }
+
+    #[test]
+    fn select_median_of_large_inputs() {
+        let inputs = vec![
+            new_u32_vec(1_000_001),
+            vec![7; 100_000],
+            (0..100_000).collect(),
+            (0..100_000).rev().collect(),
+        ];
+        for x in inputs {
+            let mut sorted = x.clone();
+            sorted.sort_unstable();
+            let mid = x.len() / 2;
+
+            let mut y = x.clone();
+            assert_eq!(*select_nth(&mut y, mid, &Ascending), sorted[mid]);
+            assert!(y[..mid].iter().all(|&v| v <= sorted[mid]));
+            assert!(y[mid + 1..].iter().all(|&v| v >= sorted[mid]));
+
+            let mut y = x.clone();
+            let nth = *select_nth_by(&mut y, 10, &|a: &u32, b: &u32| b.cmp(a));
+            assert_eq!(nth, sorted[sorted.len() - 11]);
+        }
+    }
+
+    #[test]
+    fn select_with_config() {
+        let x = new_u32_vec(100_000);
+        let mut sorted = x.clone();
+        sorted.sort_unstable();
+
+        // 小さいしきい値で、config のプールを使って分割する
+        let config = SortConfig {
+            sort_threshold: 64,
+            pool: Some(test_pool(3)),
+            ..SortConfig::default()
+        };
+        let mut y = x.clone();
+        assert_eq!(
+            *select_nth_with_config(&mut y, 500, &Ascending, &config),
+            sorted[500]
+        );
+        let mut y = x.clone();
+        assert_runs_in_pool(3, |cmp| {
+            partial_sort_by_with_config(&mut y, 1000, &|a, b| cmp(a, b), &config)
+        });
+        assert_eq!(&y[..1000], &sorted[..1000]);
+
+        let config = SortConfig {
+            sort_threshold: 1,
+            max_parallel_depth: 0,
+            ..SortConfig::default()
+        };
+        let mut y = x.clone();
+        assert_runs_on_caller(|cmp| {
+            partial_sort_by_with_config(&mut y, 1000, &|a, b| cmp(a, b), &config)
+        });
+        assert_eq!(&y[..1000], &sorted[..1000]);
+    }
+
+    #[test]
+    #[should_panic]
+    fn select_out_of_range() {
+        select_nth(&mut [1, 2, 3], 3, &Ascending);
+    }
+
+    #[test]
+    fn top_k_from_stream() {
+        // 長いイテレータから、メモリに全て載せずに取り出す
+        let stream = || (0..1_000_000u64).map(|i| i * 7919 % 1_000_003);
+        let mut expected = stream().collect::<Vec<_>>();
+        expected.sort_unstable_by(|a, b| b.cmp(a));
+        assert_eq!(top_k(stream(), 5, &Descending), &expected[..5]);
+
+        let x = new_u32_vec(10000);
+        let mut expected = x.clone();
+        expected.sort_unstable();
+        assert_eq!(top_k(x.iter().copied(), 100, &Ascending), &expected[..100]);
+        assert_eq!(top_k(x.iter().copied(), 20000, &Ascending), expected);
+        assert_eq!(top_k(x.iter().copied(), usize::MAX, &Ascending), expected);
+        assert_eq!(top_k(vec![3, 1, 2], usize::MAX, &Descending), vec![3, 2, 1]);
+        assert!(top_k(x.iter().copied(), 0, &Ascending).is_empty());
+    }
+
+    #[test]
+    fn top_k_keeps_earlier_ties() {
+        let rows = vec![("b", 2), ("a", 1), ("c", 2), ("d", 3), ("e", 2), ("f", 0)];
+        let by_score = |a: &(&str, i32), b: &(&str, i32)| a.1.cmp(&b.1);
+        let top = top_k_by(rows.clone(), 3, &by_score);
+        assert_eq!(top, vec![("f", 0), ("a", 1), ("b", 2)]);
+        let top = top_k_by(rows, 5, &by_score);
+        assert_eq!(top, vec![("f", 0), ("a", 1), ("b", 2), ("c", 2), ("e", 2)]);
+    }
+}
@@ -0,0 +1,622 @@
+//! SIMD 命令で比較と交換を行うバイトニックソート
+//!
+//! `u32` と `f32` を [`fourth`](crate::ch03::fourth) の再帰と [`SortConfig`] でソートし、
+//! 葉のソートと比較と交換だけを SIMD 命令で行う。
+//! 比較と交換は 1 要素ずつではなく、レジスタのレーンごとの最小値と最大値で行う。
+//! 使える命令セット ([`Backend`]) は実行時に CPU を調べて選び、
+//! SSE4.1 も使えない場合はスカラーの実装で処理する。
+
+use crate::ch03::fourth::{self, Kernel};
+use crate::ch03::{SortConfig, SortOrder, Sorter};
+use rayon::prelude::*;
+
+/// スカラーの実装で葉としてまとめてソートする要素数
+const SCALAR_BLOCK_LEN: usize = 16;
+
+/// 比較と交換に使う命令セット
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// 256 ビットのレジスタで 8 要素ずつ処理する
+    Avx2,
+    /// 128 ビットのレジスタで 4 要素ずつ処理する
+    Sse41,
+    /// 1 要素ずつ処理する
+    Scalar,
+}
+
+impl Backend {
+    /// この CPU で使える最も幅の広い命令セットを返す
+    pub fn detect() -> Self {
+        [Backend::Avx2, Backend::Sse41]
+            .iter()
+            .copied()
+            .find(|backend| backend.is_available())
+            .unwrap_or(Backend::Scalar)
+    }
+
+    /// この CPU で使えるかどうか
+    pub fn is_available(self) -> bool {
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => is_x86_feature_detected!("avx2"),
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse41 => is_x86_feature_detected!("sse4.1"),
+            Backend::Scalar => true,
+            #[allow(unreachable_patterns)]
+            _ => false,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Backend::Avx2 => "avx2",
+            Backend::Sse41 => "sse4.1",
+            Backend::Scalar => "scalar",
+        }
+    }
+
+    /// 1 つのレジスタに入る要素数
+    fn lanes(self) -> usize {
+        match self {
+            Backend::Avx2 => 8,
+            Backend::Sse41 => 4,
+            Backend::Scalar => 1,
+        }
+    }
+
+    /// 葉としてまとめてソートする要素数
+    fn block_len(self) -> usize {
+        match self {
+            Backend::Scalar => SCALAR_BLOCK_LEN,
+            _ => self.lanes() * self.lanes(),
+        }
+    }
+
+    /// lo[i] と hi[i] を比較し、小さい方を lo[i] に、大きい方を hi[i] に置く
+    fn min_max(self, lo: &mut [u32], hi: &mut [u32]) {
+        debug_assert_eq!(lo.len(), hi.len());
+        if lo.len() < self.lanes() {
+            min_max_scalar(lo, hi);
+            return;
+        }
+        // Safety: Backend は is_available を確かめてから使っている
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => unsafe { x86::min_max_avx2(lo, hi) },
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse41 => unsafe { x86::min_max_sse41(lo, hi) },
+            _ => min_max_scalar(lo, hi),
+        }
+    }
+
+    /// 要素数 lanes() のバイトニック列 x を、1 つのレジスタの中で forward の向きにマージする
+    fn merge_lanes(self, x: &mut [u32], forward: bool) {
+        debug_assert_eq!(x.len(), self.lanes());
+        // Safety: Backend は is_available を確かめてから使っている
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => unsafe { x86::merge_lanes_avx2(x, forward) },
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse41 => unsafe { x86::merge_lanes_sse41(x, forward) },
+            _ => fourth::merge_seq(x, forward, &Backend::Scalar),
+        }
+    }
+
+    /// 要素数 block_len() の x を、lanes() 個ずつの昇順の列に並べる
+    ///
+    /// lanes() 本のレジスタに x を読み込み、レジスタ間でソーティングネットワークを
+    /// 適用すると、各レーン (列) がソートされる。それを転置して書き戻す。
+    fn sort_columns(self, x: &mut [u32]) {
+        debug_assert_eq!(x.len(), self.block_len());
+        // Safety: Backend は is_available を確かめてから使っている
+        match self {
+            #[cfg(target_arch = "x86_64")]
+            Backend::Avx2 => unsafe { x86::sort_columns_avx2(x) },
+            #[cfg(target_arch = "x86_64")]
+            Backend::Sse41 => unsafe { x86::sort_columns_sse41(x) },
+            _ => x.sort_unstable(),
+        }
+    }
+}
+
+impl Kernel<u32> for Backend {
+    fn leaf_len(&self) -> usize {
+        self.block_len()
+    }
+
+    fn sort_leaf(&self, x: &mut [u32], forward: bool) {
+        sort_block(x, forward, *self);
+    }
+
+    fn compare_and_swap(&self, x: &mut [u32], mid: usize, forward: bool) {
+        let (first, second) = x.split_at_mut(mid);
+        let first = &mut first[..second.len()];
+        if forward {
+            self.min_max(first, second);
+        } else {
+            self.min_max(second, first);
+        }
+    }
+
+    fn merge_leaf(&self, x: &mut [u32], forward: bool) -> bool {
+        let merges = x.len() == self.lanes() && *self != Backend::Scalar;
+        if merges {
+            self.merge_lanes(x, forward);
+        }
+        merges
+    }
+}
+
+/// SIMD 命令を使ったバイトニックソート。安定ではない
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SimdBitonicSort;
+
+impl Sorter<u32> for SimdBitonicSort {
+    fn name(&self) -> &'static str {
+        "simd_bitonic"
+    }
+
+    fn is_stable(&self) -> bool {
+        false
+    }
+
+    fn sort(&self, x: &mut [u32], order: &SortOrder) {
+        sort_u32(x, order);
+    }
+}
+
+impl Sorter<f32> for SimdBitonicSort {
+    fn name(&self) -> &'static str {
+        "simd_bitonic"
+    }
+
+    fn is_stable(&self) -> bool {
+        false
+    }
+
+    fn sort(&self, x: &mut [f32], order: &SortOrder) {
+        sort_f32(x, order);
+    }
+}
+
+/// x をソートする
+pub fn sort_u32(x: &mut [u32], order: &SortOrder) {
+    sort_u32_with_config(x, order, &SortConfig::default());
+}
+
+/// config に従って x をソートする
+pub fn sort_u32_with_config(x: &mut [u32], order: &SortOrder, config: &SortConfig) {
+    sort_keys(x, order, Backend::detect(), config);
+}
+
+/// 命令セットを指定して x をソートする
+///
+/// backend がこの CPU で使えない場合は panic する。
+pub fn sort_u32_with(x: &mut [u32], order: &SortOrder, backend: Backend) {
+    assert!(
+        backend.is_available(),
+        "{} is not supported on this CPU",
+        backend.name()
+    );
+    sort_keys(x, order, backend, &SortConfig::default());
+}
+
+/// x を [`f32::total_cmp`] と同じ全順序でソートする
+pub fn sort_f32(x: &mut [f32], order: &SortOrder) {
+    sort_f32_with_config(x, order, &SortConfig::default());
+}
+
+/// config に従って x を [`f32::total_cmp`] と同じ全順序でソートする
+pub fn sort_f32_with_config(x: &mut [f32], order: &SortOrder, config: &SortConfig) {
+    sort_floats(x, order, Backend::detect(), config);
+}
+
+/// 命令セットを指定して x を [`f32::total_cmp`] と同じ全順序でソートする
+///
+/// backend がこの CPU で使えない場合は panic する。
+pub fn sort_f32_with(x: &mut [f32], order: &SortOrder, backend: Backend) {
+    assert!(
+        backend.is_available(),
+        "{} is not supported on this CPU",
+        backend.name()
+    );
+    sort_floats(x, order, backend, &SortConfig::default());
+}
+
+fn sort_keys(x: &mut [u32], order: &SortOrder, backend: Backend, config: &SortConfig) {
+    let forward = matches!(order, SortOrder::Ascending);
+    fourth::sort_with_kernel(x, forward, &backend, config);
+}
+
+/// 浮動小数点数の最小値と最大値の命令は NaN や -0.0 を正しく扱えないので、
+/// 値をビット列の大小が順序と一致する `u32` のキーに変換してから比較する。
+fn sort_floats(x: &mut [f32], order: &SortOrder, backend: Backend, config: &SortConfig) {
+    // Safety: f32 と u32 は大きさもアラインメントも同じ
+    let keys = unsafe { std::slice::from_raw_parts_mut(x.as_mut_ptr() as *mut u32, x.len()) };
+    convert_keys(keys, to_key, config);
+    sort_keys(keys, order, backend, config);
+    convert_keys(keys, from_key, config);
+}
+
+/// keys の全ての要素に f を適用する。長い keys は config のプールで並列に処理する
+fn convert_keys(keys: &mut [u32], f: fn(u32) -> u32, config: &SortConfig) {
+    if config.forks(keys.len(), config.sort_threshold, 0) {
+        config.install(|| {
+            keys.par_iter_mut()
+                .with_min_len(config.sort_threshold)
+                .for_each(|k| *k = f(*k))
+        });
+    } else {
+        keys.iter_mut().for_each(|k| *k = f(*k));
+    }
+}
+
+/// 負の数は全てのビットを、正の数は符号ビットだけを反転する
+fn to_key(bits: u32) -> u32 {
+    let mask = if bits >> 31 == 1 { u32::MAX } else { 1 << 31 };
+    bits ^ mask
+}
+
+fn from_key(key: u32) -> u32 {
+    let mask = if key >> 31 == 1 { 1 << 31 } else { u32::MAX };
+    key ^ mask
+}
+
+/// 葉になる短い x を forward の向きにソートする
+///
+/// 要素数がちょうど block_len() なら、レジスタ内でソートした lanes() 個ずつの列を
+/// バイトニックマージでつなげる。それ以外の端数はスカラーでソートする。
+fn sort_block(x: &mut [u32], forward: bool, backend: Backend) {
+    if x.len() == backend.block_len() && backend != Backend::Scalar {
+        backend.sort_columns(x);
+        let mut width = backend.lanes();
+        while width < x.len() {
+            for pair in x.chunks_mut(width * 2) {
+                // 昇順の列を 2 つ並べ、後ろを逆順にするとバイトニック列になる
+                pair[width..].reverse();
+                fourth::merge_seq(pair, true, &backend);
+            }
+            width *= 2;
+        }
+    } else {
+        x.sort_unstable();
+    }
+    if !forward {
+        x.reverse();
+    }
+}
+
+fn min_max_scalar(lo: &mut [u32], hi: &mut [u32]) {
+    for (a, b) in lo.iter_mut().zip(hi.iter_mut()) {
+        if *a > *b {
+            std::mem::swap(a, b);
+        }
+    }
+}
+
+/// 8 入力の最適なソーティングネットワーク (比較と交換 19 回)
+const NETWORK_8: [(usize, usize); 19] = [
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (2, 4),
+    (3, 5),
+    (1, 4),
+    (3, 6),
+    (1, 2),
+    (3, 4),
+    (5, 6),
+];
+
+/// 4 入力の最適なソーティングネットワーク (比較と交換 5 回)
+const NETWORK_4: [(usize, usize); 5] = [(0, 1), (2, 3), (0, 2), (1, 3), (1, 2)];
+
+#[cfg(target_arch = "x86_64")]
+mod x86 {
+    use super::{min_max_scalar, NETWORK_4, NETWORK_8};
+    use std::arch::x86_64::*;
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn min_max_avx2(lo: &mut [u32], hi: &mut [u32]) {
+        let n = lo.len() / 8 * 8;
+        for i in (0..n).step_by(8) {
+            let a = lo.as_mut_ptr().add(i) as *mut __m256i;
+            let b = hi.as_mut_ptr().add(i) as *mut __m256i;
+            let (va, vb) = (_mm256_loadu_si256(a), _mm256_loadu_si256(b));
+            _mm256_storeu_si256(a, _mm256_min_epu32(va, vb));
+            _mm256_storeu_si256(b, _mm256_max_epu32(va, vb));
+        }
+        min_max_scalar(&mut lo[n..], &mut hi[n..]);
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn min_max_sse41(lo: &mut [u32], hi: &mut [u32]) {
+        let n = lo.len() / 4 * 4;
+        for i in (0..n).step_by(4) {
+            let a = lo.as_mut_ptr().add(i) as *mut __m128i;
+            let b = hi.as_mut_ptr().add(i) as *mut __m128i;
+            let (va, vb) = (_mm_loadu_si128(a), _mm_loadu_si128(b));
+            _mm_storeu_si128(a, _mm_min_epu32(va, vb));
+            _mm_storeu_si128(b, _mm_max_epu32(va, vb));
+        }
+        min_max_scalar(&mut lo[n..], &mut hi[n..]);
+    }
+
+    /// 距離 4、2、1 の要素の組をレジスタ内で入れ替えて比較し、blend で小さい方と大きい方を選ぶ
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn merge_lanes_avx2(x: &mut [u32], forward: bool) {
+        let p = x.as_mut_ptr() as *mut __m256i;
+        let mut v = _mm256_loadu_si256(p);
+        let (lo, hi) = min_max_avx2_vector(v, _mm256_permute2x128_si256(v, v, 0x01), forward);
+        v = _mm256_blend_epi32(lo, hi, 0b1111_0000);
+        let (lo, hi) = min_max_avx2_vector(v, _mm256_shuffle_epi32(v, 0x4e), forward);
+        v = _mm256_blend_epi32(lo, hi, 0b1100_1100);
+        let (lo, hi) = min_max_avx2_vector(v, _mm256_shuffle_epi32(v, 0xb1), forward);
+        v = _mm256_blend_epi32(lo, hi, 0b1010_1010);
+        _mm256_storeu_si256(p, v);
+    }
+
+    /// forward なら (最小値, 最大値) を、そうでなければ (最大値, 最小値) を返す
+    #[target_feature(enable = "avx2")]
+    unsafe fn min_max_avx2_vector(a: __m256i, b: __m256i, forward: bool) -> (__m256i, __m256i) {
+        let (min, max) = (_mm256_min_epu32(a, b), _mm256_max_epu32(a, b));
+        if forward {
+            (min, max)
+        } else {
+            (max, min)
+        }
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn merge_lanes_sse41(x: &mut [u32], forward: bool) {
+        let p = x.as_mut_ptr() as *mut __m128i;
+        let mut v = _mm_loadu_si128(p);
+        let (lo, hi) = min_max_sse41_vector(v, _mm_shuffle_epi32(v, 0x4e), forward);
+        v = _mm_blend_epi16(lo, hi, 0b1111_0000);
+        let (lo, hi) = min_max_sse41_vector(v, _mm_shuffle_epi32(v, 0xb1), forward);
+        v = _mm_blend_epi16(lo, hi, 0b1100_1100);
+        _mm_storeu_si128(p, v);
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    unsafe fn min_max_sse41_vector(a: __m128i, b: __m128i, forward: bool) -> (__m128i, __m128i) {
+        let (min, max) = (_mm_min_epu32(a, b), _mm_max_epu32(a, b));
+        if forward {
+            (min, max)
+        } else {
+            (max, min)
+        }
+    }
+
+    #[target_feature(enable = "avx2")]
+    pub unsafe fn sort_columns_avx2(x: &mut [u32]) {
+        let p = x.as_mut_ptr() as *mut __m256i;
+        let mut r = [_mm256_setzero_si256(); 8];
+        for (i, v) in r.iter_mut().enumerate() {
+            *v = _mm256_loadu_si256(p.add(i));
+        }
+        for &(i, j) in NETWORK_8.iter() {
+            let (a, b) = (r[i], r[j]);
+            r[i] = _mm256_min_epu32(a, b);
+            r[j] = _mm256_max_epu32(a, b);
+        }
+
+        // 8x8 の転置
+        let mut f = [_mm256_setzero_ps(); 8];
+        for (v, w) in f.iter_mut().zip(r.iter()) {
+            *v = _mm256_castsi256_ps(*w);
+        }
+        let r = f;
+        let t0 = _mm256_unpacklo_ps(r[0], r[1]);
+        let t1 = _mm256_unpackhi_ps(r[0], r[1]);
+        let t2 = _mm256_unpacklo_ps(r[2], r[3]);
+        let t3 = _mm256_unpackhi_ps(r[2], r[3]);
+        let t4 = _mm256_unpacklo_ps(r[4], r[5]);
+        let t5 = _mm256_unpackhi_ps(r[4], r[5]);
+        let t6 = _mm256_unpacklo_ps(r[6], r[7]);
+        let t7 = _mm256_unpackhi_ps(r[6], r[7]);
+        let s0 = _mm256_shuffle_ps(t0, t2, 0x44);
+        let s1 = _mm256_shuffle_ps(t0, t2, 0xee);
+        let s2 = _mm256_shuffle_ps(t1, t3, 0x44);
+        let s3 = _mm256_shuffle_ps(t1, t3, 0xee);
+        let s4 = _mm256_shuffle_ps(t4, t6, 0x44);
+        let s5 = _mm256_shuffle_ps(t4, t6, 0xee);
+        let s6 = _mm256_shuffle_ps(t5, t7, 0x44);
+        let s7 = _mm256_shuffle_ps(t5, t7, 0xee);
+        let columns = [
+            _mm256_permute2f128_ps(s0, s4, 0x20),
+            _mm256_permute2f128_ps(s1, s5, 0x20),
+            _mm256_permute2f128_ps(s2, s6, 0x20),
+            _mm256_permute2f128_ps(s3, s7, 0x20),
+            _mm256_permute2f128_ps(s0, s4, 0x31),
+            _mm256_permute2f128_ps(s1, s5, 0x31),
+            _mm256_permute2f128_ps(s2, s6, 0x31),
+            _mm256_permute2f128_ps(s3, s7, 0x31),
+        ];
+        for (i, v) in columns.iter().enumerate() {
+            _mm256_storeu_si256(p.add(i), _mm256_castps_si256(*v));
+        }
+    }
+
+    #[target_feature(enable = "sse4.1")]
+    pub unsafe fn sort_columns_sse41(x: &mut [u32]) {
+        let p = x.as_mut_ptr() as *mut __m128i;
+        let mut r = [_mm_setzero_si128(); 4];
+        for (i, v) in r.iter_mut().enumerate() {
+            *v = _mm_loadu_si128(p.add(i));
+        }
+        for &(i, j) in NETWORK_4.iter() {
+            let (a, b) = (r[i], r[j]);
+            r[i] = _mm_min_epu32(a, b);
+            r[j] = _mm_max_epu32(a, b);
+        }
+
+        // 4x4 の転置
+        let mut f = [_mm_setzero_ps(); 4];
+        for (v, w) in f.iter_mut().zip(r.iter()) {
+            *v = _mm_castsi128_ps(*w);
+        }
+        let r = f;
+        let t0 = _mm_unpacklo_ps(r[0], r[1]);
+        let t1 = _mm_unpacklo_ps(r[2], r[3]);
+        let t2 = _mm_unpackhi_ps(r[0], r[1]);
+        let t3 = _mm_unpackhi_ps(r[2], r[3]);
+        let columns = [
+            _mm_movelh_ps(t0, t1),
+            _mm_movehl_ps(t1, t0),
+            _mm_movelh_ps(t2, t3),
+            _mm_movehl_ps(t3, t2),
+        ];
+        for (i, v) in columns.iter().enumerate() {
+            _mm_storeu_si128(p.add(i), _mm_castps_si128(*v));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch03::common::test_util::test_pool;
+    use crate::ch03::util::{is_sorted, new_u32_vec};
+    use crate::ch03::SortOrder::*;
+
+    fn available_backends() -> Vec<Backend> {
+        [Backend::Avx2, Backend::Sse41, Backend::Scalar]
+            .iter()
+            .copied()
+            .filter(|backend| backend.is_available())
+            .collect()
+    }
+
+    /// 0 と 1 だけからなる全ての入力をソートできれば、任意の入力をソートできる (0-1 原理)
+    #[test]
+    fn networks_sort_every_01_input() {
+        for network in &[&NETWORK_4[..], &NETWORK_8[..]] {
+            let n = network.iter().map(|&(_, j)| j).max().unwrap() + 1;
+            for bits in 0..1u32 << n {
+                let mut x = (0..n).map(|i| (bits >> i) & 1).collect::<Vec<_>>();
+                for &(i, j) in network.iter() {
+                    if x[i] > x[j] {
+                        x.swap(i, j);
+                    }
+                }
+                assert!(is_sorted(&x, &Ascending), "input: {:b}", bits);
+            }
+        }
+    }
+
+    #[test]
+    fn sort_every_length_with_every_backend() {
+        let lengths = (0..=300)
+            .chain(vec![4095, 4096, 4097, 10000, 65536])
+            .collect::<Vec<_>>();
+        for backend in available_backends() {
+            for &len in &lengths {
+                let mut x = new_u32_vec(len);
+                let mut expected = x.clone();
+                expected.sort_unstable();
+                sort_u32_with(&mut x, &Ascending, backend);
+                assert_eq!(x, expected, "backend: {:?}, len: {}", backend, len);
+
+                sort_u32_with(&mut x, &Descending, backend);
+                assert!(
+                    is_sorted(&x, &Descending),
+                    "backend: {:?}, len: {}",
+                    backend,
+                    len
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sort_with_configs() {
+        let configs = [
+            SortConfig {
+                sort_threshold: 16,
+                merge_threshold: 16,
+                pool: Some(test_pool(3)),
+                ..SortConfig::default()
+            },
+            SortConfig {
+                sort_threshold: 1,
+                merge_threshold: 1,
+                max_parallel_depth: 0,
+                ..SortConfig::default()
+            },
+        ];
+        for config in &configs {
+            for &len in &[0, 1, 63, 64, 65, 1000, 10000] {
+                let mut x = new_u32_vec(len);
+                let mut expected = x.clone();
+                expected.sort_unstable();
+                sort_u32_with_config(&mut x, &Ascending, config);
+                assert_eq!(x, expected, "len: {}", len);
+
+                let mut x = x.into_iter().map(f32::from_bits).collect::<Vec<_>>();
+                sort_f32_with_config(&mut x, &Descending, config);
+                assert!(
+                    x.windows(2).all(|w| w[0].total_cmp(&w[1]).is_ge()),
+                    "len: {}",
+                    len
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn sort_with_duplicates() {
+        for backend in available_backends() {
+            let mut x = (0..1000).map(|i| i % 7).collect::<Vec<u32>>();
+            x.extend(&[u32::MAX, 0, u32::MAX]);
+            sort_u32_with(&mut x, &Ascending, backend);
+            assert!(is_sorted(&x, &Ascending), "backend: {:?}", backend);
+        }
+    }
+
+    #[test]
+    fn sort_floats_in_total_order() {
+        let mut x = new_u32_vec(5000)
+            .into_iter()
+            .map(f32::from_bits)
+            .collect::<Vec<_>>();
+        x.extend(&[
+            -0.0,
+            0.0,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            f32::NAN,
+            -f32::NAN,
+        ]);
+        let mut expected = x.clone();
+        expected.sort_unstable_by(|a, b| a.total_cmp(b));
+        let expected = expected.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+
+        for backend in available_backends() {
+            let mut x = x.clone();
+            sort_f32_with(&mut x, &Ascending, backend);
+            let bits = x.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+            assert_eq!(bits, expected, "backend: {:?}", backend);
+        }
+
+        let mut x = vec![1.5f32, -2.0, 0.0, -0.0];
+        sort_f32(&mut x, &Descending);
+        let bits = x.iter().map(|v| v.to_bits()).collect::<Vec<_>>();
+        assert_eq!(
+            bits,
+            [1.5f32, 0.0, -0.0, -2.0]
+                .iter()
+                .map(|v| v.to_bits())
+                .collect::<Vec<_>>()
+        );
+    }
+}
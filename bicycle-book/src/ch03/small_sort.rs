@@ -0,0 +1,578 @@
+//! 16 要素以下の固定長のソート
+//!
+//! このファイルは `cargo run --example ch03_network -- small-sort` で生成した。
+//! 直接編集しないこと。
+
+use std::cmp::Ordering;
+
+/// ネットワークでソートできる最大の要素数
+pub const MAX_LEN: usize = 16;
+
+/// 要素数 [`MAX_LEN`] 以下の x を、比較器ネットワークで forward の向きにソートする
+pub fn sort_by<T, F>(x: &mut [T], forward: bool, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let swap_condition = if forward {
+        Ordering::Greater
+    } else {
+        Ordering::Less
+    };
+    match x.len() {
+        0 | 1 => {}
+        2 => sort2(x, swap_condition, comparator),
+        3 => sort3(x, swap_condition, comparator),
+        4 => sort4(x, swap_condition, comparator),
+        5 => sort5(x, swap_condition, comparator),
+        6 => sort6(x, swap_condition, comparator),
+        7 => sort7(x, swap_condition, comparator),
+        8 => sort8(x, swap_condition, comparator),
+        9 => sort9(x, swap_condition, comparator),
+        10 => sort10(x, swap_condition, comparator),
+        11 => sort11(x, swap_condition, comparator),
+        12 => sort12(x, swap_condition, comparator),
+        13 => sort13(x, swap_condition, comparator),
+        14 => sort14(x, swap_condition, comparator),
+        15 => sort15(x, swap_condition, comparator),
+        16 => sort16(x, swap_condition, comparator),
+        n => panic!("too many elements for a sorting network: {}", n),
+    }
+}
+
+/// x[i] と x[j] を比較し、順序が swap_condition なら交換する
+#[inline(always)]
+fn compare_and_swap<T, F>(x: &mut [T], i: usize, j: usize, swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    if comparator(&x[i], &x[j]) == swap_condition {
+        x.swap(i, j);
+    }
+}
+
+/// 2 要素をソートする (比較器 1 個、深さ 1)
+pub fn sort2<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..2];
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+}
+
+/// 3 要素をソートする (比較器 3 個、深さ 3)
+pub fn sort3<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..3];
+    compare_and_swap(x, 0, 2, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+}
+
+/// 4 要素をソートする (比較器 5 個、深さ 3)
+pub fn sort4<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..4];
+    compare_and_swap(x, 0, 2, swap_condition, comparator);
+    compare_and_swap(x, 1, 3, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+}
+
+/// 5 要素をソートする (比較器 9 個、深さ 5)
+pub fn sort5<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..5];
+    compare_and_swap(x, 0, 3, swap_condition, comparator);
+    compare_and_swap(x, 1, 4, swap_condition, comparator);
+    compare_and_swap(x, 0, 2, swap_condition, comparator);
+    compare_and_swap(x, 1, 3, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+}
+
+/// 6 要素をソートする (比較器 12 個、深さ 5)
+pub fn sort6<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..6];
+    compare_and_swap(x, 0, 5, swap_condition, comparator);
+    compare_and_swap(x, 1, 3, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 0, 3, swap_condition, comparator);
+    compare_and_swap(x, 2, 5, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+}
+
+/// 7 要素をソートする (比較器 16 個、深さ 6)
+pub fn sort7<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..7];
+    compare_and_swap(x, 0, 6, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 0, 2, swap_condition, comparator);
+    compare_and_swap(x, 1, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 6, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 2, 5, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 4, 6, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+}
+
+/// 8 要素をソートする (比較器 19 個、深さ 6)
+pub fn sort8<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..8];
+    compare_and_swap(x, 0, 2, swap_condition, comparator);
+    compare_and_swap(x, 1, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 7, swap_condition, comparator);
+    compare_and_swap(x, 0, 4, swap_condition, comparator);
+    compare_and_swap(x, 1, 5, swap_condition, comparator);
+    compare_and_swap(x, 2, 6, swap_condition, comparator);
+    compare_and_swap(x, 3, 7, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 5, swap_condition, comparator);
+    compare_and_swap(x, 1, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 6, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+}
+
+/// 9 要素をソートする (比較器 25 個、深さ 7)
+pub fn sort9<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..9];
+    compare_and_swap(x, 0, 3, swap_condition, comparator);
+    compare_and_swap(x, 1, 7, swap_condition, comparator);
+    compare_and_swap(x, 2, 5, swap_condition, comparator);
+    compare_and_swap(x, 4, 8, swap_condition, comparator);
+    compare_and_swap(x, 0, 7, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 8, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+    compare_and_swap(x, 0, 2, swap_condition, comparator);
+    compare_and_swap(x, 1, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 7, 8, swap_condition, comparator);
+    compare_and_swap(x, 1, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 7, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 8, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+}
+
+/// 10 要素をソートする (比較器 29 個、深さ 8)
+pub fn sort10<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..10];
+    compare_and_swap(x, 0, 8, swap_condition, comparator);
+    compare_and_swap(x, 1, 9, swap_condition, comparator);
+    compare_and_swap(x, 2, 7, swap_condition, comparator);
+    compare_and_swap(x, 3, 5, swap_condition, comparator);
+    compare_and_swap(x, 4, 6, swap_condition, comparator);
+    compare_and_swap(x, 0, 2, swap_condition, comparator);
+    compare_and_swap(x, 1, 4, swap_condition, comparator);
+    compare_and_swap(x, 5, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 9, swap_condition, comparator);
+    compare_and_swap(x, 0, 3, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 5, 7, swap_condition, comparator);
+    compare_and_swap(x, 6, 9, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 3, 6, swap_condition, comparator);
+    compare_and_swap(x, 8, 9, swap_condition, comparator);
+    compare_and_swap(x, 1, 5, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 8, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 5, swap_condition, comparator);
+    compare_and_swap(x, 4, 6, swap_condition, comparator);
+    compare_and_swap(x, 7, 8, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+}
+
+/// 11 要素をソートする (比較器 35 個、深さ 8)
+pub fn sort11<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..11];
+    compare_and_swap(x, 0, 9, swap_condition, comparator);
+    compare_and_swap(x, 1, 6, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 7, swap_condition, comparator);
+    compare_and_swap(x, 5, 8, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 3, 5, swap_condition, comparator);
+    compare_and_swap(x, 4, 10, swap_condition, comparator);
+    compare_and_swap(x, 6, 9, swap_condition, comparator);
+    compare_and_swap(x, 7, 8, swap_condition, comparator);
+    compare_and_swap(x, 1, 3, swap_condition, comparator);
+    compare_and_swap(x, 2, 5, swap_condition, comparator);
+    compare_and_swap(x, 4, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 10, swap_condition, comparator);
+    compare_and_swap(x, 0, 4, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 7, swap_condition, comparator);
+    compare_and_swap(x, 5, 9, swap_condition, comparator);
+    compare_and_swap(x, 6, 8, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 2, 6, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 7, 8, swap_condition, comparator);
+    compare_and_swap(x, 9, 10, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 9, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+    compare_and_swap(x, 7, 8, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+}
+
+/// 12 要素をソートする (比較器 40 個、深さ 10)
+pub fn sort12<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..12];
+    compare_and_swap(x, 4, 8, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+    compare_and_swap(x, 7, 11, swap_condition, comparator);
+    compare_and_swap(x, 9, 10, swap_condition, comparator);
+    compare_and_swap(x, 0, 5, swap_condition, comparator);
+    compare_and_swap(x, 1, 7, swap_condition, comparator);
+    compare_and_swap(x, 2, 9, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 9, swap_condition, comparator);
+    compare_and_swap(x, 10, 11, swap_condition, comparator);
+    compare_and_swap(x, 0, 2, swap_condition, comparator);
+    compare_and_swap(x, 1, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 10, swap_condition, comparator);
+    compare_and_swap(x, 5, 11, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 9, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 4, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 10, swap_condition, comparator);
+    compare_and_swap(x, 9, 11, swap_condition, comparator);
+    compare_and_swap(x, 1, 4, swap_condition, comparator);
+    compare_and_swap(x, 2, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 10, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 6, swap_condition, comparator);
+    compare_and_swap(x, 3, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 9, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+    compare_and_swap(x, 7, 8, swap_condition, comparator);
+    compare_and_swap(x, 9, 10, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 9, swap_condition, comparator);
+}
+
+/// 13 要素をソートする (比較器 46 個、深さ 10)
+pub fn sort13<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..13];
+    compare_and_swap(x, 1, 12, swap_condition, comparator);
+    compare_and_swap(x, 4, 8, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+    compare_and_swap(x, 7, 11, swap_condition, comparator);
+    compare_and_swap(x, 9, 10, swap_condition, comparator);
+    compare_and_swap(x, 0, 5, swap_condition, comparator);
+    compare_and_swap(x, 1, 7, swap_condition, comparator);
+    compare_and_swap(x, 2, 9, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 11, 12, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 9, swap_condition, comparator);
+    compare_and_swap(x, 10, 11, swap_condition, comparator);
+    compare_and_swap(x, 0, 2, swap_condition, comparator);
+    compare_and_swap(x, 1, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 10, swap_condition, comparator);
+    compare_and_swap(x, 5, 11, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 9, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 12, swap_condition, comparator);
+    compare_and_swap(x, 4, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 10, swap_condition, comparator);
+    compare_and_swap(x, 9, 11, swap_condition, comparator);
+    compare_and_swap(x, 1, 4, swap_condition, comparator);
+    compare_and_swap(x, 2, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 10, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 6, swap_condition, comparator);
+    compare_and_swap(x, 9, 12, swap_condition, comparator);
+    compare_and_swap(x, 3, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 9, swap_condition, comparator);
+    compare_and_swap(x, 10, 12, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+    compare_and_swap(x, 7, 8, swap_condition, comparator);
+    compare_and_swap(x, 9, 10, swap_condition, comparator);
+    compare_and_swap(x, 11, 12, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 9, swap_condition, comparator);
+}
+
+/// 14 要素をソートする (比較器 51 個、深さ 10)
+pub fn sort14<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..14];
+    compare_and_swap(x, 0, 13, swap_condition, comparator);
+    compare_and_swap(x, 1, 12, swap_condition, comparator);
+    compare_and_swap(x, 4, 8, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+    compare_and_swap(x, 7, 11, swap_condition, comparator);
+    compare_and_swap(x, 9, 10, swap_condition, comparator);
+    compare_and_swap(x, 0, 5, swap_condition, comparator);
+    compare_and_swap(x, 1, 7, swap_condition, comparator);
+    compare_and_swap(x, 2, 9, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 6, 13, swap_condition, comparator);
+    compare_and_swap(x, 11, 12, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 9, swap_condition, comparator);
+    compare_and_swap(x, 10, 11, swap_condition, comparator);
+    compare_and_swap(x, 12, 13, swap_condition, comparator);
+    compare_and_swap(x, 0, 2, swap_condition, comparator);
+    compare_and_swap(x, 1, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 10, swap_condition, comparator);
+    compare_and_swap(x, 5, 11, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 9, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 12, swap_condition, comparator);
+    compare_and_swap(x, 4, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 10, swap_condition, comparator);
+    compare_and_swap(x, 9, 11, swap_condition, comparator);
+    compare_and_swap(x, 1, 4, swap_condition, comparator);
+    compare_and_swap(x, 2, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 10, swap_condition, comparator);
+    compare_and_swap(x, 9, 13, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 6, swap_condition, comparator);
+    compare_and_swap(x, 9, 12, swap_condition, comparator);
+    compare_and_swap(x, 11, 13, swap_condition, comparator);
+    compare_and_swap(x, 3, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 9, swap_condition, comparator);
+    compare_and_swap(x, 10, 12, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+    compare_and_swap(x, 7, 8, swap_condition, comparator);
+    compare_and_swap(x, 9, 10, swap_condition, comparator);
+    compare_and_swap(x, 11, 12, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 9, swap_condition, comparator);
+}
+
+/// 15 要素をソートする (比較器 56 個、深さ 10)
+pub fn sort15<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..15];
+    compare_and_swap(x, 0, 13, swap_condition, comparator);
+    compare_and_swap(x, 1, 12, swap_condition, comparator);
+    compare_and_swap(x, 3, 14, swap_condition, comparator);
+    compare_and_swap(x, 4, 8, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+    compare_and_swap(x, 7, 11, swap_condition, comparator);
+    compare_and_swap(x, 9, 10, swap_condition, comparator);
+    compare_and_swap(x, 0, 5, swap_condition, comparator);
+    compare_and_swap(x, 1, 7, swap_condition, comparator);
+    compare_and_swap(x, 2, 9, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 6, 13, swap_condition, comparator);
+    compare_and_swap(x, 8, 14, swap_condition, comparator);
+    compare_and_swap(x, 11, 12, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 9, swap_condition, comparator);
+    compare_and_swap(x, 10, 11, swap_condition, comparator);
+    compare_and_swap(x, 12, 13, swap_condition, comparator);
+    compare_and_swap(x, 0, 2, swap_condition, comparator);
+    compare_and_swap(x, 1, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 10, swap_condition, comparator);
+    compare_and_swap(x, 5, 11, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 9, swap_condition, comparator);
+    compare_and_swap(x, 12, 14, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 12, swap_condition, comparator);
+    compare_and_swap(x, 4, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 10, swap_condition, comparator);
+    compare_and_swap(x, 9, 11, swap_condition, comparator);
+    compare_and_swap(x, 13, 14, swap_condition, comparator);
+    compare_and_swap(x, 1, 4, swap_condition, comparator);
+    compare_and_swap(x, 2, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 10, swap_condition, comparator);
+    compare_and_swap(x, 9, 13, swap_condition, comparator);
+    compare_and_swap(x, 11, 14, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 6, swap_condition, comparator);
+    compare_and_swap(x, 9, 12, swap_condition, comparator);
+    compare_and_swap(x, 11, 13, swap_condition, comparator);
+    compare_and_swap(x, 3, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 9, swap_condition, comparator);
+    compare_and_swap(x, 10, 12, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+    compare_and_swap(x, 7, 8, swap_condition, comparator);
+    compare_and_swap(x, 9, 10, swap_condition, comparator);
+    compare_and_swap(x, 11, 12, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 9, swap_condition, comparator);
+}
+
+/// 16 要素をソートする (比較器 60 個、深さ 10)
+pub fn sort16<T, F>(x: &mut [T], swap_condition: Ordering, comparator: &F)
+where
+    F: Fn(&T, &T) -> Ordering,
+{
+    let x = &mut x[..16];
+    compare_and_swap(x, 0, 13, swap_condition, comparator);
+    compare_and_swap(x, 1, 12, swap_condition, comparator);
+    compare_and_swap(x, 2, 15, swap_condition, comparator);
+    compare_and_swap(x, 3, 14, swap_condition, comparator);
+    compare_and_swap(x, 4, 8, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+    compare_and_swap(x, 7, 11, swap_condition, comparator);
+    compare_and_swap(x, 9, 10, swap_condition, comparator);
+    compare_and_swap(x, 0, 5, swap_condition, comparator);
+    compare_and_swap(x, 1, 7, swap_condition, comparator);
+    compare_and_swap(x, 2, 9, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 6, 13, swap_condition, comparator);
+    compare_and_swap(x, 8, 14, swap_condition, comparator);
+    compare_and_swap(x, 10, 15, swap_condition, comparator);
+    compare_and_swap(x, 11, 12, swap_condition, comparator);
+    compare_and_swap(x, 0, 1, swap_condition, comparator);
+    compare_and_swap(x, 2, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 9, swap_condition, comparator);
+    compare_and_swap(x, 10, 11, swap_condition, comparator);
+    compare_and_swap(x, 12, 13, swap_condition, comparator);
+    compare_and_swap(x, 14, 15, swap_condition, comparator);
+    compare_and_swap(x, 0, 2, swap_condition, comparator);
+    compare_and_swap(x, 1, 3, swap_condition, comparator);
+    compare_and_swap(x, 4, 10, swap_condition, comparator);
+    compare_and_swap(x, 5, 11, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 9, swap_condition, comparator);
+    compare_and_swap(x, 12, 14, swap_condition, comparator);
+    compare_and_swap(x, 13, 15, swap_condition, comparator);
+    compare_and_swap(x, 1, 2, swap_condition, comparator);
+    compare_and_swap(x, 3, 12, swap_condition, comparator);
+    compare_and_swap(x, 4, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 10, swap_condition, comparator);
+    compare_and_swap(x, 9, 11, swap_condition, comparator);
+    compare_and_swap(x, 13, 14, swap_condition, comparator);
+    compare_and_swap(x, 1, 4, swap_condition, comparator);
+    compare_and_swap(x, 2, 6, swap_condition, comparator);
+    compare_and_swap(x, 5, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 10, swap_condition, comparator);
+    compare_and_swap(x, 9, 13, swap_condition, comparator);
+    compare_and_swap(x, 11, 14, swap_condition, comparator);
+    compare_and_swap(x, 2, 4, swap_condition, comparator);
+    compare_and_swap(x, 3, 6, swap_condition, comparator);
+    compare_and_swap(x, 9, 12, swap_condition, comparator);
+    compare_and_swap(x, 11, 13, swap_condition, comparator);
+    compare_and_swap(x, 3, 5, swap_condition, comparator);
+    compare_and_swap(x, 6, 8, swap_condition, comparator);
+    compare_and_swap(x, 7, 9, swap_condition, comparator);
+    compare_and_swap(x, 10, 12, swap_condition, comparator);
+    compare_and_swap(x, 3, 4, swap_condition, comparator);
+    compare_and_swap(x, 5, 6, swap_condition, comparator);
+    compare_and_swap(x, 7, 8, swap_condition, comparator);
+    compare_and_swap(x, 9, 10, swap_condition, comparator);
+    compare_and_swap(x, 11, 12, swap_condition, comparator);
+    compare_and_swap(x, 6, 7, swap_condition, comparator);
+    compare_and_swap(x, 8, 9, swap_condition, comparator);
+}
@@ -1,14 +1,14 @@
-use crate::ch03::SortOrder;
+use crate::ch03::{SortError, SortOrder};
 use std::cmp::Ordering;
 
-pub fn sort<T: Ord>(x: &mut [T], order: &SortOrder) -> Result<(), String> {
+pub fn sort<T: Ord>(x: &mut [T], order: &SortOrder) -> Result<(), SortError> {
     match *order {
         SortOrder::Ascending => sort_by(x, &|a, b| a.cmp(b)),
         SortOrder::Descending => sort_by(x, &|a, b| b.cmp(a)),
     }
 }
 
-pub fn sort_by<T, F>(x: &mut [T], comparator: &F) -> Result<(), String>
+pub fn sort_by<T, F>(x: &mut [T], comparator: &F) -> Result<(), SortError>
 where
     F: Fn(&T, &T) -> Ordering,
 {
@@ -16,10 +16,7 @@ where
         do_sort(x, true, comparator);
         Ok(())
     } else {
-        Err(format!(
-            "The length of x is not a power of two. (x.len(): {})",
-            x.len()
-        ))
+        Err(SortError::NotPowerOfTwo(x.len()))
     }
 }
 
@@ -144,7 +141,7 @@ mod tests {
     #[test]
     fn sort_to_fail() {
         let mut x = vec![10, 30, 11];
-        assert!(sort(&mut x, &Ascending).is_err());
+        assert_eq!(sort(&mut x, &Ascending), Err(SortError::NotPowerOfTwo(3)));
     }
 
     #[derive(Debug, PartialEq)]
@@ -2,15 +2,537 @@ use crate::ch03::SortOrder;
 use rand::distributions::Standard;
 use rand::{Rng, SeedableRng};
 use rand_pcg::Pcg64Mcg;
+use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::collections::hash_map::DefaultHasher;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+use thiserror::Error;
+
+/// FewUnique で使う値の種類の数
+const FEW_UNIQUE_VALUES: usize = 16;
+
+/// Zipf で使う値の種類の数の上限
+const ZIPF_RANKS: usize = 1 << 16;
 
 pub fn new_u32_vec(n: usize) -> Vec<u32> {
     let rng = Pcg64Mcg::from_seed([0; 16]);
     rng.sample_iter(&Standard).take(n).collect()
 }
 
-pub fn is_sorted<T: Ord>(x: &[T], order: &SortOrder) -> bool {
+pub fn is_sorted<T: Ord + Sync>(x: &[T], order: &SortOrder) -> bool {
     match *order {
-        SortOrder::Ascending => x.windows(2).all(|pair| pair[0] <= pair[1]),
-        SortOrder::Descending => x.windows(2).all(|pair| pair[0] >= pair[1]),
+        SortOrder::Ascending => is_sorted_by(x, &|a, b| a.cmp(b)),
+        SortOrder::Descending => is_sorted_by(x, &|a, b| b.cmp(a)),
+    }
+}
+
+/// x が comparator の順に並んでいるかを並列に確かめる
+pub fn is_sorted_by<T, F>(x: &[T], comparator: &F) -> bool
+where
+    T: Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    find_unsorted_by(x, comparator).is_none()
+}
+
+/// comparator の順に並んでいない最初の隣り合う組の、前の要素の位置を返す
+fn find_unsorted_by<T, F>(x: &[T], comparator: &F) -> Option<usize>
+where
+    T: Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    x.par_windows(2)
+        .position_first(|pair| comparator(&pair[0], &pair[1]) == Ordering::Greater)
+}
+
+/// 要素の並び順によらない、列の要素の多重集合の指紋
+///
+/// ソートの前後で指紋を比べると、入力を複製せずに、要素が失われたり重複したりしていないかを確かめられる。
+/// 異なる多重集合の指紋が偶然一致することはまれだが、ありえないわけではない。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fingerprint {
+    len: usize,
+    /// 2 種類のハッシュ値の、要素全体での和
+    sums: (u64, u64),
+}
+
+/// x の [`Fingerprint`] を並列に求める
+pub fn fingerprint<T: Hash + Sync>(x: &[T]) -> Fingerprint {
+    let sums = x
+        .par_iter()
+        .map(|v| (hash_with_salt(v, 0), hash_with_salt(v, 1)))
+        .reduce(
+            || (0, 0),
+            |a, b| (a.0.wrapping_add(b.0), a.1.wrapping_add(b.1)),
+        );
+    Fingerprint { len: x.len(), sums }
+}
+
+fn hash_with_salt<T: Hash>(v: &T, salt: u64) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    salt.hash(&mut hasher);
+    v.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// output が input を key の order の順に安定ソートしたものかどうかを確かめる
+///
+/// 安定ソートの結果は一意に決まるので、標準ライブラリの安定ソートで求めた並びと比べる。
+pub fn is_stably_sorted_by_key<T, K, F>(
+    input: &[T],
+    output: &[T],
+    key: &F,
+    order: &SortOrder,
+) -> bool
+where
+    T: PartialEq + Sync,
+    K: Ord + Send,
+    F: Fn(&T) -> K + Sync,
+{
+    find_unstable_by_key(input, output, key, order).is_none()
+}
+
+/// output が input を安定ソートした並びと異なる最初の位置を返す。長さが違う場合はその短い方の長さを返す
+fn find_unstable_by_key<T, K, F>(
+    input: &[T],
+    output: &[T],
+    key: &F,
+    order: &SortOrder,
+) -> Option<usize>
+where
+    T: PartialEq + Sync,
+    K: Ord + Send,
+    F: Fn(&T) -> K + Sync,
+{
+    if input.len() != output.len() {
+        return Some(input.len().min(output.len()));
+    }
+    let keys = input.par_iter().map(key).collect::<Vec<_>>();
+    let mut indices = (0..input.len()).collect::<Vec<_>>();
+    match *order {
+        SortOrder::Ascending => indices.sort_by(|&i, &j| keys[i].cmp(&keys[j])),
+        SortOrder::Descending => indices.sort_by(|&i, &j| keys[j].cmp(&keys[i])),
+    }
+    indices
+        .par_iter()
+        .zip(output)
+        .position_first(|(&i, v)| input[i] != *v)
+}
+
+/// x が order の順に並んでいなければ、並んでいない位置と要素を示して panic する
+#[track_caller]
+pub fn assert_sorted<T: Ord + Debug + Sync>(x: &[T], order: &SortOrder) {
+    match *order {
+        SortOrder::Ascending => assert_sorted_by(x, &|a, b| a.cmp(b)),
+        SortOrder::Descending => assert_sorted_by(x, &|a, b| b.cmp(a)),
+    }
+}
+
+/// x が comparator の順に並んでいなければ、並んでいない位置と要素を示して panic する
+#[track_caller]
+pub fn assert_sorted_by<T, F>(x: &[T], comparator: &F)
+where
+    T: Debug + Sync,
+    F: Fn(&T, &T) -> Ordering + Sync,
+{
+    if let Some(i) = find_unsorted_by(x, comparator) {
+        panic!(
+            "not sorted at index {}: {:?} comes before {:?}",
+            i,
+            x[i],
+            x[i + 1]
+        );
+    }
+}
+
+/// x の指紋が expected と異なれば panic する
+///
+/// ```
+/// use bicycle_book::ch03::fourth::sort;
+/// use bicycle_book::ch03::util::{assert_permutation, fingerprint, new_u32_vec};
+/// use bicycle_book::ch03::SortOrder;
+///
+/// let mut x = new_u32_vec(1000);
+/// let expected = fingerprint(&x);
+/// sort(&mut x, &SortOrder::Ascending);
+/// assert_permutation(&expected, &x);
+/// ```
+#[track_caller]
+pub fn assert_permutation<T: Hash + Sync>(expected: &Fingerprint, x: &[T]) {
+    let actual = fingerprint(x);
+    assert!(
+        actual == *expected,
+        "elements changed: expected {:?}, got {:?}",
+        expected,
+        actual
+    );
+}
+
+/// output が input を key の order の順に安定ソートしたものでなければ、最初に異なる位置を示して panic する
+#[track_caller]
+pub fn assert_stably_sorted_by_key<T, K, F>(input: &[T], output: &[T], key: &F, order: &SortOrder)
+where
+    T: PartialEq + Debug + Sync,
+    K: Ord + Send,
+    F: Fn(&T) -> K + Sync,
+{
+    if let Some(i) = find_unstable_by_key(input, output, key, order) {
+        if input.len() != output.len() {
+            panic!(
+                "length changed: input has {} elements, output has {}",
+                input.len(),
+                output.len()
+            );
+        }
+        panic!("not a stable sort at index {}: found {:?}", i, output[i]);
+    }
+}
+
+/// ベンチマークの入力の分布
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distribution {
+    /// 一様乱数
+    Random,
+    /// 昇順にソート済み
+    Sorted,
+    /// 降順にソート済み
+    Reversed,
+    /// ソート済みの列の 1% の位置を入れ替えたもの
+    NearlySorted,
+    /// 16 種類の値だけからなる乱数
+    FewUnique,
+    /// 順位 k の値が 1/k に比例する頻度で現れる乱数
+    Zipf,
+}
+
+impl Distribution {
+    /// 全ての分布
+    pub const ALL: [Distribution; 6] = [
+        Distribution::Random,
+        Distribution::Sorted,
+        Distribution::Reversed,
+        Distribution::NearlySorted,
+        Distribution::FewUnique,
+        Distribution::Zipf,
+    ];
+
+    /// 分布の名前。[`FromStr`] で解釈できる
+    pub fn name(&self) -> &'static str {
+        match self {
+            Distribution::Random => "random",
+            Distribution::Sorted => "sorted",
+            Distribution::Reversed => "reversed",
+            Distribution::NearlySorted => "nearly_sorted",
+            Distribution::FewUnique => "few_unique",
+            Distribution::Zipf => "zipf",
+        }
+    }
+}
+
+/// 分布の名前が不明な時のエラー
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("unknown distribution: {0}")]
+pub struct UnknownDistribution(pub String);
+
+impl FromStr for Distribution {
+    type Err = UnknownDistribution;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Distribution::ALL
+            .iter()
+            .find(|dist| dist.name() == s)
+            .copied()
+            .ok_or_else(|| UnknownDistribution(s.to_string()))
+    }
+}
+
+/// dist に従う n 個の u32 を返す。同じ引数には常に同じ列を返す
+pub fn new_u32_vec_with(n: usize, dist: Distribution) -> Vec<u32> {
+    let mut rng = Pcg64Mcg::from_seed([0; 16]);
+    match dist {
+        Distribution::Random => new_u32_vec(n),
+        Distribution::Sorted => {
+            let mut x = new_u32_vec(n);
+            x.sort_unstable();
+            x
+        }
+        Distribution::Reversed => {
+            let mut x = new_u32_vec(n);
+            x.sort_unstable_by(|a, b| b.cmp(a));
+            x
+        }
+        Distribution::NearlySorted => {
+            let mut x = new_u32_vec_with(n, Distribution::Sorted);
+            if n >= 2 {
+                for _ in 0..(n / 100).max(1) {
+                    x.swap(rng.gen_range(0, n), rng.gen_range(0, n));
+                }
+            }
+            x
+        }
+        Distribution::FewUnique => {
+            let values = new_u32_vec(FEW_UNIQUE_VALUES);
+            (0..n)
+                .map(|_| values[rng.gen_range(0, FEW_UNIQUE_VALUES)])
+                .collect()
+        }
+        Distribution::Zipf => {
+            // 順位ごとの累積の重みを作り、一様乱数を二分探索して順位を選ぶ
+            let ranks = n.clamp(1, ZIPF_RANKS);
+            let cumulative = (1..=ranks)
+                .scan(0.0, |sum, k| {
+                    *sum += 1.0 / k as f64;
+                    Some(*sum)
+                })
+                .collect::<Vec<_>>();
+            let total = cumulative[ranks - 1];
+            (0..n)
+                .map(|_| {
+                    let p = rng.gen::<f64>() * total;
+                    let rank = cumulative.partition_point(|&c| c < p).min(ranks - 1);
+                    // 順位と値の大小が一致しないように、順位を値全体に散らす
+                    (rank as u32).wrapping_mul(0x9e37_79b9)
+                })
+                .collect()
+        }
+    }
+}
+
+/// dist に従う n 個の文字列を返す
+///
+/// 文字列の順序は、元にした u32 の順序と一致する。
+pub fn new_string_vec(n: usize, dist: Distribution) -> Vec<String> {
+    new_u32_vec_with(n, dist)
+        .into_iter()
+        .map(|v| format!("key-{:010}", v))
+        .collect()
+}
+
+/// key だけで比較する 64 バイトの構造体。大きな要素を移動する時のコストを測るのに使う
+#[derive(Debug, Clone, Copy)]
+pub struct Payload {
+    pub key: u32,
+    pub data: [u64; 7],
+}
+
+impl Payload {
+    pub fn new(key: u32) -> Self {
+        Self {
+            key,
+            data: [u64::from(key); 7],
+        }
+    }
+}
+
+impl PartialEq for Payload {
+    fn eq(&self, other: &Self) -> bool {
+        self.key == other.key
+    }
+}
+
+impl Eq for Payload {}
+
+impl Hash for Payload {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.key.hash(state);
+    }
+}
+
+impl PartialOrd for Payload {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Payload {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.key.cmp(&other.key)
+    }
+}
+
+/// dist に従うキーを持つ n 個の [`Payload`] を返す
+pub fn new_payload_vec(n: usize, dist: Distribution) -> Vec<Payload> {
+    new_u32_vec_with(n, dist)
+        .into_iter()
+        .map(Payload::new)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch03::SortOrder::*;
+    use std::cmp::Reverse;
+    use std::collections::HashSet;
+
+    #[test]
+    fn parse_distribution() {
+        for dist in Distribution::ALL.iter() {
+            assert_eq!(dist.name().parse(), Ok(*dist));
+        }
+        assert_eq!(
+            "gauss".parse::<Distribution>(),
+            Err(UnknownDistribution("gauss".to_string()))
+        );
+    }
+
+    #[test]
+    fn distributions_have_expected_shape() {
+        let n = 10000;
+        for dist in Distribution::ALL.iter() {
+            assert_eq!(new_u32_vec_with(n, *dist).len(), n);
+            assert_eq!(new_u32_vec_with(n, *dist), new_u32_vec_with(n, *dist));
+            assert!(new_u32_vec_with(0, *dist).is_empty());
+        }
+
+        assert!(is_sorted(
+            &new_u32_vec_with(n, Distribution::Sorted),
+            &Ascending
+        ));
+        assert!(is_sorted(
+            &new_u32_vec_with(n, Distribution::Reversed),
+            &Descending
+        ));
+
+        let x = new_u32_vec_with(n, Distribution::NearlySorted);
+        assert!(!is_sorted(&x, &Ascending));
+        let misplaced = x.windows(2).filter(|pair| pair[0] > pair[1]).count();
+        assert!(misplaced <= 2 * n / 100);
+
+        let x = new_u32_vec_with(n, Distribution::FewUnique);
+        assert!(x.iter().collect::<HashSet<_>>().len() <= FEW_UNIQUE_VALUES);
+
+        // 最頻の値は全体の 1 割ほどを占める
+        let x = new_u32_vec_with(n, Distribution::Zipf);
+        let top = x.iter().filter(|&&v| v == 0).count();
+        assert!(top > n / 20 && top < n / 5, "top: {}", top);
+    }
+
+    #[test]
+    fn sortedness() {
+        let x = new_u32_vec_with(100000, Distribution::Sorted);
+        assert!(is_sorted(&x, &Ascending));
+        assert!(!is_sorted(&x, &Descending));
+        assert!(is_sorted_by(&x, &|a, b| (a / 2).cmp(&(b / 2))));
+        assert!(is_sorted::<u32>(&[], &Descending));
+        assert_sorted(&x, &Ascending);
+
+        let x = new_u32_vec_with(100000, Distribution::NearlySorted);
+        let i = x.windows(2).position(|pair| pair[0] > pair[1]);
+        assert_eq!(find_unsorted_by(&x, &|a, b| a.cmp(b)), i);
+    }
+
+    #[test]
+    #[should_panic(expected = "not sorted at index 1: 3 comes before 2")]
+    fn assert_sorted_reports_position() {
+        assert_sorted(&[1, 3, 2, 4], &Ascending);
+    }
+
+    #[test]
+    fn fingerprint_ignores_order() {
+        let x = new_u32_vec(100000);
+        let expected = fingerprint(&x);
+
+        let mut y = x.clone();
+        y.sort_unstable();
+        assert_eq!(fingerprint(&y), expected);
+        assert_permutation(&expected, &y);
+
+        // 要素を 1 つ重複させて別の要素を失った列は、ソート済みでも検出できる
+        y[1] = y[0];
+        assert!(is_sorted(&y, &Ascending));
+        assert_ne!(fingerprint(&y), expected);
+        y.pop();
+        assert_ne!(fingerprint(&y), expected);
+
+        assert_eq!(fingerprint(&["b", "a", "a"]), fingerprint(&["a", "b", "a"]));
+        assert_ne!(fingerprint(&["a", "a", "b"]), fingerprint(&["a", "b", "b"]));
+    }
+
+    #[test]
+    #[should_panic(expected = "elements changed")]
+    fn assert_permutation_detects_duplicates() {
+        let expected = fingerprint(&[3, 1, 2]);
+        assert_permutation(&expected, &[1, 1, 3]);
+    }
+
+    #[test]
+    fn stability() {
+        let input = new_u32_vec(20000)
+            .into_iter()
+            .enumerate()
+            .map(|(i, v)| (v % 10, i))
+            .collect::<Vec<_>>();
+        for order in &[Ascending, Descending] {
+            let mut output = input.clone();
+            match *order {
+                Ascending => output.sort_by_key(|&(k, _)| k),
+                Descending => output.sort_by_key(|&(k, _)| Reverse(k)),
+            }
+            assert!(is_stably_sorted_by_key(
+                &input,
+                &output,
+                &|&(k, _)| k,
+                order
+            ));
+            assert_stably_sorted_by_key(&input, &output, &|&(k, _)| k, order);
+
+            // 同じキーの 2 つの要素を入れ替えると安定ではなくなる
+            let j = (1..output.len())
+                .find(|&j| output[j].0 == output[0].0)
+                .unwrap();
+            output.swap(0, j);
+            assert!(is_sorted_by(&output, &|a, b| match *order {
+                Ascending => a.0.cmp(&b.0),
+                Descending => b.0.cmp(&a.0),
+            }));
+            assert!(!is_stably_sorted_by_key(
+                &input,
+                &output,
+                &|&(k, _)| k,
+                order
+            ));
+        }
+        assert!(!is_stably_sorted_by_key(
+            &input,
+            &input[1..],
+            &|&(k, _)| k,
+            &Ascending
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "not a stable sort at index 0: found (1, 'b')")]
+    fn assert_stably_sorted_reports_position() {
+        let input = [(1, 'a'), (0, 'c'), (1, 'b')];
+        assert_stably_sorted_by_key(
+            &input,
+            &[(1, 'b'), (1, 'a'), (0, 'c')],
+            &|&(k, _)| k,
+            &Descending,
+        );
+    }
+
+    #[test]
+    fn payloads_keep_key_order() {
+        let keys = new_u32_vec_with(1000, Distribution::Random);
+        let mut strings = new_string_vec(1000, Distribution::Random);
+        let mut payloads = new_payload_vec(1000, Distribution::Random);
+        let mut sorted = keys.clone();
+        sorted.sort_unstable();
+
+        strings.sort();
+        payloads.sort();
+        assert_eq!(
+            strings,
+            sorted
+                .iter()
+                .map(|v| format!("key-{:010}", v))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(payloads.iter().map(|p| p.key).collect::<Vec<_>>(), sorted);
+        assert_eq!(std::mem::size_of::<Payload>(), 64);
     }
 }
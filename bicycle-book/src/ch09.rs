@@ -1,15 +1,35 @@
+pub mod complex;
+pub mod differential;
+pub mod generator;
+pub mod message;
+pub mod printer;
+pub mod unit;
+pub mod visualize;
+
+use message::Message;
 use std::error::Error as StdError;
 use std::fmt;
 use std::fmt::Formatter;
 use std::iter::Peekable;
 use std::str::FromStr;
 use thiserror::Error;
+use unit::Unit;
 
 /// 位置情報。 .0 から .1 までの区間を表す
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
-struct Loc(usize, usize);
+pub struct Loc(usize, usize);
 
 impl Loc {
+    /// 区間の開始位置 (バイト単位)
+    pub fn start(&self) -> usize {
+        self.0
+    }
+
+    /// 区間の終了位置 (バイト単位、この位置は含まない)
+    pub fn end(&self) -> usize {
+        self.1
+    }
+
     fn merge(&self, other: &Loc) -> Loc {
         use std::cmp::{max, min};
         Loc(min(self.0, other.0), max(self.1, other.1))
@@ -28,15 +48,35 @@ impl<T> Annotation<T> {
     fn new(value: T, loc: Loc) -> Self {
         Self { value, loc }
     }
+
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    pub fn loc(&self) -> &Loc {
+        &self.loc
+    }
 }
 
 #[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum TokenKind {
     Number(u64),
+    /// 虚数。`2i` や `i` のように数値の後に虚数単位を付けたもの
+    Imaginary(u64),
+    /// 単位
+    Unit(Unit),
     Plus,
     Minus,
     Asterisk,
     Slash,
+    Ampersand,
+    Pipe,
+    Caret,
+    Tilde,
+    /// `<<`
+    Shl,
+    /// `>>`
+    Shr,
     LParen,
     RParen,
 }
@@ -49,6 +89,14 @@ impl Token {
         Self::new(TokenKind::Number(n), loc)
     }
 
+    fn imaginary(n: u64, loc: Loc) -> Self {
+        Self::new(TokenKind::Imaginary(n), loc)
+    }
+
+    fn unit(unit: Unit, loc: Loc) -> Self {
+        Self::new(TokenKind::Unit(unit), loc)
+    }
+
     fn plus(loc: Loc) -> Self {
         Self::new(TokenKind::Plus, loc)
     }
@@ -65,6 +113,30 @@ impl Token {
         Self::new(TokenKind::Slash, loc)
     }
 
+    fn ampersand(loc: Loc) -> Self {
+        Self::new(TokenKind::Ampersand, loc)
+    }
+
+    fn pipe(loc: Loc) -> Self {
+        Self::new(TokenKind::Pipe, loc)
+    }
+
+    fn caret(loc: Loc) -> Self {
+        Self::new(TokenKind::Caret, loc)
+    }
+
+    fn tilde(loc: Loc) -> Self {
+        Self::new(TokenKind::Tilde, loc)
+    }
+
+    fn shl(loc: Loc) -> Self {
+        Self::new(TokenKind::Shl, loc)
+    }
+
+    fn shr(loc: Loc) -> Self {
+        Self::new(TokenKind::Shr, loc)
+    }
+
     fn lparen(loc: Loc) -> Self {
         Self::new(TokenKind::LParen, loc)
     }
@@ -77,6 +149,8 @@ impl Token {
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum LexErrorKind {
     InvalidChar(char),
+    /// `0x` や `0b2` のような不正な数値リテラル
+    MalformedNumber,
     Eof,
 }
 
@@ -87,6 +161,10 @@ impl LexError {
         Self::new(LexErrorKind::InvalidChar(c), loc)
     }
 
+    fn malformed_number(loc: Loc) -> Self {
+        Self::new(LexErrorKind::MalformedNumber, loc)
+    }
+
     fn eof(loc: Loc) -> Self {
         Self::new(LexErrorKind::Eof, loc)
     }
@@ -107,10 +185,17 @@ pub fn lex(input: &str) -> Result<Vec<Token>, LexError> {
     while pos < input.len() {
         match input[pos] {
             b'0'..=b'9' => lex_a_token!(lex_number(input, pos)),
+            b'a'..=b'z' | b'A'..=b'Z' => lex_a_token!(lex_word(input, pos)),
             b'+' => lex_a_token!(lex_plus(input, pos)),
             b'-' => lex_a_token!(lex_minus(input, pos)),
             b'*' => lex_a_token!(lex_asterisk(input, pos)),
             b'/' => lex_a_token!(lex_slash(input, pos)),
+            b'&' => lex_a_token!(lex_ampersand(input, pos)),
+            b'|' => lex_a_token!(lex_pipe(input, pos)),
+            b'^' => lex_a_token!(lex_caret(input, pos)),
+            b'~' => lex_a_token!(lex_tilde(input, pos)),
+            b'<' => lex_a_token!(lex_shl(input, pos)),
+            b'>' => lex_a_token!(lex_shr(input, pos)),
             b'(' => lex_a_token!(lex_lparen(input, pos)),
             b')' => lex_a_token!(lex_rparen(input, pos)),
             b' ' | b'\n' | b'\t' => {
@@ -155,6 +240,38 @@ fn lex_slash(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
     consume_byte(input, start, b'/').map(|(_, end)| (Token::slash(Loc(start, end)), end))
 }
 
+fn lex_ampersand(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
+    consume_byte(input, start, b'&').map(|(_, end)| (Token::ampersand(Loc(start, end)), end))
+}
+
+fn lex_pipe(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
+    consume_byte(input, start, b'|').map(|(_, end)| (Token::pipe(Loc(start, end)), end))
+}
+
+fn lex_caret(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
+    consume_byte(input, start, b'^').map(|(_, end)| (Token::caret(Loc(start, end)), end))
+}
+
+fn lex_tilde(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
+    consume_byte(input, start, b'~').map(|(_, end)| (Token::tilde(Loc(start, end)), end))
+}
+
+fn lex_shl(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
+    consume_pair(input, start, b'<').map(|end| (Token::shl(Loc(start, end)), end))
+}
+
+fn lex_shr(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
+    consume_pair(input, start, b'>').map(|end| (Token::shr(Loc(start, end)), end))
+}
+
+/// b が 2 つ続くことを確かめる。1 つしかなければ、その b を不正な文字として報告する
+fn consume_pair(input: &[u8], start: usize, b: u8) -> Result<usize, LexError> {
+    let (_, pos) = consume_byte(input, start, b)?;
+    consume_byte(input, pos, b)
+        .map(|(_, end)| end)
+        .map_err(|_| LexError::invalid_char(b as char, Loc(start, pos)))
+}
+
 fn lex_lparen(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
     consume_byte(input, start, b'(').map(|(_, end)| (Token::lparen(Loc(start, end)), end))
 }
@@ -167,10 +284,54 @@ fn lex_number(input: &[u8], pos: usize) -> Result<(Token, usize), LexError> {
     use std::str::from_utf8;
 
     let start = pos;
-    let end = recognize_many(input, start, |b| b"1234567890".contains(&b));
+    // 0x, 0o, 0b で始まれば 16 進数、8 進数、2 進数として読む
+    let (radix, digits_start) = match input.get(start..start + 2) {
+        Some(b"0x") => (16, start + 2),
+        Some(b"0o") => (8, start + 2),
+        Some(b"0b") => (2, start + 2),
+        _ => (10, start),
+    };
+    let end = if radix == 10 {
+        recognize_many(input, start, |b| b"1234567890_".contains(&b))
+    } else {
+        // 基数に合わない文字も含めて読み、不正なリテラルとして報告する
+        recognize_many(input, digits_start, |b| {
+            b.is_ascii_alphanumeric() || b == b'_'
+        })
+    };
+
+    // _ は桁と桁の間の区切りとして読み飛ばす。
+    // 先頭 (0x などの直後) や末尾の _、続けて並んだ _ は区切りにならないので不正とする
+    let digits = &input[digits_start..end];
+    if digits.starts_with(b"_") || digits.ends_with(b"_") || digits.windows(2).any(|w| w == b"__") {
+        return Err(LexError::malformed_number(Loc(start, end)));
+    }
+    let digits = from_utf8(digits).unwrap().replace('_', "");
+    let n = u64::from_str_radix(&digits, radix)
+        .map_err(|_| LexError::malformed_number(Loc(start, end)))?;
+    // 数値の直後に虚数単位が続けば虚数とする
+    match consume_byte(input, end, b'i') {
+        Ok((_, end)) => Ok((Token::imaginary(n, Loc(start, end)), end)),
+        Err(_) => Ok((Token::number(n, Loc(start, end)), end)),
+    }
+}
+
+/// 英字の並びを虚数単位 `i` または単位として読む
+fn lex_word(input: &[u8], start: usize) -> Result<(Token, usize), LexError> {
+    use std::str::from_utf8;
 
-    let n = from_utf8(&input[start..end]).unwrap().parse().unwrap();
-    Ok((Token::number(n, Loc(start, end)), end))
+    let end = recognize_many(input, start, |b| b.is_ascii_alphabetic());
+    let loc = Loc(start, end);
+    match from_utf8(&input[start..end]).unwrap() {
+        "i" => Ok((Token::imaginary(1, loc), end)),
+        word => match Unit::from_symbol(word) {
+            Some(unit) => Ok((Token::unit(unit, loc), end)),
+            None => Err(LexError::invalid_char(
+                input[start] as char,
+                Loc(start, start + 1),
+            )),
+        },
+    }
 }
 
 fn skip_spaces(input: &[u8], pos: usize) -> Result<((), usize), LexError> {
@@ -189,6 +350,12 @@ fn recognize_many(input: &[u8], mut pos: usize, mut f: impl FnMut(u8) -> bool) -
 pub enum AstKind {
     /// 数値
     Num(u64),
+    /// 虚数
+    Imag(u64),
+    /// 単位付きの数値
+    Quantity { n: u64, unit: Unit },
+    /// 単位のみ。1 単位を表す
+    Unit(Unit),
     /// 単項演算
     UniOp { op: UniOp, e: Box<Ast> },
     /// 二項演算
@@ -202,6 +369,18 @@ impl Ast {
         Self::new(AstKind::Num(n), loc)
     }
 
+    fn imag(n: u64, loc: Loc) -> Self {
+        Self::new(AstKind::Imag(n), loc)
+    }
+
+    fn quantity(n: u64, unit: Unit, loc: Loc) -> Self {
+        Self::new(AstKind::Quantity { n, unit }, loc)
+    }
+
+    fn unit(unit: Unit, loc: Loc) -> Self {
+        Self::new(AstKind::Unit(unit), loc)
+    }
+
     fn uni_op(op: UniOp, e: Ast, loc: Loc) -> Self {
         Self::new(AstKind::UniOp { op, e: Box::new(e) }, loc)
     }
@@ -224,6 +403,8 @@ pub enum UniOpKind {
     Plus,
     /// 負号
     Minus,
+    /// ビット反転
+    BitNot,
 }
 
 type UniOp = Annotation<UniOpKind>;
@@ -248,6 +429,16 @@ pub enum BinOpKind {
     Multi,
     /// 除算
     Div,
+    /// ビット積
+    BitAnd,
+    /// ビット和
+    BitOr,
+    /// 排他的論理和
+    BitXor,
+    /// 左シフト
+    Shl,
+    /// 算術右シフト
+    Shr,
 }
 
 type BinOp = Annotation<BinOpKind>;
@@ -270,25 +461,19 @@ impl BinOp {
     }
 }
 
-#[derive(Error, Debug, Clone, Eq, PartialEq, Hash)]
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum ParseError {
     /// 予期しないトークンがきた
-    #[error("{}: {} is not expected", .0.loc, .0.value)]
     UnexpectedToken(Token),
     /// 式を期待していたのに式でないものがきた
-    #[error("{}: '{}' is not a start of expression", .0.loc, .0.value)]
     NotExpression(Token),
     /// 演算子を期待していたのに演算子でないものがきた
-    #[error("{}: '{}' is not an operator", .0.loc, .0.value)]
     NotOperator(Token),
     /// 括弧が閉じられていない
-    #[error("{}: '{}' is not closed", .0.loc, .0.value)]
     UnclosedOpenParen(Token),
     /// 式の解析が終わったのにまだトークンが残っている
-    #[error("{}: expression after '{}' is redundant", .0.loc, .0.value)]
     RedundantExpression(Token),
     /// パース途中で入力が終わった
-    #[error("End of file")]
     Eof,
 }
 
@@ -305,7 +490,96 @@ fn parse_expr<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<Ast, ParseError>
 where
     Tokens: Iterator<Item = Token>,
 {
-    parse_expr3(tokens)
+    parse_expr7(tokens)
+}
+
+fn parse_expr7<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<Ast, ParseError>
+where
+    Tokens: Iterator<Item = Token>,
+{
+    fn parse_expr7_op<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<BinOp, ParseError>
+    where
+        Tokens: Iterator<Item = Token>,
+    {
+        let op = tokens
+            .peek()
+            .ok_or(ParseError::Eof)
+            .and_then(|tok| match tok.value {
+                TokenKind::Pipe => Ok(BinOp::new(BinOpKind::BitOr, tok.loc.clone())),
+                _ => Err(ParseError::NotOperator(tok.clone())),
+            })?;
+        tokens.next();
+        Ok(op)
+    }
+
+    parse_left_binop(tokens, parse_expr6, parse_expr7_op)
+}
+
+fn parse_expr6<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<Ast, ParseError>
+where
+    Tokens: Iterator<Item = Token>,
+{
+    fn parse_expr6_op<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<BinOp, ParseError>
+    where
+        Tokens: Iterator<Item = Token>,
+    {
+        let op = tokens
+            .peek()
+            .ok_or(ParseError::Eof)
+            .and_then(|tok| match tok.value {
+                TokenKind::Caret => Ok(BinOp::new(BinOpKind::BitXor, tok.loc.clone())),
+                _ => Err(ParseError::NotOperator(tok.clone())),
+            })?;
+        tokens.next();
+        Ok(op)
+    }
+
+    parse_left_binop(tokens, parse_expr5, parse_expr6_op)
+}
+
+fn parse_expr5<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<Ast, ParseError>
+where
+    Tokens: Iterator<Item = Token>,
+{
+    fn parse_expr5_op<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<BinOp, ParseError>
+    where
+        Tokens: Iterator<Item = Token>,
+    {
+        let op = tokens
+            .peek()
+            .ok_or(ParseError::Eof)
+            .and_then(|tok| match tok.value {
+                TokenKind::Ampersand => Ok(BinOp::new(BinOpKind::BitAnd, tok.loc.clone())),
+                _ => Err(ParseError::NotOperator(tok.clone())),
+            })?;
+        tokens.next();
+        Ok(op)
+    }
+
+    parse_left_binop(tokens, parse_expr4, parse_expr5_op)
+}
+
+fn parse_expr4<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<Ast, ParseError>
+where
+    Tokens: Iterator<Item = Token>,
+{
+    fn parse_expr4_op<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<BinOp, ParseError>
+    where
+        Tokens: Iterator<Item = Token>,
+    {
+        let op = tokens
+            .peek()
+            .ok_or(ParseError::Eof)
+            .and_then(|tok| match tok.value {
+                TokenKind::Shl => Ok(BinOp::new(BinOpKind::Shl, tok.loc.clone())),
+                TokenKind::Shr => Ok(BinOp::new(BinOpKind::Shr, tok.loc.clone())),
+                _ => Err(ParseError::NotOperator(tok.clone())),
+            })?;
+        tokens.next();
+        Ok(op)
+    }
+
+    parse_left_binop(tokens, parse_expr3, parse_expr4_op)
 }
 
 fn parse_expr3<Tokens>(tokens: &mut Peekable<Tokens>) -> Result<Ast, ParseError>
@@ -364,7 +638,7 @@ where
     Tokens: Iterator<Item = Token>,
 {
     match tokens.peek().map(|tok| tok.value) {
-        Some(TokenKind::Plus) | Some(TokenKind::Minus) => {
+        Some(TokenKind::Plus) | Some(TokenKind::Minus) | Some(TokenKind::Tilde) => {
             let op = match tokens.next() {
                 Some(Token {
                     value: TokenKind::Plus,
@@ -374,6 +648,10 @@ where
                     value: TokenKind::Minus,
                     loc,
                 }) => UniOp::minus(loc),
+                Some(Token {
+                    value: TokenKind::Tilde,
+                    loc,
+                }) => UniOp::new(UniOpKind::BitNot, loc),
                 _ => unreachable!(),
             };
 
@@ -393,7 +671,16 @@ where
         .next()
         .ok_or(ParseError::Eof)
         .and_then(|tok| match tok.value {
-            TokenKind::Number(n) => Ok(Ast::num(n, tok.loc)),
+            TokenKind::Number(n) => match tokens.peek().map(|tok| tok.value) {
+                // 数値の直後の単位は数値に付く
+                Some(TokenKind::Unit(unit)) => {
+                    let loc = tok.loc.merge(&tokens.next().unwrap().loc);
+                    Ok(Ast::quantity(n, unit, loc))
+                }
+                _ => Ok(Ast::num(n, tok.loc)),
+            },
+            TokenKind::Imaginary(n) => Ok(Ast::imag(n, tok.loc)),
+            TokenKind::Unit(unit) => Ok(Ast::unit(unit, tok.loc)),
             TokenKind::LParen => {
                 let e = parse_expr(tokens)?;
                 match tokens.next() {
@@ -433,9 +720,9 @@ where
 
 #[derive(Error, Debug, Clone, Eq, PartialEq, Hash)]
 pub enum Error {
-    #[error("lexer error")]
+    #[error("{}", Message::LexerError)]
     Lexer(#[from] LexError),
-    #[error("parser error")]
+    #[error("{}", Message::ParserError)]
     Parser(#[from] ParseError),
 }
 
@@ -454,16 +741,52 @@ impl fmt::Display for TokenKind {
         use self::TokenKind::*;
         match self {
             Number(n) => n.fmt(f),
+            Imaginary(n) => write!(f, "{}i", n),
+            Unit(unit) => unit.fmt(f),
             Plus => write!(f, "+"),
             Minus => write!(f, "-"),
             Asterisk => write!(f, "*"),
             Slash => write!(f, "/"),
+            Ampersand => write!(f, "&"),
+            Pipe => write!(f, "|"),
+            Caret => write!(f, "^"),
+            Tilde => write!(f, "~"),
+            Shl => write!(f, "<<"),
+            Shr => write!(f, ">>"),
             LParen => write!(f, "("),
             RParen => write!(f, ")"),
         }
     }
 }
 
+impl fmt::Display for UniOpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::UniOpKind::*;
+        match self {
+            Plus => write!(f, "+"),
+            Minus => write!(f, "-"),
+            BitNot => write!(f, "~"),
+        }
+    }
+}
+
+impl fmt::Display for BinOpKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::BinOpKind::*;
+        match self {
+            Add => write!(f, "+"),
+            Sub => write!(f, "-"),
+            Multi => write!(f, "*"),
+            Div => write!(f, "/"),
+            BitAnd => write!(f, "&"),
+            BitOr => write!(f, "|"),
+            BitXor => write!(f, "^"),
+            Shl => write!(f, "<<"),
+            Shr => write!(f, ">>"),
+        }
+    }
+}
+
 impl fmt::Display for Loc {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         write!(f, "{}-{}", self.0, self.1)
@@ -473,66 +796,72 @@ impl fmt::Display for Loc {
 impl fmt::Display for LexError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         use self::LexErrorKind::*;
-        let loc = &self.loc;
-        match self.value {
-            InvalidChar(c) => write!(f, "{}: invalid char '{}'", loc, c),
-            Eof => write!(f, "End of file"),
-        }
+        let message = match self.value {
+            InvalidChar(c) => Message::InvalidChar(&self.loc, c),
+            MalformedNumber => Message::MalformedNumber(&self.loc),
+            Eof => Message::Eof,
+        };
+        message.fmt(f)
     }
 }
 
-// impl fmt::Display for ParseError {
-//     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-//         use self::ParseError::*;
-//         match self {
-//             UnexpectedToken(tok) => write!(f, "{}: {} is not expected", tok.loc, tok.value),
-//             NotExpression(tok) => write!(
-//                 f,
-//                 "{}: '{}' is not a start of expression",
-//                 tok.loc, tok.value
-//             ),
-//             NotOperator(tok) => write!(f, "{}: '{}' is not an operator", tok.loc, tok.value),
-//             UnclosedOpenParen(tok) => write!(f, "{}: '{}' is not closed", tok.loc, tok.value),
-//             RedundantExpression(tok) => write!(
-//                 f,
-//                 "{}: expression after '{}' is redundant",
-//                 tok.loc, tok.value
-//             ),
-//             Eof => write!(f, "End of file"),
-//         }
-//     }
-// }
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        use self::ParseError::*;
+        let message = match self {
+            UnexpectedToken(tok) => Message::UnexpectedToken(tok),
+            NotExpression(tok) => Message::NotExpression(tok),
+            NotOperator(tok) => Message::NotOperator(tok),
+            UnclosedOpenParen(tok) => Message::UnclosedOpenParen(tok),
+            RedundantExpression(tok) => Message::RedundantExpression(tok),
+            Eof => Message::Eof,
+        };
+        message.fmt(f)
+    }
+}
 
 impl StdError for LexError {}
 
-// impl StdError for ParseError {}
+impl StdError for ParseError {}
 
 fn print_annotation(input: &str, loc: Loc) {
     eprintln!("{}", input);
-    eprintln!("{}{}", " ".repeat(loc.0), "^".repeat(loc.1 - loc.0));
+    // 入力の終わりを指す空の区間にも、その位置に ^ を 1 つ表示する
+    eprintln!(
+        "{}{}",
+        " ".repeat(loc.0),
+        "^".repeat((loc.1 - loc.0).max(1))
+    );
 }
 
 impl Error {
+    /// 入力 input 上でエラーが起きた区間を返す
+    pub fn loc(&self, input: &str) -> Loc {
+        use self::Error::*;
+        use self::ParseError as P;
+        match self {
+            Lexer(e) => e.loc.clone(),
+            Parser(e) => match e {
+                P::UnexpectedToken(Token { loc, .. })
+                | P::NotExpression(Token { loc, .. })
+                | P::NotOperator(Token { loc, .. })
+                | P::UnclosedOpenParen(Token { loc, .. }) => loc.clone(),
+                P::RedundantExpression(Token { loc, .. }) => Loc(loc.0, input.len()),
+                // 入力の外を指さないように、終わりの位置の空の区間とする
+                P::Eof => Loc(input.len(), input.len()),
+            },
+        }
+    }
+
     /// 診断メッセージを表示する
     pub fn show_diagnostic(&self, input: &str) {
         use self::Error::*;
-        use self::ParseError as P;
-        let (e, loc): (&dyn StdError, Loc) = match self {
-            Lexer(e) => (e, e.loc.clone()),
-            Parser(e) => {
-                let loc = match e {
-                    P::UnexpectedToken(Token { loc, .. })
-                    | P::NotExpression(Token { loc, .. })
-                    | P::NotOperator(Token { loc, .. })
-                    | P::UnclosedOpenParen(Token { loc, .. }) => loc.clone(),
-                    P::RedundantExpression(Token { loc, .. }) => Loc(loc.0, input.len()),
-                    P::Eof => Loc(input.len(), input.len() + 1),
-                };
-                (e, loc)
-            }
+        let e: &dyn StdError = match self {
+            Lexer(e) => e,
+            Parser(e) => e,
         };
         eprintln!("{}", e);
-        print_annotation(input, loc);
+        print_annotation(input, self.loc(input));
     }
 }
 
@@ -548,6 +877,20 @@ impl Default for Interpreter {
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
 pub enum InterpreterErrorKind {
     DivisionByZero,
+    Overflow,
+    /// 整数の評価器に虚数が渡された
+    ImaginaryNumber,
+    /// 単位を扱わない評価器に単位が渡された
+    UnitOfMeasure,
+    /// 次元の異なる量を足し引きしようとした
+    DimensionMismatch {
+        left: String,
+        right: String,
+    },
+    /// シフト量が負か、64 以上だった
+    InvalidShiftAmount,
+    /// 整数以外の値にビット演算を適用しようとした
+    NonIntegerBitwise,
 }
 
 type InterpreterError = Annotation<InterpreterErrorKind>;
@@ -555,9 +898,19 @@ type InterpreterError = Annotation<InterpreterErrorKind>;
 impl fmt::Display for InterpreterError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         use self::InterpreterErrorKind::*;
-        match self.value {
-            DivisionByZero => write!(f, "division by zero"),
-        }
+        let message = match self.value {
+            DivisionByZero => Message::DivisionByZero,
+            Overflow => Message::Overflow,
+            ImaginaryNumber => Message::ImaginaryNumber,
+            UnitOfMeasure => Message::UnitOfMeasure,
+            DimensionMismatch {
+                ref left,
+                ref right,
+            } => Message::DimensionMismatch(left, right),
+            InvalidShiftAmount => Message::InvalidShiftAmount,
+            NonIntegerBitwise => Message::NonIntegerBitwise,
+        };
+        message.fmt(f)
     }
 }
 
@@ -566,6 +919,12 @@ impl StdError for InterpreterError {
         use self::InterpreterErrorKind::*;
         match self.value {
             DivisionByZero => "the right hand expression of the division evaluates to zero",
+            Overflow => "the result of the operation does not fit in a 64-bit integer",
+            ImaginaryNumber => "imaginary numbers can only be evaluated in complex mode",
+            UnitOfMeasure => "units of measure can only be evaluated in unit mode",
+            DimensionMismatch { .. } => "the operands of the operator have different dimensions",
+            InvalidShiftAmount => "the shift amount must be between 0 and 63",
+            NonIntegerBitwise => "bitwise operators can only be applied to integers",
         }
     }
 }
@@ -582,9 +941,18 @@ impl Interpreter {
         use self::AstKind::*;
         match expr.value {
             Num(n) => Ok(n as i64),
+            Imag(_) => Err(InterpreterError::new(
+                InterpreterErrorKind::ImaginaryNumber,
+                expr.loc.clone(),
+            )),
+            Quantity { .. } | Unit(_) => Err(InterpreterError::new(
+                InterpreterErrorKind::UnitOfMeasure,
+                expr.loc.clone(),
+            )),
             UniOp { ref op, ref e } => {
                 let e = self.eval(e)?;
-                Ok(self.eval_uni_op(op, e))
+                self.eval_uni_op(op, e)
+                    .map_err(|e| InterpreterError::new(e, expr.loc.clone()))
             }
             BinOp {
                 ref op,
@@ -599,27 +967,34 @@ impl Interpreter {
         }
     }
 
-    fn eval_uni_op(&mut self, op: &UniOp, n: i64) -> i64 {
+    fn eval_uni_op(&mut self, op: &UniOp, n: i64) -> Result<i64, InterpreterErrorKind> {
         use self::UniOpKind::*;
         match op.value {
-            Plus => n,
-            Minus => -n,
+            Plus => Ok(n),
+            Minus => n.checked_neg().ok_or(InterpreterErrorKind::Overflow),
+            BitNot => Ok(!n),
         }
     }
 
     fn eval_bin_op(&mut self, op: &BinOp, l: i64, r: i64) -> Result<i64, InterpreterErrorKind> {
         use self::BinOpKind::*;
         match op.value {
-            Add => Ok(l + r),
-            Sub => Ok(l - r),
-            Multi => Ok(l * r),
+            Add => l.checked_add(r).ok_or(InterpreterErrorKind::Overflow),
+            Sub => l.checked_sub(r).ok_or(InterpreterErrorKind::Overflow),
+            Multi => l.checked_mul(r).ok_or(InterpreterErrorKind::Overflow),
             Div => {
                 if r == 0 {
                     Err(InterpreterErrorKind::DivisionByZero)
                 } else {
-                    Ok(l / r)
+                    l.checked_div(r).ok_or(InterpreterErrorKind::Overflow)
                 }
             }
+            BitAnd => Ok(l & r),
+            BitOr => Ok(l | r),
+            BitXor => Ok(l ^ r),
+            Shl | Shr if !(0..64).contains(&r) => Err(InterpreterErrorKind::InvalidShiftAmount),
+            Shl => Ok(l << r),
+            Shr => Ok(l >> r),
         }
     }
 }
@@ -643,10 +1018,13 @@ impl RpnCompiler {
         use self::AstKind::*;
         match expr.value {
             Num(n) => buf.push_str(&n.to_string()),
-            UniOp { ref op, ref e } => {
-                self.compile_uni_op(op, buf);
-                self.compile_inner(e, buf);
+            Imag(n) => {
+                buf.push_str(&n.to_string());
+                buf.push('i');
             }
+            Quantity { n, unit } => buf.push_str(&format!("{}{}", n, unit)),
+            Unit(unit) => buf.push_str(unit.symbol()),
+            UniOp { ref op, ref e } => self.compile_uni_op(op, e, buf),
             BinOp {
                 ref op,
                 ref l,
@@ -661,11 +1039,30 @@ impl RpnCompiler {
         }
     }
 
-    fn compile_uni_op(&mut self, op: &UniOp, buf: &mut String) {
+    fn compile_uni_op(&mut self, op: &UniOp, e: &Ast, buf: &mut String) {
         use self::UniOpKind::*;
-        match op.value {
-            Plus => buf.push('+'),
-            Minus => buf.push('-'),
+        match (&op.value, &e.value) {
+            // 数値リテラルには符号を直接付ける
+            (Plus, AstKind::Num(_)) => {
+                buf.push('+');
+                self.compile_inner(e, buf);
+            }
+            (Minus, AstKind::Num(_)) => {
+                buf.push('-');
+                self.compile_inner(e, buf);
+            }
+            // それ以外は 0 からの減算として扱う
+            (Plus, _) => self.compile_inner(e, buf),
+            (Minus, _) => {
+                buf.push_str("0 ");
+                self.compile_inner(e, buf);
+                buf.push_str(" -");
+            }
+            // ビット反転は後置の単項演算子にする
+            (BitNot, _) => {
+                self.compile_inner(e, buf);
+                buf.push_str(" ~");
+            }
         }
     }
 
@@ -676,6 +1073,11 @@ impl RpnCompiler {
             Sub => buf.push('-'),
             Multi => buf.push('*'),
             Div => buf.push('/'),
+            BitAnd => buf.push('&'),
+            BitOr => buf.push('|'),
+            BitXor => buf.push('^'),
+            Shl => buf.push_str("<<"),
+            Shr => buf.push_str(">>"),
         }
     }
 }
@@ -730,6 +1132,110 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_lexer_imaginary() {
+        assert_eq!(
+            lex("2i*i"),
+            Ok(vec![
+                Token::imaginary(2, Loc(0, 2)),
+                Token::asterisk(Loc(2, 3)),
+                Token::imaginary(1, Loc(3, 4)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_interpreter_rejects_imaginary() {
+        let ast = "1 + 2i".parse::<Ast>().unwrap();
+        assert_eq!(
+            Interpreter.eval(&ast),
+            Err(InterpreterError::new(
+                InterpreterErrorKind::ImaginaryNumber,
+                Loc(4, 6)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_parser_quantity() {
+        assert_eq!(
+            "3 km / s".parse::<Ast>(),
+            Ok(Ast::bin_op(
+                BinOp::div(Loc(5, 6)),
+                Ast::quantity(3, Unit::Kilometre, Loc(0, 4)),
+                Ast::unit(Unit::Second, Loc(7, 8)),
+                Loc(0, 8)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_lexer_radix_literals() {
+        assert_eq!(
+            lex("0x1F 0o17 0b1010 1_000_000"),
+            Ok(vec![
+                Token::number(31, Loc(0, 4)),
+                Token::number(15, Loc(5, 9)),
+                Token::number(10, Loc(10, 16)),
+                Token::number(1_000_000, Loc(17, 26)),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_lexer_malformed_number() {
+        assert_eq!(lex("1 + 0x"), Err(LexError::malformed_number(Loc(4, 6))));
+        assert_eq!(lex("0b2"), Err(LexError::malformed_number(Loc(0, 3))));
+        assert_eq!(lex("0o8_"), Err(LexError::malformed_number(Loc(0, 4))));
+        assert_eq!(lex("1_000_"), Err(LexError::malformed_number(Loc(0, 6))));
+        assert_eq!(lex("0x1F_"), Err(LexError::malformed_number(Loc(0, 5))));
+        assert_eq!(lex("0x_1F"), Err(LexError::malformed_number(Loc(0, 5))));
+        assert_eq!(lex("1__0"), Err(LexError::malformed_number(Loc(0, 4))));
+        assert_eq!(lex("0b1__1"), Err(LexError::malformed_number(Loc(0, 6))));
+        assert_eq!(
+            lex("18446744073709551616"),
+            Err(LexError::malformed_number(Loc(0, 20)))
+        );
+    }
+
+    #[test]
+    fn test_lexer_bitwise() {
+        assert_eq!(
+            lex("~1<<2>>3&|^"),
+            Ok(vec![
+                Token::tilde(Loc(0, 1)),
+                Token::number(1, Loc(1, 2)),
+                Token::shl(Loc(2, 4)),
+                Token::number(2, Loc(4, 5)),
+                Token::shr(Loc(5, 7)),
+                Token::number(3, Loc(7, 8)),
+                Token::ampersand(Loc(8, 9)),
+                Token::pipe(Loc(9, 10)),
+                Token::caret(Loc(10, 11)),
+            ])
+        );
+        assert_eq!(lex("1 < 2"), Err(LexError::invalid_char('<', Loc(2, 3))));
+        assert_eq!(lex("1 >"), Err(LexError::invalid_char('>', Loc(2, 3))));
+    }
+
+    #[test]
+    fn test_interpreter_bitwise_precedence() {
+        let eval = |s: &str| Interpreter.eval(&s.parse::<Ast>().unwrap());
+        assert_eq!(eval("1 + 2 << 3"), Ok(24));
+        assert_eq!(eval("6 & 3 | 8"), Ok(10));
+        assert_eq!(eval("1 | 6 ^ 3 & 5"), Ok(7));
+        assert_eq!(eval("0xF0 >> 4 ^ 0b11"), Ok(12));
+        assert_eq!(eval("~0 >> 60"), Ok(-1));
+        assert_eq!(eval("~0x0F & 0xFF"), Ok(0xF0));
+        assert_eq!(
+            eval("1 << 64"),
+            Err(InterpreterError::new(
+                InterpreterErrorKind::InvalidShiftAmount,
+                Loc(0, 7)
+            ))
+        );
+    }
+
     #[test]
     fn test_parse_error_redundant() {
         assert_eq!(
@@ -765,6 +1271,24 @@ mod tests {
         assert_eq!("1 +".parse::<Ast>(), Err(Error::Parser(ParseError::Eof)));
     }
 
+    #[test]
+    fn test_interpreter_overflow() {
+        let ast = "9223372036854775807 + 1".parse::<Ast>().unwrap();
+        assert_eq!(
+            Interpreter.eval(&ast),
+            Err(InterpreterError::new(
+                InterpreterErrorKind::Overflow,
+                Loc(0, 23)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_rpn_compiler_uni_op() {
+        let ast = "-10 * -(1 + 2)".parse::<Ast>().unwrap();
+        assert_eq!(RpnCompiler.compile(&ast), "-10 0 1 2 + - *");
+    }
+
     #[test]
     fn test_parse_error_invalid_char() {
         assert_eq!(
@@ -0,0 +1,209 @@
+//! 複素数モードの評価器
+//!
+//! 虚数リテラル (`2i`, `i`) を含む式を複素数として評価する。
+
+use super::{Ast, AstKind, BinOpKind, InterpreterError, InterpreterErrorKind, UniOpKind};
+use std::fmt;
+use std::ops::{Add, Div, Mul, Neg, Sub};
+
+/// 複素数
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Complex {
+    pub re: f64,
+    pub im: f64,
+}
+
+impl Complex {
+    pub fn new(re: f64, im: f64) -> Self {
+        Self { re, im }
+    }
+
+    /// 実部
+    pub fn re(self) -> f64 {
+        self.re
+    }
+
+    /// 虚部
+    pub fn im(self) -> f64 {
+        self.im
+    }
+
+    /// 共役複素数
+    pub fn conj(self) -> Self {
+        Self::new(self.re, -self.im)
+    }
+
+    /// 絶対値
+    pub fn abs(self) -> f64 {
+        self.re.hypot(self.im)
+    }
+
+    /// 偏角 (ラジアン)
+    pub fn arg(self) -> f64 {
+        self.im.atan2(self.re)
+    }
+
+    fn is_zero(self) -> bool {
+        self.re == 0.0 && self.im == 0.0
+    }
+}
+
+impl Add for Complex {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.re + rhs.re, self.im + rhs.im)
+    }
+}
+
+impl Sub for Complex {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self::new(self.re - rhs.re, self.im - rhs.im)
+    }
+}
+
+impl Mul for Complex {
+    type Output = Self;
+
+    fn mul(self, rhs: Self) -> Self {
+        Self::new(
+            self.re * rhs.re - self.im * rhs.im,
+            self.re * rhs.im + self.im * rhs.re,
+        )
+    }
+}
+
+impl Div for Complex {
+    type Output = Self;
+
+    fn div(self, rhs: Self) -> Self {
+        let d = rhs.re * rhs.re + rhs.im * rhs.im;
+        Self::new(
+            (self.re * rhs.re + self.im * rhs.im) / d,
+            (self.im * rhs.re - self.re * rhs.im) / d,
+        )
+    }
+}
+
+impl Neg for Complex {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.re, -self.im)
+    }
+}
+
+/// `a+bi` の形式で表示する
+impl fmt::Display for Complex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // -0 を 0 として表示するために 0.0 を足しておく
+        write!(f, "{}{:+}i", self.re + 0.0, self.im + 0.0)
+    }
+}
+
+/// 複素数モードの評価器
+pub struct ComplexInterpreter;
+
+impl ComplexInterpreter {
+    pub fn eval(&mut self, expr: &Ast) -> Result<Complex, InterpreterError> {
+        match expr.value {
+            AstKind::Num(n) => Ok(Complex::new(n as f64, 0.0)),
+            AstKind::Imag(n) => Ok(Complex::new(0.0, n as f64)),
+            AstKind::Quantity { .. } | AstKind::Unit(_) => Err(InterpreterError::new(
+                InterpreterErrorKind::UnitOfMeasure,
+                expr.loc.clone(),
+            )),
+            AstKind::UniOp { ref op, ref e } => {
+                let e = self.eval(e)?;
+                match op.value {
+                    UniOpKind::Plus => Ok(e),
+                    UniOpKind::Minus => Ok(-e),
+                    UniOpKind::BitNot => Err(InterpreterError::new(
+                        InterpreterErrorKind::NonIntegerBitwise,
+                        op.loc.clone(),
+                    )),
+                }
+            }
+            AstKind::BinOp {
+                ref op,
+                ref l,
+                ref r,
+            } => {
+                let l = self.eval(l)?;
+                let r = self.eval(r)?;
+                match op.value {
+                    BinOpKind::Add => Ok(l + r),
+                    BinOpKind::Sub => Ok(l - r),
+                    BinOpKind::Multi => Ok(l * r),
+                    BinOpKind::Div => {
+                        if r.is_zero() {
+                            Err(InterpreterError::new(
+                                InterpreterErrorKind::DivisionByZero,
+                                expr.loc.clone(),
+                            ))
+                        } else {
+                            Ok(l / r)
+                        }
+                    }
+                    BinOpKind::BitAnd
+                    | BinOpKind::BitOr
+                    | BinOpKind::BitXor
+                    | BinOpKind::Shl
+                    | BinOpKind::Shr => Err(InterpreterError::new(
+                        InterpreterErrorKind::NonIntegerBitwise,
+                        op.loc.clone(),
+                    )),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(s: &str) -> Result<Complex, InterpreterError> {
+        ComplexInterpreter.eval(&s.parse::<Ast>().unwrap())
+    }
+
+    #[test]
+    fn eval_complex_arithmetic() {
+        assert_eq!(eval("1 + 2i"), Ok(Complex::new(1.0, 2.0)));
+        assert_eq!(eval("i * i"), Ok(Complex::new(-1.0, 0.0)));
+        assert_eq!(eval("(1 + 2i) * (3 - i)"), Ok(Complex::new(5.0, 5.0)));
+        assert_eq!(eval("(5 + 5i) / (3 - i)"), Ok(Complex::new(1.0, 2.0)));
+        assert_eq!(eval("-(2 - 3i)"), Ok(Complex::new(-2.0, 3.0)));
+    }
+
+    #[test]
+    fn eval_division_by_zero() {
+        let e = eval("1 / (i - i)").unwrap_err();
+        assert_eq!(e.value, InterpreterErrorKind::DivisionByZero);
+    }
+
+    #[test]
+    fn eval_bitwise_is_error() {
+        let e = eval("i & 1").unwrap_err();
+        assert_eq!(e.value, InterpreterErrorKind::NonIntegerBitwise);
+    }
+
+    #[test]
+    fn display_complex() {
+        assert_eq!(Complex::new(3.0, 4.0).to_string(), "3+4i");
+        assert_eq!(Complex::new(3.0, -4.5).to_string(), "3-4.5i");
+        assert_eq!(Complex::new(-0.0, -0.0).to_string(), "0+0i");
+    }
+
+    #[test]
+    fn complex_functions() {
+        let z = Complex::new(3.0, 4.0);
+        assert_eq!(z.re(), 3.0);
+        assert_eq!(z.im(), 4.0);
+        assert_eq!(z.conj(), Complex::new(3.0, -4.0));
+        assert_eq!(z.abs(), 5.0);
+        assert_eq!(Complex::new(0.0, 1.0).arg(), std::f64::consts::FRAC_PI_2);
+    }
+}
@@ -0,0 +1,277 @@
+//! 差分テスト
+//!
+//! 同じ式を 3 通りの方法で評価し、結果が一致するかを確かめる。
+//!
+//! 1. [`Interpreter`] で構文木を直接評価する
+//! 2. 構文木をソースコードに書き戻し、再びパースしてから評価する
+//! 3. [`RpnCompiler`] で逆ポーランド記法に変換し、スタックマシンで評価する
+//!
+//! 食い違いが見つかったら、食い違いが再現する範囲で式を小さくしてから報告する。
+
+use super::generator::{GenConfig, Generator};
+use super::printer::print;
+use super::{Ast, AstKind, Interpreter, InterpreterErrorKind, RpnCompiler};
+use std::fmt;
+
+/// 評価結果
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum Outcome {
+    Value(i64),
+    DivisionByZero,
+    Overflow,
+    InvalidShiftAmount,
+    /// 評価以前に入力が不正だった
+    Invalid(String),
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Outcome::Value(n) => n.fmt(f),
+            Outcome::DivisionByZero => write!(f, "division by zero"),
+            Outcome::Overflow => write!(f, "overflow"),
+            Outcome::InvalidShiftAmount => write!(f, "invalid shift amount"),
+            Outcome::Invalid(msg) => write!(f, "invalid input ({})", msg),
+        }
+    }
+}
+
+/// 評価方法の間で結果が食い違った式
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct Mismatch {
+    /// 式のソースコード
+    pub source: String,
+    /// 式を逆ポーランド記法に変換したもの
+    pub rpn: String,
+    /// 構文木を直接評価した結果
+    pub interpreted: Outcome,
+    /// ソースコードを再パースして評価した結果
+    pub reparsed: Outcome,
+    /// 逆ポーランド記法を評価した結果
+    pub rpn_evaluated: Outcome,
+    /// 再パースした構文木が元の構文木と異なるか
+    pub tree_differs: bool,
+}
+
+impl fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "mismatch found in `{}`", self.source)?;
+        writeln!(f, "  interpreter: {}", self.interpreted)?;
+        writeln!(f, "  reparsed:    {}", self.reparsed)?;
+        write!(f, "  rpn:         {} (`{}`)", self.rpn_evaluated, self.rpn)?;
+        if self.tree_differs {
+            write!(f, "\n  reparsed tree differs from the original")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+/// 1 つの式について 3 通りの評価結果が一致するかを確かめる
+pub fn check(ast: &Ast) -> Result<(), Box<Mismatch>> {
+    let (source, ast) = print(ast);
+    let interpreted = interpret(&ast);
+    let (reparsed, tree_differs) = match source.parse::<Ast>() {
+        Ok(reparsed) => (interpret(&reparsed), reparsed != ast),
+        Err(e) => (Outcome::Invalid(e.to_string()), true),
+    };
+    let rpn = RpnCompiler.compile(&ast);
+    let rpn_evaluated = eval_rpn(&rpn);
+
+    if interpreted == reparsed && interpreted == rpn_evaluated && !tree_differs {
+        Ok(())
+    } else {
+        Err(Box::new(Mismatch {
+            source,
+            rpn,
+            interpreted,
+            reparsed,
+            rpn_evaluated,
+            tree_differs,
+        }))
+    }
+}
+
+/// 乱数で生成した `cases` 個の式を [`check`] する
+///
+/// 食い違いが見つかった場合は、[`shrink`] で最小化した式についての結果を返す。
+pub fn run(seed: u64, cases: usize, config: GenConfig) -> Result<(), Box<Mismatch>> {
+    let mut gen = Generator::new(seed, config);
+    for _ in 0..cases {
+        let (_, ast) = gen.generate();
+        if check(&ast).is_err() {
+            let minimal = shrink(&ast, |ast| check(ast).is_err());
+            return check(&minimal);
+        }
+    }
+    Ok(())
+}
+
+/// `fails` が真になる性質を保ったまま、構文木をできるだけ小さくする
+///
+/// 部分木での置き換えと数値の縮小を、どれも効かなくなるまで繰り返す。
+pub fn shrink<F>(ast: &Ast, fails: F) -> Ast
+where
+    F: Fn(&Ast) -> bool,
+{
+    let mut current = ast.clone();
+    'outer: loop {
+        for candidate in candidates(&current) {
+            if fails(&candidate) {
+                current = candidate;
+                continue 'outer;
+            }
+        }
+        return print(&current).1;
+    }
+}
+
+/// 1 段階だけ小さくした構文木の候補を、小さいものから順に列挙する
+fn candidates(ast: &Ast) -> Vec<Ast> {
+    let mut ret = Vec::new();
+    match ast.value {
+        AstKind::Num(n) => {
+            for m in &[0, 1, n / 2, n - n.min(1)] {
+                if *m < n && !ret.contains(&Ast::num(*m, ast.loc.clone())) {
+                    ret.push(Ast::num(*m, ast.loc.clone()));
+                }
+            }
+        }
+        AstKind::Imag(_) | AstKind::Quantity { .. } | AstKind::Unit(_) => {}
+        AstKind::UniOp { ref op, ref e } => {
+            ret.push((**e).clone());
+            for e in candidates(e) {
+                ret.push(Ast::uni_op(op.clone(), e, ast.loc.clone()));
+            }
+        }
+        AstKind::BinOp {
+            ref op,
+            ref l,
+            ref r,
+        } => {
+            ret.push((**l).clone());
+            ret.push((**r).clone());
+            for l in candidates(l) {
+                ret.push(Ast::bin_op(op.clone(), l, (**r).clone(), ast.loc.clone()));
+            }
+            for r in candidates(r) {
+                ret.push(Ast::bin_op(op.clone(), (**l).clone(), r, ast.loc.clone()));
+            }
+        }
+    }
+    ret
+}
+
+fn interpret(ast: &Ast) -> Outcome {
+    match Interpreter.eval(ast) {
+        Ok(n) => Outcome::Value(n),
+        Err(e) => match e.value {
+            InterpreterErrorKind::DivisionByZero => Outcome::DivisionByZero,
+            InterpreterErrorKind::Overflow => Outcome::Overflow,
+            InterpreterErrorKind::InvalidShiftAmount => Outcome::InvalidShiftAmount,
+            kind => Outcome::Invalid(format!("{:?}", kind)),
+        },
+    }
+}
+
+/// [`RpnCompiler`] の出力を 64 ビット整数で評価する
+fn eval_rpn(exp: &str) -> Outcome {
+    let mut stack: Vec<i64> = Vec::new();
+    for token in exp.split_whitespace() {
+        if let Ok(n) = token.parse::<i64>() {
+            stack.push(n);
+            continue;
+        }
+        if token == "~" {
+            match stack.pop() {
+                Some(x) => stack.push(!x),
+                None => return Outcome::Invalid("stack underflow at '~'".to_string()),
+            }
+            continue;
+        }
+        let (y, x) = match (stack.pop(), stack.pop()) {
+            (Some(y), Some(x)) => (y, x),
+            _ => return Outcome::Invalid(format!("stack underflow at '{}'", token)),
+        };
+        let z = match token {
+            "+" => x.checked_add(y),
+            "-" => x.checked_sub(y),
+            "*" => x.checked_mul(y),
+            "/" if y == 0 => return Outcome::DivisionByZero,
+            "/" => x.checked_div(y),
+            "&" => Some(x & y),
+            "|" => Some(x | y),
+            "^" => Some(x ^ y),
+            "<<" | ">>" if !(0..64).contains(&y) => return Outcome::InvalidShiftAmount,
+            "<<" => Some(x << y),
+            ">>" => Some(x >> y),
+            _ => return Outcome::Invalid(format!("unknown token '{}'", token)),
+        };
+        match z {
+            Some(z) => stack.push(z),
+            None => return Outcome::Overflow,
+        }
+    }
+
+    match stack.as_slice() {
+        [n] => Outcome::Value(*n),
+        _ => Outcome::Invalid(format!("{} values left on the stack", stack.len())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch09::generator::OperatorMix;
+    use crate::ch09::printer::to_source;
+
+    #[test]
+    fn random_expressions_agree() {
+        assert_eq!(run(0, 1000, GenConfig::default()), Ok(()));
+    }
+
+    #[test]
+    fn deep_expressions_agree() {
+        let config = GenConfig {
+            max_depth: 12,
+            max_number: 10,
+            leaf_probability: 0.1,
+            ..GenConfig::default()
+        };
+        assert_eq!(run(1, 200, config), Ok(()));
+    }
+
+    #[test]
+    fn bitwise_expressions_agree() {
+        let config = GenConfig {
+            max_number: 70,
+            ops: OperatorMix {
+                bit_and: 2,
+                bit_or: 2,
+                bit_xor: 2,
+                shl: 2,
+                shr: 2,
+                bit_not: 1,
+                ..OperatorMix::default()
+            },
+            ..GenConfig::default()
+        };
+        assert_eq!(run(2, 1000, config), Ok(()));
+    }
+
+    #[test]
+    fn check_reports_errors_consistently() {
+        let ast = "1 / (2 - 2)".parse::<Ast>().unwrap();
+        assert_eq!(check(&ast), Ok(()));
+        let ast = "-(-9223372036854775807 - 1)".parse::<Ast>().unwrap();
+        assert_eq!(check(&ast), Ok(()));
+    }
+
+    #[test]
+    fn shrink_finds_minimal_case() {
+        let ast = "(3 + 4 * (5 - 6 / 7)) - 8 * 9".parse::<Ast>().unwrap();
+        let has_div = |ast: &Ast| to_source(ast).contains('/');
+        assert_eq!(to_source(&shrink(&ast, has_div)), "0 / 0");
+    }
+}
@@ -0,0 +1,194 @@
+//! 構文木のランダム生成器
+//!
+//! シードを固定すれば同じ式の列が得られるので、失敗したケースを再現できる。
+
+use super::printer::print;
+use super::{Ast, BinOp, BinOpKind, Loc, UniOp, UniOpKind};
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::{Rng, SeedableRng};
+use rand_pcg::Pcg64Mcg;
+
+/// 演算子ごとの出現しやすさ (重み)
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct OperatorMix {
+    pub add: u32,
+    pub sub: u32,
+    pub multi: u32,
+    pub div: u32,
+    pub plus: u32,
+    pub minus: u32,
+    pub bit_and: u32,
+    pub bit_or: u32,
+    pub bit_xor: u32,
+    pub shl: u32,
+    pub shr: u32,
+    pub bit_not: u32,
+}
+
+impl Default for OperatorMix {
+    fn default() -> Self {
+        Self {
+            add: 3,
+            sub: 3,
+            multi: 2,
+            div: 2,
+            plus: 1,
+            minus: 1,
+            bit_and: 0,
+            bit_or: 0,
+            bit_xor: 0,
+            shl: 0,
+            shr: 0,
+            bit_not: 0,
+        }
+    }
+}
+
+/// [`Generator`] の設定
+#[derive(Debug, Clone, PartialEq)]
+pub struct GenConfig {
+    /// 構文木の深さの上限
+    pub max_depth: u32,
+    /// 数値リテラルの最大値
+    pub max_number: u64,
+    /// 深さの上限に達していなくても数値リテラルを生成する確率
+    pub leaf_probability: f64,
+    /// 演算子の重み
+    pub ops: OperatorMix,
+}
+
+impl Default for GenConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 5,
+            max_number: 100,
+            leaf_probability: 0.2,
+            ops: OperatorMix::default(),
+        }
+    }
+}
+
+/// 文法的に正しい構文木と、それに対応するソースコードを生成する
+pub struct Generator {
+    rng: Pcg64Mcg,
+    config: GenConfig,
+}
+
+impl Generator {
+    pub fn new(seed: u64, config: GenConfig) -> Self {
+        Self {
+            rng: Pcg64Mcg::seed_from_u64(seed),
+            config,
+        }
+    }
+
+    /// 式を 1 つ生成する。構文木の位置情報は返すソースコード上の位置を指す
+    pub fn generate(&mut self) -> (String, Ast) {
+        let ast = self.gen_expr(0);
+        print(&ast)
+    }
+
+    fn gen_expr(&mut self, depth: u32) -> Ast {
+        let ops = &self.config.ops;
+        let weights = [
+            ops.add,
+            ops.sub,
+            ops.multi,
+            ops.div,
+            ops.plus,
+            ops.minus,
+            ops.bit_and,
+            ops.bit_or,
+            ops.bit_xor,
+            ops.shl,
+            ops.shr,
+            ops.bit_not,
+        ];
+        let dist = match WeightedIndex::new(weights) {
+            Ok(dist) => dist,
+            // 演算子が 1 つも選べないときは数値だけになる
+            Err(_) => return self.gen_num(),
+        };
+        if depth >= self.config.max_depth || self.rng.gen_bool(self.config.leaf_probability) {
+            return self.gen_num();
+        }
+
+        match dist.sample(&mut self.rng) {
+            0 => self.gen_bin_op(BinOpKind::Add, depth),
+            1 => self.gen_bin_op(BinOpKind::Sub, depth),
+            2 => self.gen_bin_op(BinOpKind::Multi, depth),
+            3 => self.gen_bin_op(BinOpKind::Div, depth),
+            4 => self.gen_uni_op(UniOpKind::Plus, depth),
+            5 => self.gen_uni_op(UniOpKind::Minus, depth),
+            6 => self.gen_bin_op(BinOpKind::BitAnd, depth),
+            7 => self.gen_bin_op(BinOpKind::BitOr, depth),
+            8 => self.gen_bin_op(BinOpKind::BitXor, depth),
+            9 => self.gen_bin_op(BinOpKind::Shl, depth),
+            10 => self.gen_bin_op(BinOpKind::Shr, depth),
+            _ => self.gen_uni_op(UniOpKind::BitNot, depth),
+        }
+    }
+
+    fn gen_bin_op(&mut self, kind: BinOpKind, depth: u32) -> Ast {
+        let l = self.gen_expr(depth + 1);
+        let r = self.gen_expr(depth + 1);
+        // 位置情報は print で付け直すので、ここではダミーを入れておく
+        Ast::bin_op(BinOp::new(kind, Loc(0, 0)), l, r, Loc(0, 0))
+    }
+
+    fn gen_uni_op(&mut self, kind: UniOpKind, depth: u32) -> Ast {
+        let e = self.gen_expr(depth + 1);
+        Ast::uni_op(UniOp::new(kind, Loc(0, 0)), e, Loc(0, 0))
+    }
+
+    fn gen_num(&mut self) -> Ast {
+        let n = self
+            .rng
+            .gen_range(0, self.config.max_number.saturating_add(1));
+        Ast::num(n, Loc(0, 0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generated_source_parses_to_generated_ast() {
+        let mut gen = Generator::new(0, GenConfig::default());
+        for _ in 0..200 {
+            let (src, ast) = gen.generate();
+            assert_eq!(src.parse::<Ast>(), Ok(ast), "{}", src);
+        }
+    }
+
+    #[test]
+    fn same_seed_same_expressions() {
+        let mut a = Generator::new(42, GenConfig::default());
+        let mut b = Generator::new(42, GenConfig::default());
+        for _ in 0..10 {
+            assert_eq!(a.generate(), b.generate());
+        }
+    }
+
+    #[test]
+    fn operator_mix_is_respected() {
+        let config = GenConfig {
+            ops: OperatorMix {
+                add: 1,
+                sub: 0,
+                multi: 0,
+                div: 0,
+                plus: 0,
+                minus: 0,
+                ..OperatorMix::default()
+            },
+            ..GenConfig::default()
+        };
+        let mut gen = Generator::new(0, config);
+        for _ in 0..50 {
+            let (src, _) = gen.generate();
+            assert!(!src.contains(|c| "-*/".contains(c)), "{}", src);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+//! 診断メッセージのカタログ
+//!
+//! エラーの種類ごとに英語と日本語のメッセージを持つ。
+//! 表示に使う言語は環境変数 `LANG` から決まり、[`set_locale`] で明示的に切り替えられる。
+
+use super::{Loc, Token};
+use std::fmt;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// メッセージの言語
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Default)]
+pub enum Locale {
+    /// 英語
+    #[default]
+    En,
+    /// 日本語
+    Ja,
+}
+
+impl Locale {
+    /// `ja_JP.UTF-8` のようなロケール名から言語を決める。日本語以外は英語とする
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use bicycle_book::ch09::message::Locale;
+    ///
+    /// assert_eq!(Locale::from_name("ja_JP.UTF-8"), Locale::Ja);
+    /// assert_eq!(Locale::from_name("C"), Locale::En);
+    /// ```
+    pub fn from_name(name: &str) -> Locale {
+        if name.starts_with("ja") {
+            Locale::Ja
+        } else {
+            Locale::En
+        }
+    }
+
+    /// 環境変数 `LC_ALL`、`LC_MESSAGES`、`LANG` の順に調べて言語を決める
+    pub fn from_env() -> Locale {
+        ["LC_ALL", "LC_MESSAGES", "LANG"]
+            .iter()
+            .filter_map(|key| std::env::var(key).ok())
+            .find(|name| !name.is_empty())
+            .map_or(Locale::En, |name| Locale::from_name(&name))
+    }
+}
+
+/// まだ言語が決まっていないことを表す
+const UNSET: u8 = 0;
+
+static LOCALE: AtomicU8 = AtomicU8::new(UNSET);
+
+/// 診断メッセージの言語を設定する
+pub fn set_locale(locale: Locale) {
+    let n = match locale {
+        Locale::En => 1,
+        Locale::Ja => 2,
+    };
+    LOCALE.store(n, Ordering::Relaxed);
+}
+
+/// 診断メッセージの言語を返す。設定されていなければ環境変数から決める
+pub fn locale() -> Locale {
+    match LOCALE.load(Ordering::Relaxed) {
+        1 => Locale::En,
+        2 => Locale::Ja,
+        _ => {
+            let locale = Locale::from_env();
+            set_locale(locale);
+            locale
+        }
+    }
+}
+
+/// メッセージの種類と、埋め込む値
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub(super) enum Message<'a> {
+    InvalidChar(&'a Loc, char),
+    MalformedNumber(&'a Loc),
+    Eof,
+    UnexpectedToken(&'a Token),
+    NotExpression(&'a Token),
+    NotOperator(&'a Token),
+    UnclosedOpenParen(&'a Token),
+    RedundantExpression(&'a Token),
+    LexerError,
+    ParserError,
+    DivisionByZero,
+    Overflow,
+    ImaginaryNumber,
+    UnitOfMeasure,
+    DimensionMismatch(&'a str, &'a str),
+    InvalidShiftAmount,
+    NonIntegerBitwise,
+}
+
+impl Message<'_> {
+    /// 指定した言語でメッセージを組み立てる
+    pub(super) fn text(&self, locale: Locale) -> String {
+        use self::Locale::*;
+        use self::Message::*;
+        match (locale, *self) {
+            (En, InvalidChar(loc, c)) => format!("{}: invalid char '{}'", loc, c),
+            (Ja, InvalidChar(loc, c)) => format!("{}: 不正な文字 '{}' です", loc, c),
+            (En, MalformedNumber(loc)) => format!("{}: malformed number literal", loc),
+            (Ja, MalformedNumber(loc)) => format!("{}: 数値リテラルの形式が不正です", loc),
+            (En, Eof) => "End of file".to_string(),
+            (Ja, Eof) => "入力が途中で終わっています".to_string(),
+            (En, UnexpectedToken(tok)) => format!("{}: {} is not expected", tok.loc, tok.value),
+            (Ja, UnexpectedToken(tok)) => {
+                format!("{}: {} は予期しないトークンです", tok.loc, tok.value)
+            }
+            (En, NotExpression(tok)) => {
+                format!("{}: '{}' is not a start of expression", tok.loc, tok.value)
+            }
+            (Ja, NotExpression(tok)) => {
+                format!("{}: '{}' は式の始まりではありません", tok.loc, tok.value)
+            }
+            (En, NotOperator(tok)) => format!("{}: '{}' is not an operator", tok.loc, tok.value),
+            (Ja, NotOperator(tok)) => {
+                format!("{}: '{}' は演算子ではありません", tok.loc, tok.value)
+            }
+            (En, UnclosedOpenParen(tok)) => {
+                format!("{}: '{}' is not closed", tok.loc, tok.value)
+            }
+            (Ja, UnclosedOpenParen(tok)) => {
+                format!("{}: '{}' が閉じられていません", tok.loc, tok.value)
+            }
+            (En, RedundantExpression(tok)) => {
+                format!("{}: expression after '{}' is redundant", tok.loc, tok.value)
+            }
+            (Ja, RedundantExpression(tok)) => {
+                format!("{}: '{}' より後ろの式は余分です", tok.loc, tok.value)
+            }
+            (En, LexerError) => "lexer error".to_string(),
+            (Ja, LexerError) => "字句解析エラー".to_string(),
+            (En, ParserError) => "parser error".to_string(),
+            (Ja, ParserError) => "構文解析エラー".to_string(),
+            (En, DivisionByZero) => "division by zero".to_string(),
+            (Ja, DivisionByZero) => "ゼロで除算しました".to_string(),
+            (En, Overflow) => "overflow".to_string(),
+            (Ja, Overflow) => "オーバーフローしました".to_string(),
+            (En, ImaginaryNumber) => "imaginary number in integer expression".to_string(),
+            (Ja, ImaginaryNumber) => "整数の式に虚数が含まれています".to_string(),
+            (En, UnitOfMeasure) => "unit of measure in unitless expression".to_string(),
+            (Ja, UnitOfMeasure) => "単位を扱わない式に単位が含まれています".to_string(),
+            (En, DimensionMismatch(left, right)) => {
+                format!("dimension mismatch: {} and {}", left, right)
+            }
+            (Ja, DimensionMismatch(left, right)) => {
+                format!("次元が一致しません: {} と {}", left, right)
+            }
+            (En, InvalidShiftAmount) => "shift amount out of range".to_string(),
+            (Ja, InvalidShiftAmount) => "シフト量が範囲外です".to_string(),
+            (En, NonIntegerBitwise) => "bitwise operation on non-integer value".to_string(),
+            (Ja, NonIntegerBitwise) => "整数以外の値にビット演算を適用しました".to_string(),
+        }
+    }
+}
+
+/// 現在の言語で表示する
+impl fmt::Display for Message<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.text(locale()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch09::TokenKind;
+
+    #[test]
+    fn every_message_has_both_languages() {
+        let loc = Loc(2, 3);
+        let tok = Token::new(TokenKind::Asterisk, Loc(4, 5));
+        let messages = [
+            Message::InvalidChar(&loc, 'a'),
+            Message::MalformedNumber(&loc),
+            Message::Eof,
+            Message::UnexpectedToken(&tok),
+            Message::NotExpression(&tok),
+            Message::NotOperator(&tok),
+            Message::UnclosedOpenParen(&tok),
+            Message::RedundantExpression(&tok),
+            Message::LexerError,
+            Message::ParserError,
+            Message::DivisionByZero,
+            Message::Overflow,
+            Message::ImaginaryNumber,
+            Message::UnitOfMeasure,
+            Message::DimensionMismatch("m", "s"),
+            Message::InvalidShiftAmount,
+            Message::NonIntegerBitwise,
+        ];
+        for message in &messages {
+            let en = message.text(Locale::En);
+            let ja = message.text(Locale::Ja);
+            assert!(en.is_ascii(), "{}", en);
+            assert!(!ja.is_ascii(), "{}", ja);
+        }
+    }
+
+    #[test]
+    fn text_embeds_values() {
+        let tok = Token::new(TokenKind::Asterisk, Loc(4, 5));
+        assert_eq!(
+            Message::NotExpression(&tok).text(Locale::En),
+            "4-5: '*' is not a start of expression"
+        );
+        assert_eq!(
+            Message::NotExpression(&tok).text(Locale::Ja),
+            "4-5: '*' は式の始まりではありません"
+        );
+    }
+
+    #[test]
+    fn locale_from_name() {
+        assert_eq!(Locale::from_name("ja_JP.UTF-8"), Locale::Ja);
+        assert_eq!(Locale::from_name("ja"), Locale::Ja);
+        assert_eq!(Locale::from_name("en_US.UTF-8"), Locale::En);
+        assert_eq!(Locale::from_name("C.UTF-8"), Locale::En);
+    }
+}
@@ -0,0 +1,148 @@
+//! 構文木をソースコードに書き戻すプリティプリンタ
+//!
+//! 優先順位と左結合性から必要な箇所にだけ括弧を付けるので、
+//! 出力を再びパースすると元と同じ形の構文木が得られる。
+
+use super::{Ast, AstKind, BinOp, BinOpKind, Loc, UniOp};
+
+/// 構文木をソースコードに変換する
+///
+/// # Examples
+///
+/// ```
+/// use bicycle_book::ch09::printer::to_source;
+/// use bicycle_book::ch09::Ast;
+///
+/// let ast = "((1 + 2)) * (3 - (4 - 5))".parse::<Ast>().unwrap();
+/// assert_eq!(to_source(&ast), "(1 + 2) * (3 - (4 - 5))");
+/// ```
+pub fn to_source(ast: &Ast) -> String {
+    print(ast).0
+}
+
+/// 構文木をソースコードに変換し、その文字列上の位置情報を付け直した構文木とともに返す
+pub fn print(ast: &Ast) -> (String, Ast) {
+    let mut buf = String::new();
+    let ast = write_expr(ast, &mut buf);
+    (buf, ast)
+}
+
+/// 式の結合の強さ。大きいほど強く結合する
+fn precedence(ast: &Ast) -> u8 {
+    match ast.value {
+        AstKind::Num(_) | AstKind::Imag(_) | AstKind::Quantity { .. } | AstKind::Unit(_) => 8,
+        AstKind::UniOp { .. } => 7,
+        AstKind::BinOp { ref op, .. } => match op.value {
+            BinOpKind::Multi | BinOpKind::Div => 6,
+            BinOpKind::Add | BinOpKind::Sub => 5,
+            BinOpKind::Shl | BinOpKind::Shr => 4,
+            BinOpKind::BitAnd => 3,
+            BinOpKind::BitXor => 2,
+            BinOpKind::BitOr => 1,
+        },
+    }
+}
+
+fn write_expr(ast: &Ast, buf: &mut String) -> Ast {
+    match ast.value {
+        AstKind::Num(n) => {
+            let start = buf.len();
+            buf.push_str(&n.to_string());
+            Ast::num(n, Loc(start, buf.len()))
+        }
+        AstKind::Imag(n) => {
+            let start = buf.len();
+            buf.push_str(&n.to_string());
+            buf.push('i');
+            Ast::imag(n, Loc(start, buf.len()))
+        }
+        AstKind::Quantity { n, unit } => {
+            let start = buf.len();
+            buf.push_str(&format!("{} {}", n, unit));
+            Ast::quantity(n, unit, Loc(start, buf.len()))
+        }
+        AstKind::Unit(unit) => {
+            let start = buf.len();
+            buf.push_str(unit.symbol());
+            Ast::unit(unit, Loc(start, buf.len()))
+        }
+        AstKind::UniOp { ref op, ref e } => {
+            let start = buf.len();
+            buf.push_str(&op.value.to_string());
+            let op = UniOp::new(op.value.clone(), Loc(start, buf.len()));
+            let e = write_operand(e, precedence(e) < 8, buf);
+            let loc = op.loc.merge(&e.loc);
+            Ast::uni_op(op, e, loc)
+        }
+        AstKind::BinOp {
+            ref op,
+            ref l,
+            ref r,
+        } => {
+            let prec = precedence(ast);
+            let l = write_operand(l, precedence(l) < prec, buf);
+            buf.push(' ');
+            let start = buf.len();
+            buf.push_str(&op.value.to_string());
+            let op = BinOp::new(op.value.clone(), Loc(start, buf.len()));
+            buf.push(' ');
+            // 左結合なので、右辺は同じ優先順位でも括弧が必要
+            let r = write_operand(r, precedence(r) <= prec, buf);
+            let loc = l.loc.merge(&r.loc);
+            Ast::bin_op(op, l, r, loc)
+        }
+    }
+}
+
+fn write_operand(ast: &Ast, paren: bool, buf: &mut String) -> Ast {
+    if paren {
+        buf.push('(');
+        let ast = write_expr(ast, buf);
+        buf.push(')');
+        ast
+    } else {
+        write_expr(ast, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn print_keeps_associativity() {
+        for src in &["1 - 2 - 3", "1 - (2 - 3)", "8 / 4 / 2", "8 / (4 / 2)"] {
+            let ast = src.parse::<Ast>().unwrap();
+            assert_eq!(&to_source(&ast), src);
+        }
+    }
+
+    #[test]
+    fn print_bitwise_precedence() {
+        for src in &[
+            "1 | 2 ^ 3 & 4 << 5 + 6",
+            "((1 | 2) ^ 3) & 4",
+            "~(1 << 2) >> (3 >> 4)",
+        ] {
+            let ast = src.parse::<Ast>().unwrap();
+            let (printed, relocated) = print(&ast);
+            assert_eq!(printed.parse::<Ast>(), Ok(relocated));
+        }
+        let ast = "((1 | 2) ^ 3) & 4".parse::<Ast>().unwrap();
+        assert_eq!(to_source(&ast), "((1 | 2) ^ 3) & 4");
+    }
+
+    #[test]
+    fn print_unary_operand() {
+        let ast = "-(-(1)) * +(2 + 3) - -4".parse::<Ast>().unwrap();
+        assert_eq!(to_source(&ast), "-(-1) * +(2 + 3) - -4");
+    }
+
+    #[test]
+    fn print_relocates_ast() {
+        let ast = "  (1+2)   *3".parse::<Ast>().unwrap();
+        let (src, relocated) = print(&ast);
+        assert_eq!(src, "(1 + 2) * 3");
+        assert_eq!(src.parse::<Ast>(), Ok(relocated));
+    }
+}
@@ -0,0 +1,342 @@
+//! 単位付きの数量の評価器
+//!
+//! `3 m / 2 s` や `10 km + 200 m` のように単位を付けた式を評価する。
+//! 次元は `+ - * /` を通して追跡し、同じ次元の単位は自動的に換算する。
+
+use super::{Ast, AstKind, BinOpKind, InterpreterError, InterpreterErrorKind, UniOpKind};
+use std::fmt;
+
+/// 基本次元
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Dimension {
+    Length,
+    Time,
+    Mass,
+    Data,
+}
+
+/// 単位
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+pub enum Unit {
+    Millimetre,
+    Centimetre,
+    Metre,
+    Kilometre,
+    Millisecond,
+    Second,
+    Minute,
+    Hour,
+    Day,
+    Gram,
+    Kilogram,
+    Byte,
+    Kilobyte,
+    Megabyte,
+    Gigabyte,
+    Terabyte,
+    Kibibyte,
+    Mebibyte,
+    Gibibyte,
+    Tebibyte,
+}
+
+/// 単位の一覧。記号、次元、基本単位 (m, s, kg, B) への換算係数の組
+const UNITS: &[(Unit, &str, Dimension, f64)] = &[
+    (Unit::Millimetre, "mm", Dimension::Length, 1e-3),
+    (Unit::Centimetre, "cm", Dimension::Length, 1e-2),
+    (Unit::Metre, "m", Dimension::Length, 1.0),
+    (Unit::Kilometre, "km", Dimension::Length, 1e3),
+    (Unit::Millisecond, "ms", Dimension::Time, 1e-3),
+    (Unit::Second, "s", Dimension::Time, 1.0),
+    (Unit::Minute, "min", Dimension::Time, 60.0),
+    (Unit::Hour, "h", Dimension::Time, 3600.0),
+    (Unit::Day, "d", Dimension::Time, 86400.0),
+    (Unit::Gram, "g", Dimension::Mass, 1e-3),
+    (Unit::Kilogram, "kg", Dimension::Mass, 1.0),
+    (Unit::Byte, "B", Dimension::Data, 1.0),
+    (Unit::Kilobyte, "KB", Dimension::Data, 1e3),
+    (Unit::Megabyte, "MB", Dimension::Data, 1e6),
+    (Unit::Gigabyte, "GB", Dimension::Data, 1e9),
+    (Unit::Terabyte, "TB", Dimension::Data, 1e12),
+    (Unit::Kibibyte, "KiB", Dimension::Data, 1024.0),
+    (Unit::Mebibyte, "MiB", Dimension::Data, 1048576.0),
+    (Unit::Gibibyte, "GiB", Dimension::Data, 1073741824.0),
+    (Unit::Tebibyte, "TiB", Dimension::Data, 1099511627776.0),
+];
+
+impl Unit {
+    /// 記号から単位を得る
+    pub fn from_symbol(s: &str) -> Option<Unit> {
+        UNITS
+            .iter()
+            .find(|(_, symbol, _, _)| *symbol == s)
+            .map(|(unit, _, _, _)| *unit)
+    }
+
+    fn entry(self) -> &'static (Unit, &'static str, Dimension, f64) {
+        UNITS.iter().find(|(unit, _, _, _)| *unit == self).unwrap()
+    }
+
+    pub fn symbol(self) -> &'static str {
+        self.entry().1
+    }
+
+    pub fn dimension(self) -> Dimension {
+        self.entry().2
+    }
+
+    /// 基本単位への換算係数
+    pub fn factor(self) -> f64 {
+        self.entry().3
+    }
+}
+
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.symbol())
+    }
+}
+
+/// 単位付きの数量
+///
+/// 単位は基本次元ごとに高々 1 つで、指数と組にして出現順に持つ。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    units: Vec<(Unit, i32)>,
+}
+
+impl Quantity {
+    /// 無次元の数量
+    pub fn scalar(value: f64) -> Self {
+        Self {
+            value,
+            units: Vec::new(),
+        }
+    }
+
+    pub fn new(value: f64, unit: Unit) -> Self {
+        Self {
+            value,
+            units: vec![(unit, 1)],
+        }
+    }
+
+    /// 単位と指数の組
+    pub fn units(&self) -> &[(Unit, i32)] {
+        &self.units
+    }
+
+    fn exponent(&self, dim: Dimension) -> i32 {
+        self.units
+            .iter()
+            .find(|(unit, _)| unit.dimension() == dim)
+            .map_or(0, |(_, exp)| *exp)
+    }
+
+    fn same_dimension(&self, other: &Quantity) -> bool {
+        use self::Dimension::*;
+        [Length, Time, Mass, Data]
+            .iter()
+            .all(|dim| self.exponent(*dim) == other.exponent(*dim))
+    }
+
+    /// 単位を基本単位に換算するときの係数
+    fn scale(&self) -> f64 {
+        self.units
+            .iter()
+            .fold(1.0, |v, (unit, exp)| v * unit.factor().powi(*exp))
+    }
+
+    /// other を self と同じ単位に換算して足す。次元が異なれば None
+    fn add(&self, other: &Quantity, sign: f64) -> Option<Quantity> {
+        if !self.same_dimension(other) {
+            return None;
+        }
+        Some(Quantity {
+            value: self.value + sign * other.value * other.scale() / self.scale(),
+            units: self.units.clone(),
+        })
+    }
+
+    /// other の単位を exp_sign 倍した指数で掛け合わせる。
+    /// 同じ次元の単位があれば self の単位に換算してまとめる
+    fn mul(&self, other: &Quantity, value: f64, exp_sign: i32) -> Quantity {
+        let mut ret = Quantity {
+            value,
+            units: self.units.clone(),
+        };
+        for &(unit, exp) in &other.units {
+            let exp = exp * exp_sign;
+            match ret
+                .units
+                .iter_mut()
+                .find(|(u, _)| u.dimension() == unit.dimension())
+            {
+                Some((u, e)) => {
+                    ret.value *= (unit.factor() / u.factor()).powi(exp);
+                    *e += exp;
+                }
+                None => ret.units.push((unit, exp)),
+            }
+        }
+        ret.units.retain(|(_, exp)| *exp != 0);
+        ret
+    }
+
+    fn unit_string(&self) -> String {
+        fn join<'a>(units: impl Iterator<Item = &'a (Unit, i32)>) -> Vec<String> {
+            units
+                .map(|(unit, exp)| match exp.abs() {
+                    1 => unit.to_string(),
+                    n => format!("{}^{}", unit, n),
+                })
+                .collect()
+        }
+        let num = join(self.units.iter().filter(|(_, exp)| *exp > 0));
+        let den = join(self.units.iter().filter(|(_, exp)| *exp < 0));
+        match (num.is_empty(), den.is_empty()) {
+            (true, true) => "1".to_string(),
+            (false, true) => num.join("*"),
+            (true, false) => format!("1/{}", den.join("/")),
+            (false, false) => format!("{}/{}", num.join("*"), den.join("/")),
+        }
+    }
+}
+
+/// `3 m/s` のように、値と単位を空白で区切って表示する
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.units.is_empty() {
+            self.value.fmt(f)
+        } else {
+            write!(f, "{} {}", self.value, self.unit_string())
+        }
+    }
+}
+
+/// 単位付きの数量を評価する評価器
+pub struct QuantityInterpreter;
+
+impl QuantityInterpreter {
+    pub fn eval(&mut self, expr: &Ast) -> Result<Quantity, InterpreterError> {
+        match expr.value {
+            AstKind::Num(n) => Ok(Quantity::scalar(n as f64)),
+            AstKind::Quantity { n, unit } => Ok(Quantity::new(n as f64, unit)),
+            AstKind::Unit(unit) => Ok(Quantity::new(1.0, unit)),
+            AstKind::Imag(_) => Err(InterpreterError::new(
+                InterpreterErrorKind::ImaginaryNumber,
+                expr.loc.clone(),
+            )),
+            AstKind::UniOp { ref op, ref e } => {
+                let mut e = self.eval(e)?;
+                match op.value {
+                    UniOpKind::Plus => {}
+                    UniOpKind::Minus => e.value = -e.value,
+                    UniOpKind::BitNot => {
+                        return Err(InterpreterError::new(
+                            InterpreterErrorKind::NonIntegerBitwise,
+                            op.loc.clone(),
+                        ))
+                    }
+                }
+                Ok(e)
+            }
+            AstKind::BinOp {
+                ref op,
+                ref l,
+                ref r,
+            } => {
+                let l = self.eval(l)?;
+                let r = self.eval(r)?;
+                let mismatch = || {
+                    InterpreterError::new(
+                        InterpreterErrorKind::DimensionMismatch {
+                            left: l.unit_string(),
+                            right: r.unit_string(),
+                        },
+                        op.loc.clone(),
+                    )
+                };
+                match op.value {
+                    BinOpKind::Add => l.add(&r, 1.0).ok_or_else(mismatch),
+                    BinOpKind::Sub => l.add(&r, -1.0).ok_or_else(mismatch),
+                    BinOpKind::Multi => Ok(l.mul(&r, l.value * r.value, 1)),
+                    BinOpKind::Div => {
+                        if r.value == 0.0 {
+                            Err(InterpreterError::new(
+                                InterpreterErrorKind::DivisionByZero,
+                                expr.loc.clone(),
+                            ))
+                        } else {
+                            Ok(l.mul(&r, l.value / r.value, -1))
+                        }
+                    }
+                    BinOpKind::BitAnd
+                    | BinOpKind::BitOr
+                    | BinOpKind::BitXor
+                    | BinOpKind::Shl
+                    | BinOpKind::Shr => Err(InterpreterError::new(
+                        InterpreterErrorKind::NonIntegerBitwise,
+                        op.loc.clone(),
+                    )),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ch09::Loc;
+
+    fn eval(s: &str) -> Result<Quantity, InterpreterError> {
+        QuantityInterpreter.eval(&s.parse::<Ast>().unwrap())
+    }
+
+    #[test]
+    fn eval_velocity() {
+        assert_eq!(eval("3 m / 2 s").unwrap().to_string(), "1.5 m/s");
+    }
+
+    #[test]
+    fn eval_converts_compatible_units() {
+        assert_eq!(eval("10 km + 200 m").unwrap().to_string(), "10.2 km");
+        assert_eq!(eval("1 h - 30 min").unwrap().to_string(), "0.5 h");
+        assert_eq!(eval("2 km * 500 m").unwrap().to_string(), "1 km^2");
+    }
+
+    #[test]
+    fn eval_bare_units() {
+        assert_eq!(eval("GiB / s * h").unwrap().to_string(), "3600 GiB");
+        assert_eq!(eval("10 MB / s").unwrap().to_string(), "10 MB/s");
+        assert_eq!(eval("1 / s").unwrap().to_string(), "1 1/s");
+        assert_eq!(eval("6 m / 3 m").unwrap().to_string(), "2");
+    }
+
+    #[test]
+    fn eval_dimension_mismatch() {
+        assert_eq!(
+            eval("1 m + 2 s"),
+            Err(InterpreterError::new(
+                InterpreterErrorKind::DimensionMismatch {
+                    left: "m".to_string(),
+                    right: "s".to_string()
+                },
+                Loc(4, 5)
+            ))
+        );
+        assert!(eval("3 m / s - 1 m").is_err());
+        assert!(eval("3 m - 1").is_err());
+    }
+
+    #[test]
+    fn unit_symbols() {
+        for (unit, symbol, _, _) in UNITS {
+            assert_eq!(Unit::from_symbol(symbol), Some(*unit));
+            assert_eq!(unit.to_string(), *symbol);
+        }
+        assert_eq!(Unit::from_symbol("parsec"), None);
+    }
+}
@@ -0,0 +1,150 @@
+//! 構文木の可視化
+//!
+//! [`Ast`] を Graphviz の DOT 形式や、端末向けの罫線を使った木構造に変換する。
+//! 演算子の優先順位や結合性を確認するときに使う。
+
+use super::{Ast, AstKind};
+use std::fmt::Write;
+
+/// 構文木を Graphviz の DOT 形式に変換する
+///
+/// 各ノードには演算子 (または数値) と、そのノードが表す入力上の区間が表示される。
+///
+/// # Examples
+///
+/// ```
+/// use bicycle_book::ch09::visualize::to_dot;
+/// use bicycle_book::ch09::Ast;
+///
+/// let ast = "1 + 2".parse::<Ast>().unwrap();
+/// let dot = to_dot(&ast);
+///
+/// assert!(dot.starts_with("digraph ast {"));
+/// assert!(dot.contains(r#"node0 [label="+\n0-5"];"#));
+/// ```
+pub fn to_dot(ast: &Ast) -> String {
+    let mut buf = String::new();
+    let mut id = 0;
+    buf.push_str("digraph ast {\n");
+    buf.push_str("    node [shape=box, fontname=\"monospace\"];\n");
+    write_dot_node(ast, &mut id, &mut buf);
+    buf.push_str("}\n");
+    buf
+}
+
+fn write_dot_node(ast: &Ast, id: &mut usize, buf: &mut String) -> usize {
+    let me = *id;
+    *id += 1;
+    writeln!(
+        buf,
+        "    node{} [label=\"{}\\n{}\"];",
+        me,
+        label(ast),
+        ast.loc
+    )
+    .unwrap();
+    for child in children(ast) {
+        let child_id = write_dot_node(child, id, buf);
+        writeln!(buf, "    node{} -> node{};", me, child_id).unwrap();
+    }
+    me
+}
+
+/// 構文木を罫線文字を使ったテキストの木構造に変換する
+///
+/// # Examples
+///
+/// ```
+/// use bicycle_book::ch09::visualize::to_tree;
+/// use bicycle_book::ch09::Ast;
+///
+/// let ast = "1 + 2 * 3".parse::<Ast>().unwrap();
+/// assert_eq!(
+///     to_tree(&ast),
+///     "+ [0-9]\n\
+///      ├── 1 [0-1]\n\
+///      └── * [4-9]\n    \
+///          ├── 2 [4-5]\n    \
+///          └── 3 [8-9]\n"
+/// );
+/// ```
+pub fn to_tree(ast: &Ast) -> String {
+    let mut buf = String::new();
+    writeln!(buf, "{} [{}]", label(ast), ast.loc).unwrap();
+    write_tree_children(ast, "", &mut buf);
+    buf
+}
+
+fn write_tree_children(ast: &Ast, prefix: &str, buf: &mut String) {
+    let children = children(ast);
+    let last = children.len().saturating_sub(1);
+    for (i, child) in children.into_iter().enumerate() {
+        let (branch, indent) = if i == last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        writeln!(buf, "{}{}{} [{}]", prefix, branch, label(child), child.loc).unwrap();
+        write_tree_children(child, &format!("{}{}", prefix, indent), buf);
+    }
+}
+
+fn label(ast: &Ast) -> String {
+    match ast.value {
+        AstKind::Num(n) => n.to_string(),
+        AstKind::Imag(n) => format!("{}i", n),
+        AstKind::Quantity { n, unit } => format!("{} {}", n, unit),
+        AstKind::Unit(unit) => unit.to_string(),
+        AstKind::UniOp { ref op, .. } => op.value.to_string(),
+        AstKind::BinOp { ref op, .. } => op.value.to_string(),
+    }
+}
+
+fn children(ast: &Ast) -> Vec<&Ast> {
+    match ast.value {
+        AstKind::Num(_) | AstKind::Imag(_) | AstKind::Quantity { .. } | AstKind::Unit(_) => {
+            vec![]
+        }
+        AstKind::UniOp { ref e, .. } => vec![e],
+        AstKind::BinOp { ref l, ref r, .. } => vec![l, r],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dot_has_a_node_per_ast_node() {
+        let ast = "1 + 2 * 3 - - 10".parse::<Ast>().unwrap();
+        let dot = to_dot(&ast);
+
+        assert_eq!(dot.matches("[label=").count(), 8);
+        assert_eq!(dot.matches(" -> ").count(), 7);
+        assert!(dot.contains(r#"node0 [label="-\n0-16"];"#));
+        assert!(dot.contains(r#"node6 [label="-\n12-16"];"#));
+        assert!(dot.ends_with("}\n"));
+    }
+
+    #[test]
+    fn tree_shows_precedence() {
+        let ast = "1 + 2 * 3 - - 10".parse::<Ast>().unwrap();
+        let expected = "\
+- [0-16]
+├── + [0-9]
+│   ├── 1 [0-1]
+│   └── * [4-9]
+│       ├── 2 [4-5]
+│       └── 3 [8-9]
+└── - [12-16]
+    └── 10 [14-16]
+";
+        assert_eq!(to_tree(&ast), expected);
+    }
+
+    #[test]
+    fn tree_of_single_number() {
+        let ast = "42".parse::<Ast>().unwrap();
+        assert_eq!(to_tree(&ast), "42 [0-2]\n");
+    }
+}
@@ -0,0 +1,161 @@
+#ifndef BICYCLE_H
+#define BICYCLE_H
+
+/* Warning, this file is autogenerated by cbindgen. Don't modify this manually. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * 評価に成功した
+ */
+#define CALC_OK 0
+
+/**
+ * 字句解析に失敗した
+ */
+#define CALC_LEX_ERROR 1
+
+/**
+ * 構文解析に失敗した
+ */
+#define CALC_PARSE_ERROR 2
+
+/**
+ * 評価に失敗した
+ */
+#define CALC_EVAL_ERROR 3
+
+/**
+ * 引数が NULL か、入力が UTF-8 でない
+ */
+#define CALC_INVALID_ARGUMENT 4
+
+/**
+ * エラーメッセージのバッファの大きさ (終端の NUL を含む)
+ */
+#define CALC_MESSAGE_LEN 256
+
+/**
+ * 文字ごとに数える
+ */
+#define WC_CHAR 0
+
+/**
+ * 単語ごとに数える
+ */
+#define WC_WORD 1
+
+/**
+ * 行ごとに数える
+ */
+#define WC_LINE 2
+
+/**
+ * 頻度表。[`wc_count`] で作り、[`wc_table_free`] で解放する
+ */
+typedef struct wc_table wc_table;
+
+/**
+ * 評価に失敗したときの詳細
+ */
+typedef struct calc_error {
+  /**
+   * エラーが起きた区間の開始位置 (入力の先頭からのバイト数)
+   */
+  size_t start;
+  /**
+   * エラーが起きた区間の終了位置 (この位置は含まない)
+   */
+  size_t end;
+  /**
+   * NUL で終端されたエラーメッセージ
+   */
+  char message[CALC_MESSAGE_LEN];
+} calc_error;
+
+/**
+ * 頻度表の 1 項目
+ */
+typedef struct wc_entry {
+  /**
+   * NUL で終端された文字列。頻度表を解放するまで有効
+   */
+  const char *word;
+  /**
+   * word の長さ (終端の NUL を含まないバイト数)
+   */
+  size_t len;
+  /**
+   * 出現回数
+   */
+  size_t count;
+} wc_entry;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * NUL 終端された文字列 input を整数の式として評価する
+ *
+ * 成功すると結果を result に書き込んで `CALC_OK` を返す。
+ * 失敗するとエラーの種類を返し、error が NULL でなければ、そこにエラーの区間とメッセージを書き込む。
+ *
+ * # Safety
+ *
+ * input は NUL 終端された文字列を、result と error は書き込み可能な領域を指していなければならない。
+ * error は NULL でもよい。
+ */
+int calc_eval(const char *input, int64_t *result, struct calc_error *error);
+
+/**
+ * 長さ len のバッファ buf に含まれる UTF-8 文字列の頻度を数える
+ *
+ * 頻度表は出現回数の多い順に、同じ回数なら文字列の辞書順に並ぶ。
+ * buf が UTF-8 でないか、option が不正な場合は NULL を返す。
+ *
+ * # Safety
+ *
+ * buf は len バイトの読み込み可能な領域を指していなければならない。
+ */
+struct wc_table *wc_count(const uint8_t *buf, size_t len, uint32_t option);
+
+/**
+ * 頻度表の項目数を返す
+ *
+ * # Safety
+ *
+ * table は [`wc_count`] が返した解放前の頻度表か、NULL でなければならない。
+ */
+size_t wc_table_len(const struct wc_table *table);
+
+/**
+ * 頻度表の index 番目の項目を entry に書き込む
+ *
+ * 成功すれば 1 を、index が範囲外なら 0 を返す。
+ *
+ * # Safety
+ *
+ * table は [`wc_count`] が返した解放前の頻度表か NULL で、
+ * entry は書き込み可能な領域を指していなければならない。
+ */
+int wc_table_get(const struct wc_table *table, size_t index, struct wc_entry *entry);
+
+/**
+ * 頻度表を解放する。NULL を渡した場合は何もしない
+ *
+ * # Safety
+ *
+ * table は [`wc_count`] が返した解放前の頻度表か、NULL でなければならない。
+ */
+void wc_table_free(struct wc_table *table);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif /* BICYCLE_H */
@@ -0,0 +1,179 @@
+//! ch09 の式の評価器
+
+use bicycle_book::ch09::{Ast, Interpreter};
+use std::error::Error;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int};
+
+/// 評価に成功した
+pub const CALC_OK: c_int = 0;
+/// 字句解析に失敗した
+pub const CALC_LEX_ERROR: c_int = 1;
+/// 構文解析に失敗した
+pub const CALC_PARSE_ERROR: c_int = 2;
+/// 評価に失敗した
+pub const CALC_EVAL_ERROR: c_int = 3;
+/// 引数が NULL か、入力が UTF-8 でない
+pub const CALC_INVALID_ARGUMENT: c_int = 4;
+
+/// エラーメッセージのバッファの大きさ (終端の NUL を含む)
+pub const CALC_MESSAGE_LEN: usize = 256;
+
+/// 評価に失敗したときの詳細
+#[repr(C)]
+pub struct CalcError {
+    /// エラーが起きた区間の開始位置 (入力の先頭からのバイト数)
+    pub start: usize,
+    /// エラーが起きた区間の終了位置 (この位置は含まない)
+    pub end: usize,
+    /// NUL で終端されたエラーメッセージ
+    pub message: [c_char; CALC_MESSAGE_LEN],
+}
+
+impl CalcError {
+    fn set(&mut self, start: usize, end: usize, message: &str) {
+        self.start = start;
+        self.end = end;
+
+        // 文字の途中で切らないように、収まる長さまで縮める
+        let mut len = message.len().min(CALC_MESSAGE_LEN - 1);
+        while !message.is_char_boundary(len) {
+            len -= 1;
+        }
+        for (dst, src) in self.message.iter_mut().zip(&message.as_bytes()[..len]) {
+            *dst = *src as c_char;
+        }
+        self.message[len] = 0;
+    }
+}
+
+/// NUL 終端された文字列 input を整数の式として評価する
+///
+/// 成功すると結果を result に書き込んで `CALC_OK` を返す。
+/// 失敗するとエラーの種類を返し、error が NULL でなければ、そこにエラーの区間とメッセージを書き込む。
+///
+/// # Safety
+///
+/// input は NUL 終端された文字列を、result と error は書き込み可能な領域を指していなければならない。
+/// error は NULL でもよい。
+#[no_mangle]
+pub unsafe extern "C" fn calc_eval(
+    input: *const c_char,
+    result: *mut i64,
+    error: *mut CalcError,
+) -> c_int {
+    let error = error.as_mut();
+    let fail = |status, start, end, message: &str| {
+        if let Some(error) = error {
+            error.set(start, end, message);
+        }
+        status
+    };
+
+    if input.is_null() || result.is_null() {
+        return fail(CALC_INVALID_ARGUMENT, 0, 0, "null pointer argument");
+    }
+    let input = match CStr::from_ptr(input).to_str() {
+        Ok(input) => input,
+        Err(e) => {
+            let pos = e.valid_up_to();
+            return fail(CALC_INVALID_ARGUMENT, pos, pos + 1, "input is not UTF-8");
+        }
+    };
+
+    let ast = match input.parse::<Ast>() {
+        Ok(ast) => ast,
+        Err(e) => {
+            use bicycle_book::ch09::Error::*;
+            let loc = e.loc(input);
+            let status = match e {
+                Lexer(_) => CALC_LEX_ERROR,
+                Parser(_) => CALC_PARSE_ERROR,
+            };
+            let message = e.source().map_or_else(|| e.to_string(), |s| s.to_string());
+            return fail(status, loc.start(), loc.end(), &message);
+        }
+    };
+
+    match Interpreter.eval(&ast) {
+        Ok(n) => {
+            *result = n;
+            CALC_OK
+        }
+        Err(e) => fail(
+            CALC_EVAL_ERROR,
+            e.loc().start(),
+            e.loc().end(),
+            &e.to_string(),
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    fn eval(input: &str) -> (c_int, i64, CalcError) {
+        let input = CString::new(input).unwrap();
+        let mut result = 0;
+        let mut error = CalcError {
+            start: 0,
+            end: 0,
+            message: [0; CALC_MESSAGE_LEN],
+        };
+        let status = unsafe { calc_eval(input.as_ptr(), &mut result, &mut error) };
+        (status, result, error)
+    }
+
+    fn message(error: &CalcError) -> String {
+        unsafe { CStr::from_ptr(error.message.as_ptr()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn eval_ok() {
+        let (status, result, _) = eval("1 + 2 * 3");
+        assert_eq!(status, CALC_OK);
+        assert_eq!(result, 7);
+    }
+
+    #[test]
+    fn eval_errors() {
+        let (status, _, error) = eval("1 + 2 * $");
+        assert_eq!(status, CALC_LEX_ERROR);
+        assert_eq!((error.start, error.end), (8, 9));
+
+        let (status, _, error) = eval("1 + (2 - 3");
+        assert_eq!(status, CALC_PARSE_ERROR);
+        assert_eq!((error.start, error.end), (4, 5));
+
+        let (status, _, error) = eval("1 +");
+        assert_eq!(status, CALC_PARSE_ERROR);
+        assert_eq!((error.start, error.end), (3, 3));
+
+        let (status, _, error) = eval("10 / (2 - 2)");
+        assert_eq!(status, CALC_EVAL_ERROR);
+        assert_eq!((error.start, error.end), (0, 11));
+        assert!(!message(&error).is_empty());
+    }
+
+    #[test]
+    fn null_arguments() {
+        let status = unsafe { calc_eval(std::ptr::null(), &mut 0, std::ptr::null_mut()) };
+        assert_eq!(status, CALC_INVALID_ARGUMENT);
+    }
+
+    #[test]
+    fn long_message_is_truncated_at_char_boundary() {
+        let mut error = CalcError {
+            start: 0,
+            end: 0,
+            message: [1; CALC_MESSAGE_LEN],
+        };
+        error.set(0, 0, &"あ".repeat(100));
+        let message = message(&error);
+        assert_eq!(message, "あ".repeat(85));
+    }
+}
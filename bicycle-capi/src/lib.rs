@@ -0,0 +1,11 @@
+//! bicycle-book の計算機 (ch09) と単語頻度カウンタ (ch10) を C から呼び出すための API
+//!
+//! ヘッダファイルは `include/bicycle.h` にある。
+//! cbindgen で生成しているので、API を変更したら次のコマンドで作り直すこと。
+//!
+//! ```sh
+//! cbindgen --config cbindgen.toml --output include/bicycle.h
+//! ```
+
+pub mod calc;
+pub mod wordcount;
@@ -0,0 +1,179 @@
+//! ch10 の単語頻度カウンタ
+
+use bicycle_book::ch10::{count, CountOption};
+use std::os::raw::{c_char, c_int};
+use std::slice;
+
+/// 文字ごとに数える
+pub const WC_CHAR: u32 = 0;
+/// 単語ごとに数える
+pub const WC_WORD: u32 = 1;
+/// 行ごとに数える
+pub const WC_LINE: u32 = 2;
+
+/// 頻度表。[`wc_count`] で作り、[`wc_table_free`] で解放する
+pub struct WcTable {
+    /// NUL 終端した文字列と出現回数の組
+    entries: Vec<(Vec<u8>, usize)>,
+}
+
+/// 頻度表の 1 項目
+#[repr(C)]
+pub struct WcEntry {
+    /// NUL で終端された文字列。頻度表を解放するまで有効
+    pub word: *const c_char,
+    /// word の長さ (終端の NUL を含まないバイト数)
+    pub len: usize,
+    /// 出現回数
+    pub count: usize,
+}
+
+/// 長さ len のバッファ buf に含まれる UTF-8 文字列の頻度を数える
+///
+/// 頻度表は出現回数の多い順に、同じ回数なら文字列の辞書順に並ぶ。
+/// buf が UTF-8 でないか、option が不正な場合は NULL を返す。
+///
+/// # Safety
+///
+/// buf は len バイトの読み込み可能な領域を指していなければならない。
+#[no_mangle]
+pub unsafe extern "C" fn wc_count(buf: *const u8, len: usize, option: u32) -> *mut WcTable {
+    let option = match option {
+        WC_CHAR => CountOption::Char,
+        WC_WORD => CountOption::Word,
+        WC_LINE => CountOption::Line,
+        _ => return std::ptr::null_mut(),
+    };
+    let input = if len == 0 {
+        &[][..]
+    } else if buf.is_null() {
+        return std::ptr::null_mut();
+    } else {
+        slice::from_raw_parts(buf, len)
+    };
+    // count は UTF-8 でない入力でパニックするので、先に確かめておく
+    if std::str::from_utf8(input).is_err() {
+        return std::ptr::null_mut();
+    }
+
+    let mut entries = count(input, option)
+        .into_iter()
+        .map(|(word, n)| {
+            let mut word = word.into_bytes();
+            word.push(0);
+            (word, n)
+        })
+        .collect::<Vec<_>>();
+    entries.sort_by(|(a, m), (b, n)| n.cmp(m).then_with(|| a.cmp(b)));
+
+    Box::into_raw(Box::new(WcTable { entries }))
+}
+
+/// 頻度表の項目数を返す
+///
+/// # Safety
+///
+/// table は [`wc_count`] が返した解放前の頻度表か、NULL でなければならない。
+#[no_mangle]
+pub unsafe extern "C" fn wc_table_len(table: *const WcTable) -> usize {
+    table.as_ref().map_or(0, |table| table.entries.len())
+}
+
+/// 頻度表の index 番目の項目を entry に書き込む
+///
+/// 成功すれば 1 を、index が範囲外なら 0 を返す。
+///
+/// # Safety
+///
+/// table は [`wc_count`] が返した解放前の頻度表か NULL で、
+/// entry は書き込み可能な領域を指していなければならない。
+#[no_mangle]
+pub unsafe extern "C" fn wc_table_get(
+    table: *const WcTable,
+    index: usize,
+    entry: *mut WcEntry,
+) -> c_int {
+    let (table, entry) = match (table.as_ref(), entry.as_mut()) {
+        (Some(table), Some(entry)) => (table, entry),
+        _ => return 0,
+    };
+    match table.entries.get(index) {
+        Some((word, n)) => {
+            entry.word = word.as_ptr() as *const c_char;
+            entry.len = word.len() - 1;
+            entry.count = *n;
+            1
+        }
+        None => 0,
+    }
+}
+
+/// 頻度表を解放する。NULL を渡した場合は何もしない
+///
+/// # Safety
+///
+/// table は [`wc_count`] が返した解放前の頻度表か、NULL でなければならない。
+#[no_mangle]
+pub unsafe extern "C" fn wc_table_free(table: *mut WcTable) {
+    if !table.is_null() {
+        drop(Box::from_raw(table));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CStr;
+
+    fn entries(input: &[u8], option: u32) -> Vec<(String, usize)> {
+        unsafe {
+            let table = wc_count(input.as_ptr(), input.len(), option);
+            assert!(!table.is_null());
+            let mut ret = Vec::new();
+            let mut entry = WcEntry {
+                word: std::ptr::null(),
+                len: 0,
+                count: 0,
+            };
+            for i in 0..wc_table_len(table) {
+                assert_eq!(wc_table_get(table, i, &mut entry), 1);
+                let word = CStr::from_ptr(entry.word).to_str().unwrap().to_string();
+                assert_eq!(word.len(), entry.len);
+                ret.push((word, entry.count));
+            }
+            assert_eq!(wc_table_get(table, wc_table_len(table), &mut entry), 0);
+            wc_table_free(table);
+            ret
+        }
+    }
+
+    #[test]
+    fn count_words_sorted_by_frequency() {
+        assert_eq!(
+            entries(b"cc bb aa bb cc cc", WC_WORD),
+            vec![
+                ("cc".to_string(), 3),
+                ("bb".to_string(), 2),
+                ("aa".to_string(), 1)
+            ]
+        );
+    }
+
+    #[test]
+    fn count_lines() {
+        assert_eq!(
+            entries("あ\nい\nあ\n".as_bytes(), WC_LINE),
+            vec![("あ".to_string(), 2), ("い".to_string(), 1)]
+        );
+    }
+
+    #[test]
+    fn invalid_input() {
+        unsafe {
+            assert!(wc_count(b"\xff".as_ptr(), 1, WC_CHAR).is_null());
+            assert!(wc_count(b"a".as_ptr(), 1, 3).is_null());
+            assert_eq!(wc_table_len(std::ptr::null()), 0);
+            wc_table_free(std::ptr::null_mut());
+        }
+    }
+}
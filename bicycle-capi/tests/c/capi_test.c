@@ -0,0 +1,50 @@
+#include <assert.h>
+#include <stdio.h>
+#include <string.h>
+
+#include "bicycle.h"
+
+static void test_calc(void) {
+  int64_t result = 0;
+  calc_error error;
+
+  assert(calc_eval("1 + 2 * 3", &result, &error) == CALC_OK);
+  assert(result == 7);
+
+  assert(calc_eval("1 + 2 * $", &result, &error) == CALC_LEX_ERROR);
+  assert(error.start == 8 && error.end == 9);
+
+  assert(calc_eval("1 + (2 - 3", &result, &error) == CALC_PARSE_ERROR);
+  assert(error.start == 4 && error.end == 5);
+
+  assert(calc_eval("10 / (2 - 2)", &result, &error) == CALC_EVAL_ERROR);
+  assert(strlen(error.message) > 0);
+
+  assert(calc_eval("1 +", &result, NULL) == CALC_PARSE_ERROR);
+  assert(calc_eval(NULL, &result, NULL) == CALC_INVALID_ARGUMENT);
+}
+
+static void test_wordcount(void) {
+  const char *text = "cc bb aa bb cc cc";
+  wc_table *table = wc_count((const uint8_t *)text, strlen(text), WC_WORD);
+  wc_entry entry;
+
+  assert(table != NULL);
+  assert(wc_table_len(table) == 3);
+  assert(wc_table_get(table, 0, &entry));
+  assert(strcmp(entry.word, "cc") == 0 && entry.len == 2 && entry.count == 3);
+  assert(wc_table_get(table, 2, &entry));
+  assert(strcmp(entry.word, "aa") == 0 && entry.count == 1);
+  assert(!wc_table_get(table, 3, &entry));
+  wc_table_free(table);
+
+  assert(wc_count((const uint8_t *)"\xff", 1, WC_CHAR) == NULL);
+  assert(wc_count((const uint8_t *)text, strlen(text), 42) == NULL);
+}
+
+int main(void) {
+  test_calc();
+  test_wordcount();
+  puts("ok");
+  return 0;
+}
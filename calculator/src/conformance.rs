@@ -0,0 +1,173 @@
+//! 全ての電卓で同じ計算を確かめるための表
+//!
+//! 各ケースは中置記法で書く。逆ポーランド記法の電卓には
+//! [`bicycle_book::ch09::RpnCompiler`] で変換した式を渡す。
+//! 構文の誤りのように変換できないケースは、逆ポーランド記法の式も併せて書く。
+
+use crate::{Arithmetic, Calculator, ErrorKind, Notation};
+use bicycle_book::ch09::{Ast, RpnCompiler};
+use std::fmt;
+
+/// 期待する評価結果
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Expected {
+    Value(f64),
+    Error(ErrorKind),
+}
+
+/// 1 つのテストケース
+#[derive(Debug, Clone, Copy)]
+pub struct Case {
+    pub infix: &'static str,
+    /// `None` なら infix から変換する
+    pub postfix: Option<&'static str>,
+    /// `Some` なら、その種類の数で計算する電卓だけで確かめる
+    pub arithmetic: Option<Arithmetic>,
+    pub expected: Expected,
+}
+
+const fn value(infix: &'static str, n: f64) -> Case {
+    Case {
+        infix,
+        postfix: None,
+        arithmetic: None,
+        expected: Expected::Value(n),
+    }
+}
+
+const fn syntax_error(infix: &'static str, postfix: &'static str) -> Case {
+    Case {
+        infix,
+        postfix: Some(postfix),
+        arithmetic: None,
+        expected: Expected::Error(ErrorKind::Syntax),
+    }
+}
+
+const fn error(infix: &'static str, kind: ErrorKind) -> Case {
+    Case {
+        infix,
+        postfix: None,
+        arithmetic: None,
+        expected: Expected::Error(kind),
+    }
+}
+
+const fn only(arithmetic: Arithmetic, case: Case) -> Case {
+    Case {
+        arithmetic: Some(arithmetic),
+        ..case
+    }
+}
+
+/// 全ての電卓が満たすべきケース
+pub const CASES: &[Case] = &[
+    value("0", 0.0),
+    value("42", 42.0),
+    value("1 + 2", 3.0),
+    value("10 - 4 - 3", 3.0),
+    value("2 * 3 + 4", 10.0),
+    value("2 + 3 * 4", 14.0),
+    value("(2 + 3) * 4", 20.0),
+    value("100 / 4 / 5", 5.0),
+    value("-5 + 2", -3.0),
+    value("-(2 + 3) * 2", -10.0),
+    value("1 - (2 - (3 - 4))", -2.0),
+    value("((((7))))", 7.0),
+    value("123456 * 1000", 123_456_000.0),
+    only(Arithmetic::Integer, value("7 / 2", 3.0)),
+    only(Arithmetic::Integer, value("-7 / 2", -3.0)),
+    only(Arithmetic::Float, value("7 / 2", 3.5)),
+    error("1 / (2 - 2)", ErrorKind::DivisionByZero),
+    error("1 / 0", ErrorKind::DivisionByZero),
+    // 2^64 は i32 でも i64 でもあふれる
+    only(
+        Arithmetic::Integer,
+        error("65536 * 65536 * 65536 * 65536", ErrorKind::Overflow),
+    ),
+    only(
+        Arithmetic::Integer,
+        error(
+            "(0 - 2) * 65536 * 65536 * 65536 * 32768",
+            ErrorKind::Overflow,
+        ),
+    ),
+    only(
+        Arithmetic::Float,
+        value(
+            "65536 * 65536 * 65536 * 65536",
+            18_446_744_073_709_551_616.0,
+        ),
+    ),
+    syntax_error("", ""),
+    syntax_error("1 +", "1 +"),
+    syntax_error("1 2", "1 2"),
+    syntax_error("1 $ 2", "1 2 $"),
+];
+
+impl Case {
+    /// calc に渡す式を返す
+    pub fn source(&self, notation: Notation) -> String {
+        match (notation, self.postfix) {
+            (Notation::Infix, _) => self.infix.to_string(),
+            (Notation::Postfix, Some(postfix)) => postfix.to_string(),
+            (Notation::Postfix, None) => {
+                let ast = self
+                    .infix
+                    .parse::<Ast>()
+                    .unwrap_or_else(|e| panic!("cannot translate {:?}: {}", self.infix, e));
+                RpnCompiler.compile(&ast)
+            }
+        }
+    }
+
+    /// arithmetic で計算する電卓で確かめる対象のケースかどうか
+    pub fn applies_to(&self, arithmetic: Arithmetic) -> bool {
+        self.arithmetic.is_none() || self.arithmetic == Some(arithmetic)
+    }
+}
+
+/// 期待と異なる結果になったケース
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub calculator: String,
+    pub source: String,
+    pub expected: Expected,
+    pub actual: Result<f64, ErrorKind>,
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:?} expected {:?}, but got {:?}",
+            self.calculator, self.source, self.expected, self.actual
+        )
+    }
+}
+
+/// cases のうち calc に当てはまるものを全て評価し、失敗したケースを返す
+pub fn run(calc: &mut dyn Calculator, cases: &[Case]) -> Vec<Failure> {
+    let mut failures = Vec::new();
+    let arithmetic = calc.arithmetic();
+
+    for case in cases.iter().filter(|case| case.applies_to(arithmetic)) {
+        let source = case.source(calc.notation());
+        let actual = calc.eval(&source).map_err(|e| e.kind);
+        let ok = match (case.expected, actual) {
+            (Expected::Value(expected), Ok(actual)) => expected == actual,
+            (Expected::Error(expected), Err(actual)) => expected == actual,
+            _ => false,
+        };
+        if !ok {
+            failures.push(Failure {
+                calculator: calc.name().to_string(),
+                source,
+                expected: case.expected,
+                actual,
+            });
+        }
+    }
+
+    failures
+}
@@ -0,0 +1,158 @@
+//! ワークスペース内の電卓への [`Calculator`] の実装
+
+use crate::{Arithmetic, CalcError, Calculator, ErrorKind, Notation};
+use bicycle_book::ch02::rpn::{Forth, RpnError, RpnErrorKind};
+use bicycle_book::ch09::{self, Ast, Interpreter, InterpreterErrorKind};
+use gear_book::ch04::{RpnCalculator, RpnCalculatorError};
+
+/// [`bicycle_book::ch02::rpn`] の評価器で計算する電卓
+///
+/// `rpn` 関数はスタックに値が余っていても一番上の値を返すが、
+/// 他の電卓に合わせて、評価後のスタックに値がちょうど 1 つ残る場合だけを成功とする。
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Ch02Rpn;
+
+impl Calculator for Ch02Rpn {
+    fn name(&self) -> &str {
+        "bicycle_book::ch02::rpn"
+    }
+
+    fn notation(&self) -> Notation {
+        Notation::Postfix
+    }
+
+    fn arithmetic(&self) -> Arithmetic {
+        Arithmetic::Float
+    }
+
+    fn eval(&mut self, source: &str) -> Result<f64, CalcError> {
+        let mut forth = Forth::new();
+        forth.eval(source).map_err(|e| rpn_error(source, e))?;
+
+        // 余った値がどのトークンから来たかは分からないので、入力の末尾を指す
+        let end = source.len()..source.len();
+        match forth.stack() {
+            [x] => Ok(*x),
+            [] => Err(CalcError {
+                kind: ErrorKind::Syntax,
+                span: end,
+                message: RpnErrorKind::StackUnderflow.to_string(),
+            }),
+            stack => Err(CalcError {
+                kind: ErrorKind::Syntax,
+                span: end,
+                message: format!("{} leftover operand(s)", stack.len() - 1),
+            }),
+        }
+    }
+}
+
+fn rpn_error(source: &str, e: RpnError) -> CalcError {
+    // トークンの番号をバイト単位の区間に直す。入力の末尾を指す番号もある
+    let span = source
+        .split_whitespace()
+        .nth(e.index)
+        .map(|token| {
+            let start = token.as_ptr() as usize - source.as_ptr() as usize;
+            start..start + token.len()
+        })
+        .unwrap_or(source.len()..source.len());
+    let kind = match e.kind {
+        RpnErrorKind::TooDeep => ErrorKind::Other,
+        RpnErrorKind::DivisionByZero => ErrorKind::DivisionByZero,
+        _ => ErrorKind::Syntax,
+    };
+    CalcError {
+        kind,
+        span,
+        message: e.kind.to_string(),
+    }
+}
+
+impl From<RpnCalculatorError> for CalcError {
+    fn from(e: RpnCalculatorError) -> Self {
+        let kind = match e {
+            RpnCalculatorError::DivisionByZero { .. } => ErrorKind::DivisionByZero,
+            RpnCalculatorError::Overflow { .. } => ErrorKind::Overflow,
+            _ => ErrorKind::Syntax,
+        };
+        CalcError {
+            kind,
+            span: e.span().clone(),
+            message: e.to_string(),
+        }
+    }
+}
+
+macro_rules! impl_calculator_for_rpn_calculator {
+    ($($t:ty => $arithmetic:expr),*) => {$(
+        impl Calculator for RpnCalculator<$t> {
+            fn name(&self) -> &str {
+                concat!("gear_book::ch04::RpnCalculator<", stringify!($t), ">")
+            }
+
+            fn notation(&self) -> Notation {
+                Notation::Postfix
+            }
+
+            fn arithmetic(&self) -> Arithmetic {
+                $arithmetic
+            }
+
+            fn eval(&mut self, source: &str) -> Result<f64, CalcError> {
+                Ok(RpnCalculator::eval(self, source)? as f64)
+            }
+        }
+    )*};
+}
+
+impl_calculator_for_rpn_calculator!(
+    i32 => Arithmetic::Integer,
+    i64 => Arithmetic::Integer,
+    f64 => Arithmetic::Float
+);
+
+impl Calculator for Interpreter {
+    fn name(&self) -> &str {
+        "bicycle_book::ch09::Interpreter"
+    }
+
+    fn notation(&self) -> Notation {
+        Notation::Infix
+    }
+
+    fn arithmetic(&self) -> Arithmetic {
+        Arithmetic::Integer
+    }
+
+    fn eval(&mut self, source: &str) -> Result<f64, CalcError> {
+        let ast = source.parse::<Ast>().map_err(|e| {
+            let loc = e.loc(source);
+            let message = match &e {
+                ch09::Error::Lexer(e) => e.to_string(),
+                ch09::Error::Parser(e) => e.to_string(),
+            };
+            CalcError {
+                kind: ErrorKind::Syntax,
+                span: loc.start()..loc.end().min(source.len()),
+                message,
+            }
+        })?;
+
+        match Interpreter::eval(self, &ast) {
+            Ok(n) => Ok(n as f64),
+            Err(e) => {
+                let kind = match e.value() {
+                    InterpreterErrorKind::DivisionByZero => ErrorKind::DivisionByZero,
+                    InterpreterErrorKind::Overflow => ErrorKind::Overflow,
+                    _ => ErrorKind::Other,
+                };
+                Err(CalcError {
+                    kind,
+                    span: e.loc().start()..e.loc().end(),
+                    message: e.to_string(),
+                })
+            }
+        }
+    }
+}
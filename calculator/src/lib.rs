@@ -0,0 +1,69 @@
+//! ワークスペース内の電卓を共通の [`Calculator`] トレイトで扱うためのクレート
+//!
+//! * [`bicycle_book::ch02::rpn`] (逆ポーランド記法、浮動小数点数)
+//! * [`gear_book::ch04::RpnCalculator`] (逆ポーランド記法、整数または浮動小数点数)
+//! * [`bicycle_book::ch09::Interpreter`] (中置記法、整数)
+//!
+//! [`conformance`] モジュールの表を使うと、同じ計算をそれぞれの電卓で確かめられる。
+
+pub mod conformance;
+mod impls;
+
+pub use impls::Ch02Rpn;
+
+use std::ops::Range;
+use thiserror::Error;
+
+/// 式の書き方
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Notation {
+    /// `1 + 2 * 3`
+    Infix,
+    /// `1 2 3 * +`
+    Postfix,
+}
+
+/// 計算に使う数の種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arithmetic {
+    /// 整数。除算は 0 に向かって切り捨てる
+    Integer,
+    /// 浮動小数点数
+    Float,
+}
+
+/// エラーの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorKind {
+    /// 字句や構文の誤り。逆ポーランド記法のスタックの過不足も含む
+    Syntax,
+    DivisionByZero,
+    Overflow,
+    /// 上のどれにも当てはまらないエラー
+    Other,
+}
+
+/// 入力上の位置を持つエラー
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{message} at {}..{}", span.start, span.end)]
+pub struct CalcError {
+    pub kind: ErrorKind,
+    /// エラーが起きた入力上のバイト単位の区間
+    pub span: Range<usize>,
+    pub message: String,
+}
+
+/// ソースコードを評価して数値を返す電卓
+pub trait Calculator {
+    /// 結果の表示などに使う名前
+    fn name(&self) -> &str;
+
+    fn notation(&self) -> Notation;
+
+    fn arithmetic(&self) -> Arithmetic;
+
+    /// source を評価する
+    ///
+    /// 整数で計算する電卓も、結果は `f64` に変換して返す。
+    fn eval(&mut self, source: &str) -> Result<f64, CalcError>;
+}
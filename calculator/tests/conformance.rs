@@ -0,0 +1,40 @@
+use bicycle_book::ch09::Interpreter;
+use calculator::conformance::{run, CASES};
+use calculator::{Calculator, Ch02Rpn, ErrorKind};
+use gear_book::ch04::RpnCalculator;
+
+fn calculators() -> Vec<Box<dyn Calculator>> {
+    vec![
+        Box::new(Ch02Rpn),
+        Box::new(RpnCalculator::<i32>::new(false)),
+        Box::new(RpnCalculator::<i64>::new(false)),
+        Box::new(RpnCalculator::<f64>::new(false)),
+        Box::new(Interpreter),
+    ]
+}
+
+#[test]
+fn all_calculators_conform() {
+    let failures = calculators()
+        .iter_mut()
+        .flat_map(|calc| run(calc.as_mut(), CASES))
+        .collect::<Vec<_>>();
+
+    for failure in &failures {
+        eprintln!("{}", failure);
+    }
+    assert!(failures.is_empty());
+}
+
+#[test]
+fn errors_are_located() {
+    for mut calc in calculators() {
+        let source = match calc.notation() {
+            calculator::Notation::Infix => "1 + $",
+            calculator::Notation::Postfix => "1 $ +",
+        };
+        let e = calc.eval(source).unwrap_err();
+        assert_eq!(e.kind, ErrorKind::Syntax, "{}", calc.name());
+        assert_eq!(&source[e.span], "$", "{}", calc.name());
+    }
+}
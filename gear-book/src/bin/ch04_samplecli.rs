@@ -1,13 +1,16 @@
-use anyhow::Result;
+use anyhow::{bail, Error, Result};
 
 use std::{
     fs::File,
-    io::{stdin, BufRead, BufReader},
+    io::{stdin, stdout, BufRead, BufReader, IsTerminal, Write},
     path::PathBuf,
+    str::FromStr,
 };
 
 use clap::Clap;
-use gear_book::ch04::RpnCalculator;
+use gear_book::ch04::session::Session;
+use gear_book::ch04::{Number, RpnCalculator, RpnCalculatorError, Step};
+use num_bigint::BigInt;
 
 #[derive(Clap, Debug)]
 #[clap(
@@ -21,35 +24,375 @@ struct Opts {
     #[clap(short, long)]
     verbose: bool,
 
+    /// Sets the type of numbers used in calculation
+    #[clap(
+        short,
+        long,
+        default_value = "i32",
+        possible_values = &["i32", "i64", "f64", "bigint"]
+    )]
+    number_type: NumberType,
+
+    /// Prints each evaluation step in the given format
+    #[clap(long, possible_values = &["json", "table"])]
+    trace: Option<TraceFormat>,
+
+    /// Waits for Enter before each evaluation step (q + Enter runs to the end)
+    #[clap(long, requires = "FILE")]
+    step: bool,
+
+    /// Stops at the first formula that fails
+    #[clap(long)]
+    fail_fast: bool,
+
+    /// Sets the output format of results and errors
+    #[clap(long, default_value = "text", possible_values = &["text", "json"])]
+    format: OutputFormat,
+
     /// Formulas written in RPN
     #[clap(name = "FILE")]
     formula_file: Option<PathBuf>,
 }
 
+#[derive(Debug, Clone, Copy)]
+enum NumberType {
+    I32,
+    I64,
+    F64,
+    BigInt,
+}
+
+impl FromStr for NumberType {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "i32" => Ok(NumberType::I32),
+            "i64" => Ok(NumberType::I64),
+            "f64" => Ok(NumberType::F64),
+            "bigint" => Ok(NumberType::BigInt),
+            _ => bail!("unknown number type: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum TraceFormat {
+    Json,
+    Table,
+}
+
+impl FromStr for TraceFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "json" => Ok(TraceFormat::Json),
+            "table" => Ok(TraceFormat::Table),
+            _ => bail!("unknown trace format: {}", s),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "text" => Ok(OutputFormat::Text),
+            "json" => Ok(OutputFormat::Json),
+            _ => bail!("unknown output format: {}", s),
+        }
+    }
+}
+
 fn main() -> Result<()> {
     let opts = Opts::parse();
 
-    if let Some(path) = opts.formula_file {
+    let succeeded = if let Some(path) = &opts.formula_file {
         let f = File::open(path)?;
         let reader = BufReader::new(f);
-        run(reader, opts.verbose)
+        run_with(reader, &path.display().to_string(), &opts)?
+    } else if stdin().is_terminal() {
+        // 端末から起動されたら対話モードにする
+        match opts.number_type {
+            NumberType::I32 => interactive::<i32>()?,
+            NumberType::I64 => interactive::<i64>()?,
+            NumberType::F64 => interactive::<f64>()?,
+            NumberType::BigInt => interactive::<BigInt>()?,
+        }
+        true
     } else {
         let stdin = stdin();
         let reader = stdin.lock();
-        run(reader, opts.verbose)
+        run_with(reader, "<stdin>", &opts)?
+    };
+
+    if !succeeded {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
+fn run_with(reader: impl BufRead, name: &str, opts: &Opts) -> Result<bool> {
+    match opts.number_type {
+        NumberType::I32 => run::<i32>(reader, name, opts),
+        NumberType::I64 => run::<i64>(reader, name, opts),
+        NumberType::F64 => run::<f64>(reader, name, opts),
+        NumberType::BigInt => run::<BigInt>(reader, name, opts),
     }
 }
 
-fn run(reader: impl BufRead, verbose: bool) -> Result<()> {
-    let calc = RpnCalculator::new(verbose);
+/// reader から 1 行ずつ式を読み込んで評価する
+///
+/// 空行と `#` で始まる行は読み飛ばす。全ての式の評価に成功したら true を返す。
+fn run<T: Number>(reader: impl BufRead, name: &str, opts: &Opts) -> Result<bool> {
+    let calc = RpnCalculator::<T>::new(opts.verbose);
+    // ステップ実行では、形式の指定がなければ表で表示する
+    let format = match (opts.trace, opts.step) {
+        (None, true) => Some(TraceFormat::Table),
+        (format, _) => format,
+    };
+    let mut stepping = opts.step;
+    let mut succeeded = true;
 
-    for line in reader.lines() {
+    for (lineno, line) in reader.lines().enumerate() {
         let line = line?;
-        match calc.eval(&line) {
-            Ok(answer) => println!("{}", answer),
-            Err(e) => eprintln!("{:?}", e),
+        let lineno = lineno + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let result = match format {
+            Some(format) => trace::<T>(&calc, lineno, &line, format, &mut stepping)?,
+            None => calc.eval(&line),
+        };
+        match (&result, opts.format) {
+            (Ok(answer), OutputFormat::Text) => println!("{}", answer),
+            (Err(e), OutputFormat::Text) => print_error(name, lineno, &line, e),
+            (result, OutputFormat::Json) => println!("{}", result_to_json(lineno, &line, result)),
+        }
+
+        if result.is_err() {
+            succeeded = false;
+            if opts.fail_fast {
+                break;
+            }
         }
     }
 
-    Ok(())
+    Ok(succeeded)
+}
+
+/// スタックとレジスタを保持しながら 1 行ずつ入力を実行する
+fn interactive<T: Number>() -> Result<()> {
+    let mut session = Session::<T>::new();
+    println!("RPN calculator (sto/rcl NAME, undo, redo, save/load PATH, Ctrl-D to quit)");
+
+    let stdin = stdin();
+    let mut input = stdin.lock();
+    loop {
+        print!("> ");
+        stdout().flush()?;
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            println!();
+            return Ok(());
+        }
+        let line = line.trim_end();
+
+        if let Err(e) = session.execute(line) {
+            eprintln!("error: {}", e);
+            if let Some(span) = e.span() {
+                let column = line[..span.start].chars().count();
+                let width = line[span.clone()].chars().count().max(1);
+                eprintln!("  {}{}", " ".repeat(column), "^".repeat(width));
+            }
+        }
+        print_stack(session.stack());
+    }
+}
+
+/// スタックを一番上の値が最後の行になるように、段の番号をつけて表示する
+fn print_stack<T: Number>(stack: &[T]) {
+    for (i, x) in stack.iter().enumerate() {
+        println!("{:>3}: {}", stack.len() - i, x);
+    }
+}
+
+/// 式 line を 1 ステップずつ format で表示しながら評価する
+fn trace<T: Number>(
+    calc: &RpnCalculator<T>,
+    lineno: usize,
+    line: &str,
+    format: TraceFormat,
+    stepping: &mut bool,
+) -> Result<Result<T, RpnCalculatorError>> {
+    let mut trace = calc.trace(line);
+    let mut rows = Vec::new();
+    if let TraceFormat::Table = format {
+        println!("{}", line);
+    }
+    for step in &mut trace {
+        let step = match step {
+            Ok(step) => step,
+            Err(_) => break,
+        };
+        match format {
+            TraceFormat::Json => println!("{}", step_to_json(lineno, &step)),
+            TraceFormat::Table if *stepping => print_table(&[to_row(&step)]),
+            TraceFormat::Table => rows.push(to_row(&step)),
+        }
+        if *stepping {
+            *stepping = wait_for_step()?;
+        }
+    }
+    print_table(&rows);
+
+    Ok(trace.finish())
+}
+
+/// Enter が押されるまで待つ。q が入力されたらステップ実行をやめるので false を返す
+fn wait_for_step() -> Result<bool> {
+    print!("-- step (Enter: next, q: run to the end) --");
+    stdout().flush()?;
+    let mut input = String::new();
+    stdin().read_line(&mut input)?;
+    Ok(input.trim() != "q")
+}
+
+/// s を JSON の文字列リテラルにする
+fn json_string(s: &str) -> String {
+    let mut ret = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => ret.push_str("\\\""),
+            '\\' => ret.push_str("\\\\"),
+            c if c.is_control() => ret.push_str(&format!("\\u{:04x}", c as u32)),
+            c => ret.push(c),
+        }
+    }
+    ret.push('"');
+    ret
+}
+
+/// 1 行の評価結果を JSON オブジェクトとして 1 行で表す
+///
+/// 数値は型によって JSON の数値で表せないことがあるので、文字列にする。
+fn result_to_json<T: Number>(
+    line: usize,
+    input: &str,
+    result: &Result<T, RpnCalculatorError>,
+) -> String {
+    use RpnCalculatorError::*;
+    let value = match result {
+        Ok(answer) => format!(r#""result":{}"#, json_string(&answer.to_string())),
+        Err(e) => {
+            let kind = match e {
+                StackUnderflow { .. } => "stack_underflow",
+                UnknownOperator { .. } => "unknown_operator",
+                DivisionByZero { .. } => "division_by_zero",
+                Overflow { .. } => "overflow",
+                LeftoverOperands { .. } => "leftover_operands",
+            };
+            format!(
+                r#""error":{{"kind":"{}","message":{},"span":[{},{}]}}"#,
+                kind,
+                json_string(&e.to_string()),
+                e.span().start,
+                e.span().end
+            )
+        }
+    };
+    format!(
+        r#"{{"line":{},"input":{},{}}}"#,
+        line,
+        json_string(input),
+        value
+    )
+}
+
+/// 1 ステップを JSON オブジェクトとして 1 行で表す
+///
+/// 数値は [`result_to_json`] と同じく文字列にする。
+fn step_to_json<T: Number>(line: usize, step: &Step<T>) -> String {
+    fn stack<T: Number>(values: &[T]) -> String {
+        let values = values
+            .iter()
+            .map(|x| json_string(&x.to_string()))
+            .collect::<Vec<_>>();
+        format!("[{}]", values.join(","))
+    }
+
+    format!(
+        r#"{{"line":{},"index":{},"token":{},"span":[{},{}],"operation":"{}","before":{},"after":{}}}"#,
+        line,
+        step.index,
+        json_string(step.token),
+        step.span.start,
+        step.span.end,
+        step.operation.name(),
+        stack(&step.before),
+        stack(&step.after)
+    )
+}
+
+/// 表の列見出し
+const HEADER: [&str; 5] = ["#", "token", "operation", "before", "after"];
+
+fn to_row<T: Number>(step: &Step<T>) -> [String; 5] {
+    fn stack<T: Number>(values: &[T]) -> String {
+        let values = values.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        format!("[{}]", values.join(" "))
+    }
+
+    [
+        step.index.to_string(),
+        step.token.to_string(),
+        step.operation.name().to_string(),
+        stack(&step.before),
+        stack(&step.after),
+    ]
+}
+
+/// 見出しと rows を列の幅をそろえて表示する。rows が空なら何も表示しない
+fn print_table(rows: &[[String; 5]]) {
+    if rows.is_empty() {
+        return;
+    }
+
+    let mut widths = HEADER.iter().map(|h| h.len()).collect::<Vec<_>>();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let header = HEADER.iter().map(|h| h.to_string()).collect::<Vec<_>>();
+    for row in std::iter::once(&header[..]).chain(rows.iter().map(|row| &row[..])) {
+        let cells = row
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{:<1$}", cell, width))
+            .collect::<Vec<_>>();
+        println!("{}", cells.join("  ").trim_end());
+    }
+}
+
+/// エラーの位置と内容、原因となったトークンを指す行を表示する
+fn print_error(name: &str, lineno: usize, line: &str, e: &RpnCalculatorError) {
+    let span = e.span();
+    let column = line[..span.start].chars().count();
+    let width = line[span.clone()].chars().count().max(1);
+
+    eprintln!("{}:{}:{}: error: {}", name, lineno, column + 1, e);
+    eprintln!("{}", line);
+    eprintln!("{}{}", " ".repeat(column), "^".repeat(width));
 }
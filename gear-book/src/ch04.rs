@@ -1,55 +1,368 @@
+pub mod session;
+
+use num_bigint::{BigInt, Sign};
+use std::fmt::{Debug, Display};
+use std::marker::PhantomData;
+use std::ops::Range;
+use std::str::FromStr;
 use thiserror::Error;
 
-pub struct RpnCalculator(bool);
+/// 計算機で扱える数値型
+///
+/// 演算は結果が表現できない場合に `None` を返す。
+/// 0 による除算は呼び出し側で [`Number::is_zero`] を使って検出する。
+pub trait Number: FromStr + Display + Debug + Clone + PartialEq {
+    /// 式中のトークンを数値として読む。数値でなければ `None` を返す
+    fn parse_token(token: &str) -> Option<Self> {
+        token.parse().ok()
+    }
+    fn checked_add(&self, rhs: &Self) -> Option<Self>;
+    fn checked_sub(&self, rhs: &Self) -> Option<Self>;
+    fn checked_mul(&self, rhs: &Self) -> Option<Self>;
+    fn checked_div(&self, rhs: &Self) -> Option<Self>;
+    fn checked_rem(&self, rhs: &Self) -> Option<Self>;
+    fn is_zero(&self) -> bool;
+
+    /// 同じ値かどうかを返す。`==` と違い、NaN は NaN と同じ値とみなす
+    fn same_value(&self, other: &Self) -> bool {
+        self == other
+    }
+}
+
+macro_rules! impl_number_for_int {
+    ($($t:ty),*) => {$(
+        impl Number for $t {
+            fn checked_add(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_add(*self, *rhs)
+            }
+            fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_sub(*self, *rhs)
+            }
+            fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_mul(*self, *rhs)
+            }
+            fn checked_div(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_div(*self, *rhs)
+            }
+            fn checked_rem(&self, rhs: &Self) -> Option<Self> {
+                <$t>::checked_rem(*self, *rhs)
+            }
+            fn is_zero(&self) -> bool {
+                *self == 0
+            }
+        }
+    )*};
+}
+
+impl_number_for_int!(i32, i64);
 
-#[derive(Debug, Error)]
+/// 有限の値から無限大や NaN が得られた場合をオーバーフローとみなす
+fn finite(x: f64, y: f64, res: f64) -> Option<f64> {
+    if res.is_finite() || !x.is_finite() || !y.is_finite() {
+        Some(res)
+    } else {
+        None
+    }
+}
+
+impl Number for f64 {
+    /// 整数型と同じく、符号と数字からなるトークン (小数点と指数を含む) だけを数値とする
+    ///
+    /// `str::parse` は `inf` や `nan` も受け付けるが、それらは演算子として扱う。
+    fn parse_token(token: &str) -> Option<Self> {
+        let numeric = token.bytes().any(|b| b.is_ascii_digit())
+            && token
+                .bytes()
+                .all(|b| b.is_ascii_digit() || b"+-.eE".contains(&b));
+        if numeric {
+            token.parse().ok()
+        } else {
+            None
+        }
+    }
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        finite(*self, *rhs, self + rhs)
+    }
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        finite(*self, *rhs, self - rhs)
+    }
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        finite(*self, *rhs, self * rhs)
+    }
+    fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        finite(*self, *rhs, self / rhs)
+    }
+    fn checked_rem(&self, rhs: &Self) -> Option<Self> {
+        finite(*self, *rhs, self % rhs)
+    }
+    fn is_zero(&self) -> bool {
+        *self == 0.0
+    }
+    fn same_value(&self, other: &Self) -> bool {
+        self == other || (self.is_nan() && other.is_nan())
+    }
+}
+
+impl Number for BigInt {
+    fn checked_add(&self, rhs: &Self) -> Option<Self> {
+        Some(self + rhs)
+    }
+    fn checked_sub(&self, rhs: &Self) -> Option<Self> {
+        Some(self - rhs)
+    }
+    fn checked_mul(&self, rhs: &Self) -> Option<Self> {
+        Some(self * rhs)
+    }
+    fn checked_div(&self, rhs: &Self) -> Option<Self> {
+        Some(self / rhs)
+    }
+    fn checked_rem(&self, rhs: &Self) -> Option<Self> {
+        Some(self % rhs)
+    }
+    fn is_zero(&self) -> bool {
+        self.sign() == Sign::NoSign
+    }
+}
+
+/// 数値型 T で計算する逆ポーランド記法の計算機
+pub struct RpnCalculator<T = i32>(bool, PhantomData<T>);
+
+/// 入力中のトークンの位置を表すバイト単位の区間
+pub type Span = Range<usize>;
+
+#[derive(Debug, Error, Clone, PartialEq, Eq)]
 pub enum RpnCalculatorError {
-    #[error("invalid syntax at {0}")]
-    InvalidSyntax(i32),
+    #[error("stack underflow at {}..{}", span.start, span.end)]
+    StackUnderflow { span: Span },
+    #[error("unknown operator at {}..{}", span.start, span.end)]
+    UnknownOperator { span: Span },
+    #[error("division by zero at {}..{}", span.start, span.end)]
+    DivisionByZero { span: Span },
+    #[error("overflow at {}..{}", span.start, span.end)]
+    Overflow { span: Span },
+    #[error("{count} leftover operand(s) at {}..{}", span.start, span.end)]
+    LeftoverOperands { count: usize, span: Span },
+}
+
+impl RpnCalculatorError {
+    /// エラーの原因となったトークンの区間を返す
+    pub fn span(&self) -> &Span {
+        use RpnCalculatorError::*;
+        match self {
+            StackUnderflow { span }
+            | UnknownOperator { span }
+            | DivisionByZero { span }
+            | Overflow { span }
+            | LeftoverOperands { span, .. } => span,
+        }
+    }
 }
 
-impl RpnCalculator {
+impl<T: Number> RpnCalculator<T> {
     pub fn new(verbose: bool) -> Self {
-        Self(verbose)
-    }
-
-    pub fn eval(&self, formula: &str) -> Result<i32, RpnCalculatorError> {
-        let mut tokens = formula.split_whitespace().rev().collect::<Vec<_>>();
-        self.eval_inner(&mut tokens)
-    }
-
-    fn eval_inner(&self, tokens: &mut Vec<&str>) -> Result<i32, RpnCalculatorError> {
-        let mut stack = Vec::new();
-        let mut pos = 0;
-
-        while let Some(token) = tokens.pop() {
-            pos += 1;
-
-            if let Ok(x) = token.parse::<i32>() {
-                stack.push(x);
-            } else {
-                let y = stack.pop().ok_or(RpnCalculatorError::InvalidSyntax(pos))?;
-                let x = stack.pop().ok_or(RpnCalculatorError::InvalidSyntax(pos))?;
-                let res = match token {
-                    "+" => x + y,
-                    "-" => x - y,
-                    "*" => x * y,
-                    "/" => x / y,
-                    "%" => x % y,
-                    _ => return Err(RpnCalculatorError::InvalidSyntax(pos)),
-                };
-                stack.push(res);
-            }
+        Self(verbose, PhantomData)
+    }
 
-            if self.0 {
-                println!("{:?} {:?}", tokens, stack);
+    pub fn eval(&self, formula: &str) -> Result<T, RpnCalculatorError> {
+        let mut trace = self.trace(formula);
+        // スタックの写しは表示する時だけ作る
+        if self.0 {
+            for step in &mut trace {
+                let step = step?;
+                println!("{}: {:?} -> {:?}", step.token, step.before, step.after);
             }
         }
+        trace.finish()
+    }
 
-        if stack.len() == 1 {
-            Ok(stack[0])
-        } else {
-            Err(RpnCalculatorError::InvalidSyntax(-1))
+    /// formula を 1 トークンずつ評価するイテレータを返す
+    ///
+    /// 各トークンの評価結果を [`Step`] として返し、エラーが起きるとそこで終わる。
+    /// 全て評価し終えたら [`Trace::finish`] で計算結果を得る。
+    pub fn trace<'a>(&self, formula: &'a str) -> Trace<'a, T> {
+        let tokens = formula
+            .split_whitespace()
+            .map(|token| {
+                let start = token.as_ptr() as usize - formula.as_ptr() as usize;
+                (token, start..start + token.len())
+            })
+            .rev()
+            .collect::<Vec<_>>();
+        Trace {
+            tokens,
+            stack: Vec::new(),
+            index: 0,
+            len: formula.len(),
+            error: None,
+        }
+    }
+}
+
+/// トークンに対して行った操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Push,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl Operation {
+    /// 操作の名前を返す
+    pub fn name(self) -> &'static str {
+        use Operation::*;
+        match self {
+            Push => "push",
+            Add => "add",
+            Sub => "sub",
+            Mul => "mul",
+            Div => "div",
+            Rem => "rem",
+        }
+    }
+}
+
+/// 1 トークン分の評価の記録
+#[derive(Debug, Clone, PartialEq)]
+pub struct Step<'a, T> {
+    /// 先頭から数えたトークンの番号
+    pub index: usize,
+    pub token: &'a str,
+    pub span: Span,
+    pub operation: Operation,
+    /// 評価前のスタック (底から順)
+    pub before: Vec<T>,
+    /// 評価後のスタック (底から順)
+    pub after: Vec<T>,
+}
+
+/// [`RpnCalculator::trace`] が返すイテレータ
+pub struct Trace<'a, T> {
+    /// 残りのトークン。末尾が次に評価するトークン
+    tokens: Vec<(&'a str, Span)>,
+    /// 値とその値を計算したトークン列の区間
+    stack: Vec<(T, Span)>,
+    index: usize,
+    len: usize,
+    error: Option<RpnCalculatorError>,
+}
+
+impl<'a, T: Number> Trace<'a, T> {
+    /// 残りのトークンを評価して計算結果を返す
+    pub fn finish(mut self) -> Result<T, RpnCalculatorError> {
+        use RpnCalculatorError::*;
+        while let Some((_, token, span)) = self.next_token() {
+            self.step(token, &span).map_err(|e| self.fail(e))?;
+        }
+        if let Some(e) = self.error {
+            return Err(e);
+        }
+
+        let mut stack = self.stack;
+        match stack.len() {
+            0 => Err(StackUnderflow {
+                span: self.len..self.len,
+            }),
+            1 => Ok(stack.pop().unwrap().0),
+            n => Err(LeftoverOperands {
+                count: n - 1,
+                span: stack[0].1.start..stack[n - 2].1.end,
+            }),
+        }
+    }
+
+    /// values を積んだスタックから評価を続ける、トークンを持たない Trace を作る
+    ///
+    /// [`session`] はこれに 1 トークンずつ [`Trace::step`] を呼んで評価する。
+    /// 既にある値は区間を持たないので、空の区間を割り当てる。
+    fn resume(values: Vec<T>) -> Self {
+        Trace {
+            tokens: Vec::new(),
+            stack: values.into_iter().map(|x| (x, 0..0)).collect(),
+            index: 0,
+            len: 0,
+            error: None,
+        }
+    }
+
+    /// スタックの値を底から順に返す
+    fn into_values(self) -> Vec<T> {
+        self.stack.into_iter().map(|(x, _)| x).collect()
+    }
+
+    /// 次に評価するトークンとその番号、区間を取り出す。エラーが起きた後は `None` を返す
+    fn next_token(&mut self) -> Option<(usize, &'a str, Span)> {
+        if self.error.is_some() {
+            return None;
+        }
+        let (token, span) = self.tokens.pop()?;
+        let index = self.index;
+        self.index += 1;
+        Some((index, token, span))
+    }
+
+    /// エラー e を記録し、以降の評価を止める
+    fn fail(&mut self, e: RpnCalculatorError) -> RpnCalculatorError {
+        self.error = Some(e.clone());
+        e
+    }
+
+    fn values(&self) -> Vec<T> {
+        self.stack.iter().map(|(x, _)| x.clone()).collect()
+    }
+
+    /// 数値か演算子のトークンを 1 つ評価してスタックを更新する
+    fn step(&mut self, token: &str, span: &Span) -> Result<Operation, RpnCalculatorError> {
+        use RpnCalculatorError::*;
+
+        if let Some(x) = T::parse_token(token) {
+            self.stack.push((x, span.clone()));
+            return Ok(Operation::Push);
+        }
+
+        let (operation, op): (_, fn(&T, &T) -> Option<T>) = match token {
+            "+" => (Operation::Add, T::checked_add),
+            "-" => (Operation::Sub, T::checked_sub),
+            "*" => (Operation::Mul, T::checked_mul),
+            "/" => (Operation::Div, T::checked_div),
+            "%" => (Operation::Rem, T::checked_rem),
+            _ => return Err(UnknownOperator { span: span.clone() }),
+        };
+        let len = self.stack.len();
+        if len < 2 {
+            return Err(StackUnderflow { span: span.clone() });
+        }
+        let (x, x_span) = &self.stack[len - 2];
+        let (y, _) = &self.stack[len - 1];
+        if y.is_zero() && (operation == Operation::Div || operation == Operation::Rem) {
+            return Err(DivisionByZero { span: span.clone() });
+        }
+        let res = op(x, y).ok_or_else(|| Overflow { span: span.clone() })?;
+        let res_span = x_span.start..span.end;
+        self.stack.truncate(len - 2);
+        self.stack.push((res, res_span));
+        Ok(operation)
+    }
+}
+
+impl<'a, T: Number> Iterator for Trace<'a, T> {
+    type Item = Result<Step<'a, T>, RpnCalculatorError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (index, token, span) = self.next_token()?;
+        let before = self.values();
+        match self.step(token, &span) {
+            Ok(operation) => Some(Ok(Step {
+                index,
+                token,
+                span,
+                operation,
+                before,
+                after: self.values(),
+            })),
+            Err(e) => Some(Err(self.fail(e))),
         }
     }
 }
@@ -64,7 +377,7 @@ mod tests {
     #[case("50", 50)]
     #[case("-50", -50)]
     fn test_one_operand(#[case] input: &str, #[case] expected: i32) {
-        let calc = RpnCalculator::new(false);
+        let calc = RpnCalculator::<i32>::new(false);
         assert_eq!(calc.eval(input).unwrap(), expected);
     }
 
@@ -75,7 +388,7 @@ mod tests {
     #[case("2 3 /", 0)]
     #[case("2 3 %", 2)]
     fn test_two_operand(#[case] input: &str, #[case] expected: i32) {
-        let calc = RpnCalculator::new(false);
+        let calc = RpnCalculator::<i32>::new(false);
         assert_eq!(calc.eval(input).unwrap(), expected);
     }
 
@@ -85,7 +398,130 @@ mod tests {
     #[case("+ 1 1")]
     #[case("1 1 ^")]
     fn test_error(#[case] input: &str) {
-        let calc = RpnCalculator::new(false);
+        let calc = RpnCalculator::<i32>::new(false);
         assert!(calc.eval(input).is_err());
     }
+
+    #[rstest]
+    #[case("", RpnCalculatorError::StackUnderflow { span: 0..0 })]
+    #[case("1 1 1 +", RpnCalculatorError::LeftoverOperands { count: 1, span: 0..1 })]
+    #[case("1 2 3 4 5 +", RpnCalculatorError::LeftoverOperands { count: 3, span: 0..5 })]
+    #[case("+ 1 1", RpnCalculatorError::StackUnderflow { span: 0..1 })]
+    #[case("1 1 ^", RpnCalculatorError::UnknownOperator { span: 4..5 })]
+    #[case("1 0 /", RpnCalculatorError::DivisionByZero { span: 4..5 })]
+    #[case("1  0 %", RpnCalculatorError::DivisionByZero { span: 5..6 })]
+    #[case("2147483647 1 +", RpnCalculatorError::Overflow { span: 13..14 })]
+    #[case("-2147483648 -1 /", RpnCalculatorError::Overflow { span: 15..16 })]
+    fn test_error_kind(#[case] input: &str, #[case] expected: RpnCalculatorError) {
+        let calc = RpnCalculator::<i32>::new(false);
+        assert_eq!(calc.eval(input).unwrap_err(), expected);
+    }
+
+    #[rstest]
+    #[case("1000000 1000000 *", 1_000_000_000_000)]
+    #[case("9223372036854775807 1 -", i64::MAX - 1)]
+    fn test_i64(#[case] input: &str, #[case] expected: i64) {
+        let calc = RpnCalculator::<i64>::new(false);
+        assert_eq!(calc.eval(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("1 4 /", 0.25)]
+    #[case("1.5 2 *", 3.0)]
+    #[case("7.5 2 %", 1.5)]
+    #[case("-1.5e2 +.5 *", -75.0)]
+    fn test_f64(#[case] input: &str, #[case] expected: f64) {
+        let calc = RpnCalculator::<f64>::new(false);
+        assert_eq!(calc.eval(input).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("1 0 /", RpnCalculatorError::DivisionByZero { span: 4..5 })]
+    #[case("1e308 10 *", RpnCalculatorError::Overflow { span: 9..10 })]
+    #[case("inf", RpnCalculatorError::UnknownOperator { span: 0..3 })]
+    #[case("1 nan +", RpnCalculatorError::UnknownOperator { span: 2..5 })]
+    #[case("-Infinity", RpnCalculatorError::UnknownOperator { span: 0..9 })]
+    fn test_f64_error(#[case] input: &str, #[case] expected: RpnCalculatorError) {
+        let calc = RpnCalculator::<f64>::new(false);
+        assert_eq!(calc.eval(input).unwrap_err(), expected);
+    }
+
+    #[rstest]
+    #[case(
+        "9223372036854775807 9223372036854775807 *",
+        "85070591730234615847396907784232501249"
+    )]
+    #[case("-7 2 %", "-1")]
+    #[case("2 3 -", "-1")]
+    fn test_bigint(#[case] input: &str, #[case] expected: &str) {
+        let calc = RpnCalculator::<BigInt>::new(false);
+        assert_eq!(calc.eval(input).unwrap().to_string(), expected);
+    }
+
+    #[test]
+    fn test_trace() {
+        let calc = RpnCalculator::<i32>::new(false);
+        let steps = calc.trace("2 3 +").collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(
+            steps,
+            vec![
+                Step {
+                    index: 0,
+                    token: "2",
+                    span: 0..1,
+                    operation: Operation::Push,
+                    before: vec![],
+                    after: vec![2],
+                },
+                Step {
+                    index: 1,
+                    token: "3",
+                    span: 2..3,
+                    operation: Operation::Push,
+                    before: vec![2],
+                    after: vec![2, 3],
+                },
+                Step {
+                    index: 2,
+                    token: "+",
+                    span: 4..5,
+                    operation: Operation::Add,
+                    before: vec![2, 3],
+                    after: vec![5],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_finish_after_partial_trace() {
+        let calc = RpnCalculator::<i32>::new(false);
+        let mut trace = calc.trace("1 2 + 4 *");
+        assert_eq!(trace.next().unwrap().unwrap().after, vec![1]);
+        assert_eq!(trace.finish(), Ok(12));
+
+        let mut trace = calc.trace("1 2 + 0 / 5");
+        assert!(trace.next().unwrap().is_ok());
+        assert_eq!(
+            trace.finish(),
+            Err(RpnCalculatorError::DivisionByZero { span: 8..9 })
+        );
+    }
+
+    #[test]
+    fn test_trace_stops_at_error() {
+        let calc = RpnCalculator::<i32>::new(false);
+        let mut trace = calc.trace("1 0 / 2");
+        assert!(trace.next().unwrap().is_ok());
+        assert!(trace.next().unwrap().is_ok());
+        assert_eq!(
+            trace.next().unwrap().unwrap_err(),
+            RpnCalculatorError::DivisionByZero { span: 4..5 }
+        );
+        assert!(trace.next().is_none());
+        assert_eq!(
+            trace.finish().unwrap_err(),
+            RpnCalculatorError::DivisionByZero { span: 4..5 }
+        );
+    }
 }
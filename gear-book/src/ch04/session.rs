@@ -0,0 +1,452 @@
+//! HP の RPN 電卓風の対話的なセッション
+//!
+//! 1 行ごとに入力を評価し、スタックとレジスタは行をまたいで保持する。
+//!
+//! * 数値: スタックに積む
+//! * `+ - * / %`: 上の 2 つを取り出して演算し、結果を積む
+//! * `dup drop swap clear`: スタック操作
+//! * `sto NAME`: スタックの一番上の値をレジスタ NAME に保存する (スタックからは取り除かない)
+//! * `rcl NAME`: レジスタ NAME の値を積む
+//!
+//! 次のコマンドは 1 行に単独で書く。
+//!
+//! * `undo` / `redo`: 直前の行の操作を取り消す / やり直す
+//! * `save PATH` / `load PATH`: セッションをファイルに保存する / ファイルから読み込む。
+//!   PATH はコマンドの後から行末までで、空白を含んでもよい
+
+use super::{Number, RpnCalculatorError, Span, Trace};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use thiserror::Error;
+
+/// 取り消せる操作の数の上限
+const HISTORY_LIMIT: usize = 100;
+
+/// セッションの保存形式の 1 行目
+const SESSION_HEADER: &str = "# rpn session";
+
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error(transparent)]
+    Calc(#[from] RpnCalculatorError),
+    #[error("missing register name at {}..{}", span.start, span.end)]
+    MissingRegisterName { span: Span },
+    #[error("unknown register at {}..{}", span.start, span.end)]
+    UnknownRegister { span: Span },
+    #[error("nothing to undo")]
+    NothingToUndo,
+    #[error("nothing to redo")]
+    NothingToRedo,
+    #[error("invalid session file at line {0}")]
+    InvalidSessionFile(usize),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl SessionError {
+    /// エラーの原因となったトークンの区間を返す
+    pub fn span(&self) -> Option<&Span> {
+        use SessionError::*;
+        match self {
+            Calc(e) => Some(e.span()),
+            MissingRegisterName { span } | UnknownRegister { span } => Some(span),
+            _ => None,
+        }
+    }
+}
+
+/// 取り消しのために保存しておく状態
+#[derive(Debug, Clone)]
+struct State<T> {
+    stack: Vec<T>,
+    registers: BTreeMap<String, T>,
+}
+
+impl<T: Number> State<T> {
+    /// 同じ状態かどうかを返す。NaN は NaN と同じ値とみなす
+    fn same(&self, other: &Self) -> bool {
+        self.stack.len() == other.stack.len()
+            && self
+                .stack
+                .iter()
+                .zip(&other.stack)
+                .all(|(x, y)| x.same_value(y))
+            && self.registers.len() == other.registers.len()
+            && self
+                .registers
+                .iter()
+                .zip(&other.registers)
+                .all(|((k, x), (l, y))| k == l && x.same_value(y))
+    }
+}
+
+/// 対話的なセッション
+#[derive(Debug)]
+pub struct Session<T> {
+    state: State<T>,
+    undo: Vec<State<T>>,
+    redo: Vec<State<T>>,
+}
+
+impl<T: Number> Default for Session<T> {
+    fn default() -> Self {
+        Self {
+            state: State {
+                stack: Vec::new(),
+                registers: BTreeMap::new(),
+            },
+            undo: Vec::new(),
+            redo: Vec::new(),
+        }
+    }
+}
+
+impl<T: Number> Session<T> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// スタックを底から順に返す
+    pub fn stack(&self) -> &[T] {
+        &self.state.stack
+    }
+
+    /// レジスタを名前順に返す
+    pub fn registers(&self) -> &BTreeMap<String, T> {
+        &self.state.registers
+    }
+
+    /// 1 行分の入力を実行する
+    ///
+    /// エラーが起きた場合、状態はその行を実行する前に戻る。
+    pub fn execute(&mut self, line: &str) -> Result<(), SessionError> {
+        let mut command = line.trim().splitn(2, char::is_whitespace);
+        match (command.next(), command.next().map(str::trim_start)) {
+            (Some("save"), Some(path)) => return self.save(path),
+            (Some("load"), Some(path)) => {
+                let state = Self::read(path)?;
+                self.push_history(state);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        let tokens = tokenize(line);
+        match tokens.as_slice() {
+            [("undo", _)] => return self.undo(),
+            [("redo", _)] => return self.redo(),
+            [] => return Ok(()),
+            _ => {}
+        }
+
+        // 数値と演算子は RpnCalculator と同じく Trace::step で評価する
+        let State {
+            stack,
+            mut registers,
+        } = self.state.clone();
+        let mut trace = Trace::resume(stack);
+        let mut tokens = tokens.into_iter();
+        while let Some((token, span)) = tokens.next() {
+            match token {
+                "sto" | "rcl" => {
+                    let (name, name_span) = tokens
+                        .next()
+                        .ok_or(SessionError::MissingRegisterName { span: span.clone() })?;
+                    if token == "sto" {
+                        let (x, _) = trace
+                            .stack
+                            .last()
+                            .ok_or(RpnCalculatorError::StackUnderflow { span })?;
+                        registers.insert(name.to_string(), x.clone());
+                    } else {
+                        let x = registers
+                            .get(name)
+                            .ok_or(SessionError::UnknownRegister { span: name_span })?;
+                        trace.stack.push((x.clone(), span));
+                    }
+                }
+                "dup" | "drop" | "swap" | "clear" => stack_word(&mut trace.stack, token, span)?,
+                _ => {
+                    trace.step(token, &span)?;
+                }
+            }
+        }
+
+        let state = State {
+            stack: trace.into_values(),
+            registers,
+        };
+        if !state.same(&self.state) {
+            self.push_history(state);
+        }
+        Ok(())
+    }
+
+    fn push_history(&mut self, state: State<T>) {
+        let prev = std::mem::replace(&mut self.state, state);
+        self.undo.push(prev);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+    }
+
+    fn undo(&mut self) -> Result<(), SessionError> {
+        let prev = self.undo.pop().ok_or(SessionError::NothingToUndo)?;
+        let current = std::mem::replace(&mut self.state, prev);
+        self.redo.push(current);
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<(), SessionError> {
+        let next = self.redo.pop().ok_or(SessionError::NothingToRedo)?;
+        let current = std::mem::replace(&mut self.state, next);
+        self.undo.push(current);
+        Ok(())
+    }
+
+    /// スタックとレジスタを path に保存する
+    ///
+    /// 1 行目は見出しで、続いて `stack` の行にスタックの値を底から順に、
+    /// `reg NAME VALUE` の行にレジスタを 1 つずつ書く。
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), SessionError> {
+        let stack = self
+            .state
+            .stack
+            .iter()
+            .map(|x| format!(" {}", x))
+            .collect::<String>();
+        let mut content = format!("{}\nstack{}\n", SESSION_HEADER, stack);
+        for (name, x) in &self.state.registers {
+            content.push_str(&format!("reg {} {}\n", name, x));
+        }
+        fs::write(path, content)?;
+        Ok(())
+    }
+
+    /// [`Session::save`] で保存したセッションを読み込む
+    ///
+    /// 読み込んだ状態は取り消しの履歴を持たない。
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, SessionError> {
+        Ok(Self {
+            state: Self::read(path)?,
+            ..Self::default()
+        })
+    }
+
+    fn read(path: impl AsRef<Path>) -> Result<State<T>, SessionError> {
+        let content = fs::read_to_string(path)?;
+        let mut lines = content.lines().enumerate();
+        if lines.next().map(|(_, line)| line) != Some(SESSION_HEADER) {
+            return Err(SessionError::InvalidSessionFile(1));
+        }
+
+        let mut stack = Vec::new();
+        let mut registers = BTreeMap::new();
+        for (i, line) in lines {
+            let invalid = || SessionError::InvalidSessionFile(i + 1);
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("stack") => {
+                    for word in words {
+                        stack.push(word.parse().map_err(|_| invalid())?);
+                    }
+                }
+                Some("reg") => match (words.next(), words.next(), words.next()) {
+                    (Some(name), Some(x), None) => {
+                        let x = x.parse().map_err(|_| invalid())?;
+                        registers.insert(name.to_string(), x);
+                    }
+                    _ => return Err(invalid()),
+                },
+                None => {}
+                Some(_) => return Err(invalid()),
+            }
+        }
+
+        Ok(State { stack, registers })
+    }
+}
+
+/// line を空白で区切り、各トークンとその区間の組にする
+fn tokenize(line: &str) -> Vec<(&str, Span)> {
+    line.split_whitespace()
+        .map(|token| {
+            let start = token.as_ptr() as usize - line.as_ptr() as usize;
+            (token, start..start + token.len())
+        })
+        .collect()
+}
+
+/// スタック操作のワード word を実行する
+fn stack_word<T: Clone>(
+    stack: &mut Vec<(T, Span)>,
+    word: &str,
+    span: Span,
+) -> Result<(), RpnCalculatorError> {
+    let needed = match word {
+        "dup" | "drop" => 1,
+        "swap" => 2,
+        _ => 0,
+    };
+    let len = stack.len();
+    if len < needed {
+        return Err(RpnCalculatorError::StackUnderflow { span });
+    }
+    match word {
+        "dup" => stack.push(stack[len - 1].clone()),
+        "drop" => {
+            stack.pop();
+        }
+        "swap" => stack.swap(len - 2, len - 1),
+        _ => stack.clear(),
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stack_persists_across_lines() {
+        let mut session = Session::<i64>::new();
+        session.execute("1 2").unwrap();
+        session.execute("+").unwrap();
+        session.execute("10 *").unwrap();
+        assert_eq!(session.stack(), &[30]);
+    }
+
+    #[test]
+    fn registers() {
+        let mut session = Session::<i64>::new();
+        session.execute("3 sto a drop 4 rcl a *").unwrap();
+        assert_eq!(session.stack(), &[12]);
+        assert_eq!(session.registers()["a"], 3);
+
+        assert!(matches!(
+            session.execute("rcl b"),
+            Err(SessionError::UnknownRegister { span }) if span == (4..5)
+        ));
+        assert!(matches!(
+            session.execute("sto"),
+            Err(SessionError::MissingRegisterName { .. })
+        ));
+    }
+
+    #[test]
+    fn error_restores_state() {
+        let mut session = Session::<i64>::new();
+        session.execute("1 2").unwrap();
+        assert!(session.execute("3 0 /").is_err());
+        assert_eq!(session.stack(), &[1, 2]);
+    }
+
+    #[test]
+    fn undo_and_redo() {
+        let mut session = Session::<i64>::new();
+        session.execute("1 2").unwrap();
+        session.execute("+").unwrap();
+        session.execute("undo").unwrap();
+        assert_eq!(session.stack(), &[1, 2]);
+        session.execute("undo").unwrap();
+        assert_eq!(session.stack(), &[] as &[i64]);
+        assert!(matches!(
+            session.execute("undo"),
+            Err(SessionError::NothingToUndo)
+        ));
+
+        session.execute("redo").unwrap();
+        session.execute("redo").unwrap();
+        assert_eq!(session.stack(), &[3]);
+        assert!(matches!(
+            session.execute("redo"),
+            Err(SessionError::NothingToRedo)
+        ));
+
+        session.execute("undo").unwrap();
+        session.execute("dup").unwrap();
+        assert!(session.execute("redo").is_err());
+        assert_eq!(session.stack(), &[1, 2, 2]);
+    }
+
+    #[test]
+    fn arithmetic_errors_match_calculator() {
+        let mut session = Session::<i32>::new();
+        assert!(matches!(
+            session.execute("2147483647 1 +"),
+            Err(SessionError::Calc(RpnCalculatorError::Overflow { span })) if span == (13..14)
+        ));
+        assert!(matches!(
+            session.execute("1 0 %"),
+            Err(SessionError::Calc(RpnCalculatorError::DivisionByZero { span })) if span == (4..5)
+        ));
+        assert!(matches!(
+            session.execute("1 ^"),
+            Err(SessionError::Calc(RpnCalculatorError::UnknownOperator { span })) if span == (2..3)
+        ));
+        assert!(matches!(
+            session.execute("1 swap"),
+            Err(SessionError::Calc(RpnCalculatorError::StackUnderflow { span })) if span == (2..6)
+        ));
+        assert_eq!(session.stack(), &[] as &[i32]);
+    }
+
+    #[test]
+    fn nan_does_not_record_history() {
+        let path = std::env::temp_dir().join(format!("rpn-session-nan-{}.txt", std::process::id()));
+        fs::write(&path, "# rpn session\nstack NaN\n").unwrap();
+
+        let mut session = Session::<f64>::new();
+        session.execute("1 drop").unwrap();
+        session
+            .execute(&format!("load {}", path.display()))
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+        assert!(session.stack()[0].is_nan());
+
+        // 状態を変えない行は履歴に残らないので、1 回の undo で load の前に戻る
+        session.execute("1 drop").unwrap();
+        session.execute("dup drop").unwrap();
+        session.execute("undo").unwrap();
+        assert_eq!(session.stack(), &[] as &[f64]);
+        assert!(matches!(
+            session.execute("undo"),
+            Err(SessionError::NothingToUndo)
+        ));
+    }
+
+    #[test]
+    fn save_and_load() {
+        // パスに空白を含んでいても、行末までをパスとして読む
+        let path = std::env::temp_dir().join(format!("rpn session {}.txt", std::process::id()));
+
+        let mut session = Session::<f64>::new();
+        session.execute("1.5 sto x 2.25").unwrap();
+        session
+            .execute(&format!("save  {}", path.display()))
+            .unwrap();
+
+        let loaded = Session::<f64>::load(&path).unwrap();
+        assert_eq!(loaded.stack(), &[1.5, 2.25]);
+        assert_eq!(loaded.registers()["x"], 1.5);
+
+        let mut session = Session::<f64>::new();
+        session.execute("7").unwrap();
+        session
+            .execute(&format!("load {}", path.display()))
+            .unwrap();
+        assert_eq!(session.stack(), &[1.5, 2.25]);
+        session.execute("undo").unwrap();
+        assert_eq!(session.stack(), &[7.0]);
+
+        fs::write(&path, "# rpn session\nstack 1 x\n").unwrap();
+        assert!(matches!(
+            Session::<f64>::load(&path),
+            Err(SessionError::InvalidSessionFile(2))
+        ));
+        fs::remove_file(&path).unwrap();
+    }
+}